@@ -1,11 +1,15 @@
-use std::{borrow::Cow, io::IsTerminal, sync::Arc, time::Duration};
+use std::{borrow::Cow, io::IsTerminal, sync::Arc};
 
 use ident::Id;
 use mess::{
-    db::{rocks::db::DB, svc::ActorHandle, Message},
+    db::{
+        rocks::{db::DB, storage::RocksStorage},
+        svc::ActorHandle,
+        Message,
+    },
     ecs::{
         streams::StreamName, ApplyEvents, Component, ComponentStore, Entity,
-        EventDB,
+        EventDB, ExpectedVersion,
     },
 };
 use tracing::{error, info, warn};
@@ -144,7 +148,7 @@ async fn main() {
     configure_logging();
 
     let db = DB::new("xyz").unwrap();
-    let handle = ActorHandle::new(db);
+    let handle = ActorHandle::new(RocksStorage::new(db)).unwrap();
     let evdb = EventDB::new(handle.clone());
     let evdb = Arc::new(evdb);
     let post_store = ComponentStore::<PostData, _>::new(Arc::clone(&evdb));
@@ -157,7 +161,7 @@ async fn main() {
         status: PostStatus::Visible,
     });
     let position = evdb
-        .put(stream.source(), &event, None)
+        .put(stream.source(), &event, ExpectedVersion::NoStream)
         .await
         .expect("complete failure");
     info!(?position, "wrote thing");
@@ -166,8 +170,5 @@ async fn main() {
     info!(?post, "post");
 
     // shut down
-    tokio::time::sleep(Duration::from_secs(1)).await;
-    handle.kill();
-    // loop {}
-    // jh.join();
+    handle.shutdown().await.expect("failed to close the db");
 }
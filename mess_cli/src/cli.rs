@@ -0,0 +1,418 @@
+//! The command line.
+
+use std::{
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{arg, value_parser, Arg, ArgGroup, ArgMatches, Command};
+use ident::Id;
+
+use crate::{
+    error::{Error, Result},
+    inspect,
+    read::{self, Format, Scope, PAGE_SIZE},
+    source::Source,
+    store::{Backend, Store},
+    write::{self, parse_expected_version, Append},
+};
+
+/// Adds `--stream` and `--category`, which pick what to read.
+fn scoped(cmd: Command) -> Command {
+    cmd.arg(arg!(-s --stream <STREAM> "Only read this stream"))
+        .arg(arg!(-c --category <CATEGORY> "Only read this category"))
+        .group(ArgGroup::new("scope").args(["stream", "category"]))
+}
+
+fn format_arg() -> Arg {
+    arg!(--format <FORMAT> "text, or json for a JSON object per line")
+        .value_parser(parsed::<Format>)
+        .default_value("text")
+}
+
+/// Parses with `FromStr`, for types whose errors clap can't show itself.
+fn parsed<T: std::str::FromStr>(s: &str) -> std::result::Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    s.parse().map_err(|err: T::Err| err.to_string())
+}
+
+fn from_arg() -> Arg {
+    arg!(--from <POSITION> "A stream position in a stream, else a global one")
+        .value_parser(value_parser!(u64))
+}
+
+fn limit_arg(help: &'static str) -> Arg {
+    arg!(-n --limit <N>).help(help).value_parser(value_parser!(usize))
+}
+
+fn streams_command() -> Command {
+    Command::new("streams")
+        .about("Lists stream names")
+        .arg(arg!([PREFIX] "Only list streams starting with this"))
+        .arg(arg!(-c --category <CATEGORY> "Only list this category"))
+        .group(ArgGroup::new("names").args(["PREFIX", "category"]))
+        .arg(limit_arg("List at most this many"))
+        .arg(arg!(-l --long "Add positions and counts"))
+}
+
+fn read_command() -> Command {
+    scoped(Command::new("read"))
+        .about("Prints messages from the global log, a stream or a category")
+        .arg(from_arg())
+        .arg(limit_arg("Print at most this many"))
+        .arg(arg!(-b --backwards "Read newest first"))
+        .arg(format_arg())
+}
+
+fn tail_command() -> Command {
+    scoped(Command::new("tail"))
+        .about("Prints the newest messages")
+        .arg(
+            arg!(-n --lines <N> "How many of the newest messages to print")
+                .value_parser(value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(arg!(-f --follow "Keep printing messages as they are written"))
+        .arg(
+            arg!(--interval <MS> "How often to check for new messages")
+                .value_parser(value_parser!(u64))
+                .default_value("500"),
+        )
+        .arg(format_arg())
+}
+
+fn write_command() -> Command {
+    Command::new("write")
+        .about("Appends a message and prints its position")
+        .arg(arg!(<STREAM> "The stream to append to"))
+        .arg(arg!(<TYPE> "The message type"))
+        .arg(arg!([DATA] "The data as JSON, read from stdin when missing or -"))
+        .arg(arg!(-m --metadata <JSON> "The metadata as JSON"))
+        .arg(
+            arg!(-e --"expected-version" <VERSION>)
+                .help("any, no_stream, stream_exists or the last position")
+                .value_parser(|s: &str| {
+                    parse_expected_version(s).map_err(|err| err.to_string())
+                })
+                .default_value("any"),
+        )
+        .arg(
+            arg!(--id <ID> "The message id, generated when missing")
+                .value_parser(|s: &str| {
+                    s.parse::<Id>().map_err(|_| format!("invalid id {s}"))
+                }),
+        )
+}
+
+fn export_command() -> Command {
+    scoped(Command::new("export"))
+        .about("Writes messages as JSON lines, oldest first")
+        .arg(from_arg())
+        .arg(
+            arg!(-o --output <FILE> "Write here instead of stdout")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+fn import_command() -> Command {
+    Command::new("import")
+        .about("Appends an export's messages at their stream positions")
+        .arg(
+            arg!([FILE] "The export, read from stdin when missing or -")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+#[cfg(feature = "tui")]
+fn tui_command() -> Command {
+    Command::new("tui")
+        .about("Browses the store read-only, next to a service writing to it")
+        .arg(
+            arg!(--interval <MS> "How often to check for new messages")
+                .value_parser(value_parser!(u64))
+                .default_value("500"),
+        )
+}
+
+#[must_use]
+pub fn command() -> Command {
+    let command = Command::new("mess")
+        .about("Operates a mess store")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg(
+            arg!(-d --db <PATH> "A RocksDB directory or SQLite file")
+                .value_parser(value_parser!(PathBuf))
+                .global(true),
+        )
+        .arg(
+            arg!(--backend <BACKEND> "rocksdb or sqlite, else told from --db")
+                .value_parser(parsed::<Backend>)
+                .global(true),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Shows the head position, stream count and size"),
+        )
+        .subcommand(streams_command())
+        .subcommand(read_command())
+        .subcommand(tail_command())
+        .subcommand(write_command())
+        .subcommand(export_command())
+        .subcommand(import_command())
+        .subcommand(
+            Command::new("verify")
+                .about("Checks that the log and the stream summaries agree"),
+        )
+        .subcommand(
+            Command::new("backup")
+                .about("Copies the store to a path which doesn't exist yet")
+                .arg(
+                    arg!(<DEST> "Where to put the copy")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        );
+    #[cfg(feature = "tui")]
+    let command = command.subcommand(tui_command());
+    command
+}
+
+fn scope(matches: &ArgMatches) -> Scope {
+    if let Some(stream) = matches.get_one::<String>("stream") {
+        Scope::Stream(stream.clone())
+    } else if let Some(category) = matches.get_one::<String>("category") {
+        Scope::Category(category.clone())
+    } else {
+        Scope::All
+    }
+}
+
+/// Reads `path`, or stdin when it's missing or `-`.
+fn input(path: Option<&PathBuf>) -> Result<Box<dyn BufRead>> {
+    match path {
+        Some(path) if path.as_os_str() != "-" => {
+            Ok(Box::new(BufReader::new(std::fs::File::open(path)?)))
+        }
+        _ => Ok(Box::new(std::io::stdin().lock())),
+    }
+}
+
+/// Commands which open the store to write to it. The others open it
+/// read-only.
+const WRITING: [&str; 3] = ["write", "import", "backup"];
+
+/// Runs the command on the store. Commands which write open the store and
+/// close it afterwards even if the command failed, and the others open it
+/// read-only.
+pub async fn run(matches: &ArgMatches) -> Result<ExitCode> {
+    let path = matches
+        .get_one::<PathBuf>("db")
+        .ok_or_else(|| Error::Usage("--db is required".into()))?;
+    let backend = matches.get_one::<Backend>("backend").copied();
+    #[cfg(feature = "tui")]
+    if let Some(("tui", matches)) = matches.subcommand() {
+        let ms = *matches.get_one::<u64>("interval").expect("has a default");
+        let path = path.clone();
+        return tokio::task::spawn_blocking(move || {
+            crate::tui::run(&path, backend, Duration::from_millis(ms))
+        })
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+        .map(|()| ExitCode::SUCCESS);
+    }
+    let writing =
+        matches.subcommand_name().is_some_and(|name| WRITING.contains(&name));
+    if !writing {
+        let source = Source::open(path, backend)?;
+        return tokio::select! {
+            res = reads(&source, matches) => res,
+            _ = tokio::signal::ctrl_c() => Ok(ExitCode::SUCCESS),
+        };
+    }
+    let store = Store::open(path, backend)?;
+    let res = tokio::select! {
+        res = writes(&store, matches) => res,
+        _ = tokio::signal::ctrl_c() => Ok(ExitCode::SUCCESS),
+    };
+    let closed = store.close().await;
+    let code = res?;
+    closed?;
+    Ok(code)
+}
+
+async fn reads(source: &Source, matches: &ArgMatches) -> Result<ExitCode> {
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    match matches.subcommand() {
+        Some(("info", _)) => inspect::info(source, &mut out)?,
+        Some(("streams", matches)) => {
+            let prefix = match matches.get_one::<String>("category") {
+                Some(category) => format!("{category}-"),
+                None => matches
+                    .get_one::<String>("PREFIX")
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            let limit = matches.get_one::<usize>("limit").copied();
+            let long = matches.get_flag("long");
+            inspect::streams(source, &mut out, &prefix, limit, long)?;
+        }
+        Some(("read", matches)) => {
+            let from = matches.get_one::<u64>("from").copied();
+            let limit = matches.get_one::<usize>("limit").copied();
+            let page = limit.unwrap_or(PAGE_SIZE).clamp(1, PAGE_SIZE);
+            let cursor = scope(matches).cursor(
+                from,
+                page,
+                matches.get_flag("backwards"),
+            )?;
+            let format =
+                *matches.get_one::<Format>("format").expect("has a default");
+            read::read(source, &mut out, cursor, limit, format)?;
+        }
+        Some(("tail", matches)) => {
+            let count =
+                *matches.get_one::<usize>("lines").expect("has a default");
+            let follow = matches.get_flag("follow").then(|| {
+                let ms =
+                    *matches.get_one::<u64>("interval").expect("has a default");
+                Duration::from_millis(ms)
+            });
+            let format =
+                *matches.get_one::<Format>("format").expect("has a default");
+            read::tail(
+                source,
+                &mut out,
+                &scope(matches),
+                count,
+                follow,
+                format,
+            )
+            .await?;
+        }
+        Some(("export", matches)) => {
+            let from = matches.get_one::<u64>("from").copied();
+            let cursor = scope(matches).cursor(from, PAGE_SIZE, false)?;
+            let count = match matches.get_one::<PathBuf>("output") {
+                Some(path) => {
+                    let mut file = BufWriter::new(std::fs::File::create(path)?);
+                    read::export(source, &mut file, cursor)?
+                }
+                None => read::export(source, &mut out, cursor)?,
+            };
+            eprintln!("exported {count} messages");
+        }
+        Some(("verify", _)) => {
+            let report = inspect::verify(source)?;
+            for problem in &report.problems {
+                writeln!(out, "{problem}")?;
+            }
+            writeln!(
+                out,
+                "{} messages in {} streams, {} problems",
+                report.messages,
+                report.streams,
+                report.problems.len()
+            )?;
+            out.flush()?;
+            if !report.problems.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        _ => unreachable!("clap should ensure we don't get here"),
+    }
+    out.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn writes(store: &Store, matches: &ArgMatches) -> Result<ExitCode> {
+    let db = &store.handle;
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    match matches.subcommand() {
+        Some(("write", matches)) => {
+            let data = match matches.get_one::<String>("DATA") {
+                Some(data) if data != "-" => data.clone(),
+                _ => {
+                    let mut data = String::new();
+                    std::io::stdin().read_to_string(&mut data)?;
+                    data
+                }
+            };
+            let append = Append {
+                stream: matches
+                    .get_one::<String>("STREAM")
+                    .expect("is required"),
+                message_type: matches
+                    .get_one::<String>("TYPE")
+                    .expect("is required"),
+                data: &data,
+                metadata: matches
+                    .get_one::<String>("metadata")
+                    .map(String::as_str),
+                expected_version: *matches
+                    .get_one("expected-version")
+                    .expect("has a default"),
+                id: matches.get_one::<Id>("id").cloned(),
+            };
+            write::write(db, &mut out, append).await?;
+        }
+        Some(("import", matches)) => {
+            let input = input(matches.get_one::<PathBuf>("FILE"))?;
+            let count = write::import(db, input).await?;
+            eprintln!("imported {count} messages");
+        }
+        Some(("backup", matches)) => {
+            let dest = matches.get_one::<PathBuf>("DEST").expect("is required");
+            db.backup(dest).await?;
+            eprintln!("backed up to {}", dest.display());
+        }
+        _ => unreachable!("clap should ensure we don't get here"),
+    }
+    out.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn the_command_is_well_formed() {
+        command().debug_assert();
+    }
+
+    #[test]
+    fn arguments_parse_into_their_types() {
+        let matches = command()
+            .try_get_matches_from([
+                "mess",
+                "write",
+                "account-1",
+                "Opened",
+                "{}",
+                "--db",
+                "data",
+                "-e",
+                "3",
+            ])
+            .unwrap();
+        let (_, write) = matches.subcommand().unwrap();
+        let expected = mess_db::write::ExpectedVersion::Exact(
+            mess_db::StreamPos::Sequential(3),
+        );
+        assert!(write.get_one("expected-version") == Some(&expected));
+        assert!(
+            matches.get_one::<PathBuf>("db").unwrap() == &PathBuf::from("data")
+        );
+
+        let both =
+            ["mess", "-d", "data", "read", "-s", "account-1", "-c", "account"];
+        assert!(command().try_get_matches_from(both).is_err());
+        let format = ["mess", "-d", "data", "read", "--format", "yaml"];
+        assert!(command().try_get_matches_from(format).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use mess_db::error::Error as DbError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The command line asked for something which can't be done.
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("line {line}: {reason}")]
+    Import { line: usize, reason: String },
+}
+
+pub type Result<T> = ::core::result::Result<T, Error>;
+
+impl Error {
+    /// Whether the output was closed early, such as by `mess read | head`,
+    /// which isn't worth reporting.
+    #[must_use]
+    pub fn is_broken_pipe(&self) -> bool {
+        let Self::Io(err) = self else { return false };
+        err.kind() == std::io::ErrorKind::BrokenPipe
+    }
+}
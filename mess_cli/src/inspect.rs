@@ -0,0 +1,214 @@
+//! Looking over a store.
+
+use std::{collections::HashMap, io::Write};
+
+use mess_db::{
+    read::{GetMessages, IntoCursor},
+    StreamPos,
+};
+
+use crate::{error::Result, read::PAGE_SIZE, source::Source};
+
+/// Returns every stream name starting with `prefix`.
+fn all_streams(source: &Source, prefix: &str) -> Result<Vec<String>> {
+    let mut names: Vec<String> = vec![];
+    loop {
+        let after = names.last().map(String::as_str);
+        let page = source.list_streams(prefix, after, PAGE_SIZE)?;
+        let done = page.len() < PAGE_SIZE;
+        names.extend(page);
+        if done {
+            return Ok(names);
+        }
+    }
+}
+
+/// Prints the store's backend, head position, stream count and size.
+pub fn info(source: &Source, out: &mut impl Write) -> Result<()> {
+    let head = source.head()?;
+    let streams = all_streams(source, "")?.len();
+    writeln!(out, "path\t{}", source.path().display())?;
+    writeln!(out, "backend\t{}", source.backend())?;
+    writeln!(out, "head\t{head}")?;
+    writeln!(out, "streams\t{streams}")?;
+    writeln!(out, "size\t{}", source.size()?)?;
+    Ok(())
+}
+
+/// Prints the names of the streams starting with `prefix`, with their
+/// position, message count and last global position when `long`.
+pub fn streams(
+    source: &Source,
+    out: &mut impl Write,
+    prefix: &str,
+    limit: Option<usize>,
+    long: bool,
+) -> Result<()> {
+    let mut names = all_streams(source, prefix)?;
+    names.truncate(limit.unwrap_or(usize::MAX));
+    for name in names {
+        if !long {
+            writeln!(out, "{name}")?;
+            continue;
+        }
+        // A stream may have been listed just before it was written to, but
+        // never loses messages.
+        let Some(info) = source.stream_info(&name)? else {
+            continue;
+        };
+        writeln!(
+            out,
+            "{name}\t{}\t{}\t{}",
+            info.position.position(),
+            info.count,
+            info.last_global
+        )?;
+    }
+    Ok(())
+}
+
+/// What a stream looked like in the global log.
+struct Seen {
+    position: StreamPos,
+    count: u64,
+    last_global: u64,
+}
+
+/// What [`verify`] found.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub messages: u64,
+    pub streams: u64,
+    pub problems: Vec<String>,
+}
+
+/// Reads the whole global log, checking that global positions increase and
+/// each stream's positions follow on from one another, then checks that
+/// every stream's summary and listing agree with the log.
+pub fn verify(source: &Source) -> Result<Report> {
+    let mut report = Report::default();
+    let mut seen: HashMap<String, Seen> = HashMap::new();
+    let mut last_global = 0;
+    let read = GetMessages::default().from_global(0).with_limit(PAGE_SIZE);
+    for msg in source.messages(read.into_cursor()?) {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(err) => {
+                report.problems.push(format!(
+                    "reading after global position {last_global}: {err}"
+                ));
+                break;
+            }
+        };
+        report.messages += 1;
+        let global = msg.global_position;
+        if global <= last_global {
+            report.problems.push(format!(
+                "global position {global} follows {last_global}"
+            ));
+        }
+        last_global = global;
+        let expected = seen
+            .get(&msg.stream_name)
+            .map_or(StreamPos::Sequential(0), |seen| seen.position.next());
+        if msg.stream_position != expected {
+            report.problems.push(format!(
+                "{} at global position {global} is at {:?}, expected {:?}",
+                msg.stream_name, msg.stream_position, expected
+            ));
+        }
+        let stream = seen.entry(msg.stream_name).or_insert(Seen {
+            position: msg.stream_position,
+            count: 0,
+            last_global: global,
+        });
+        stream.position = msg.stream_position;
+        stream.count += 1;
+        stream.last_global = global;
+    }
+
+    let listed = all_streams(source, "")?;
+    report.streams = listed.len() as u64;
+    for name in &listed {
+        let Some(stream) = seen.remove(name) else {
+            report
+                .problems
+                .push(format!("{name} is listed but not in the log"));
+            continue;
+        };
+        let Some(info) = source.stream_info(name)? else {
+            report.problems.push(format!("{name} has no summary"));
+            continue;
+        };
+        if (info.position, info.count, info.last_global)
+            != (stream.position, stream.count, stream.last_global)
+        {
+            report.problems.push(format!(
+                "{name} is summarized at {:?} with {} messages up to global \
+                 position {}, but the log has it at {:?} with {} up to {}",
+                info.position,
+                info.count,
+                info.last_global,
+                stream.position,
+                stream.count,
+                stream.last_global
+            ));
+        }
+    }
+    let mut unlisted: Vec<_> = seen.into_keys().collect();
+    unlisted.sort();
+    for name in unlisted {
+        report.problems.push(format!("{name} is in the log but not listed"));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{store::test::new_store, write::test::put};
+    use assert2::assert;
+    use std::path::Path;
+
+    fn source(path: &Path) -> Source {
+        Source::open(path, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn info_counts_the_streams() {
+        let store = new_store();
+        put(&store.handle, "account-1", 2).await;
+        put(&store.handle, "account-2", 1).await;
+        let mut out = vec![];
+        info(&source(&store.path), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("backend\trocksdb\nhead\t3\nstreams\t2\n"));
+    }
+
+    #[tokio::test]
+    async fn streams_are_listed_by_prefix() {
+        let store = new_store();
+        for stream in ["account-2", "billing-1", "account-1"] {
+            put(&store.handle, stream, 1).await;
+        }
+        put(&store.handle, "account-2", 1).await;
+        let source = source(&store.path);
+        let mut out = vec![];
+        streams(&source, &mut out, "account-", None, true).unwrap();
+        assert!(out == b"account-1\t0\t1\t3\naccount-2\t1\t2\t4\n");
+        let mut out = vec![];
+        streams(&source, &mut out, "", Some(1), false).unwrap();
+        assert!(out == b"account-1\n");
+    }
+
+    #[tokio::test]
+    async fn a_consistent_store_verifies() {
+        let store = new_store();
+        put(&store.handle, "account-1", 3).await;
+        put(&store.handle, "billing-1", 2).await;
+        let report = verify(&source(&store.path)).unwrap();
+        assert!(report.messages == 5);
+        assert!(report.streams == 2);
+        assert!(report.problems.is_empty());
+    }
+}
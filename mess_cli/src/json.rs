@@ -0,0 +1,168 @@
+//! Messages as JSON, one object per line.
+//!
+//! Data and metadata are bytes to the store. They're written out as JSON
+//! when the stored bytes are JSON exactly as serde_json writes it, so that
+//! importing them again stores the same bytes, and as base64 otherwise.
+
+use std::borrow::Cow;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ident::Id;
+use mess_db::{
+    write::{ExpectedVersion, WriteMessage},
+    OwnedMessage, Position, StreamPos,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MessageLine {
+    pub global_position: u64,
+    pub stream_position: u64,
+    pub stream_name: String,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_base64: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_base64: Option<String>,
+}
+
+/// Splits stored bytes into their JSON or, failing that, base64 form.
+fn encoded(bytes: &[u8]) -> (Option<Value>, Option<String>) {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(json) if serde_json::to_vec(&json).is_ok_and(|out| out == bytes) => {
+            (Some(json), None)
+        }
+        _ => (None, Some(STANDARD.encode(bytes))),
+    }
+}
+
+/// Returns the bytes stored for a payload given as JSON or as base64.
+fn decoded(
+    name: &str,
+    json: Option<Value>,
+    base64: Option<String>,
+) -> Result<Vec<u8>> {
+    match (json, base64) {
+        (Some(_), Some(_)) => Err(Error::Usage(format!(
+            "give either {name} or {name}_base64, not both"
+        ))),
+        (Some(json), None) => Ok(json.to_string().into_bytes()),
+        (None, Some(base64)) => STANDARD
+            .decode(base64)
+            .map_err(|err| Error::Usage(format!("{name}_base64: {err}"))),
+        (None, None) => Ok(vec![]),
+    }
+}
+
+impl From<OwnedMessage> for MessageLine {
+    fn from(msg: OwnedMessage) -> Self {
+        let (data, data_base64) = encoded(&msg.data);
+        let (metadata, metadata_base64) = match msg.metadata.as_deref() {
+            None | Some([]) => (None, None),
+            Some(metadata) => encoded(metadata),
+        };
+        Self {
+            global_position: msg.global_position,
+            stream_position: msg.stream_position.position(),
+            stream_name: msg.stream_name,
+            message_type: msg.message_type,
+            data,
+            data_base64,
+            metadata,
+            metadata_base64,
+        }
+    }
+}
+
+impl MessageLine {
+    /// Returns the message to write it again under a new id, expecting its
+    /// stream to end right before it, so that importing twice fails rather
+    /// than duplicating it.
+    pub fn into_write(self) -> Result<WriteMessage<'static>> {
+        let expected_version = match self.stream_position {
+            0 => ExpectedVersion::NoStream,
+            pos => ExpectedVersion::Exact(StreamPos::Sequential(pos - 1)),
+        };
+        Ok(WriteMessage {
+            id: Id::new(),
+            data: decoded("data", self.data, self.data_base64)?.into(),
+            metadata: decoded("metadata", self.metadata, self.metadata_base64)?
+                .into(),
+            stream_name: Cow::Owned(self.stream_name),
+            message_type: Cow::Owned(self.message_type),
+            expected_version,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+pub struct PositionLine {
+    pub global_position: u64,
+    pub stream_position: u64,
+}
+
+impl From<Position> for PositionLine {
+    fn from(pos: Position) -> Self {
+        Self {
+            global_position: pos.global,
+            stream_position: pos.stream.position(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+    use serde_json::json;
+
+    fn message(data: &[u8], metadata: Option<&[u8]>) -> OwnedMessage {
+        OwnedMessage {
+            global_position: 3,
+            stream_position: StreamPos::Sequential(1),
+            stream_name: "account-1".into(),
+            message_type: "Opened".into(),
+            data: data.to_vec(),
+            metadata: metadata.map(<[u8]>::to_vec),
+        }
+    }
+
+    #[test]
+    fn only_exact_json_is_written_as_json() {
+        let line = MessageLine::from(message(br#"{"a":1}"#, Some(b"{ }")));
+        let json = serde_json::to_value(&line).unwrap();
+        assert!(
+            json == json!({
+                "global_position": 3,
+                "stream_position": 1,
+                "stream_name": "account-1",
+                "type": "Opened",
+                "data": {"a": 1},
+                "metadata_base64": "eyB9",
+            })
+        );
+    }
+
+    #[test]
+    fn lines_import_as_the_same_bytes() {
+        for data in [&br#"{"b":1,"a":[2]}"#[..], b"\xff\x00", b""] {
+            let line = MessageLine::from(message(data, None));
+            let text = serde_json::to_string(&line).unwrap();
+            let line: MessageLine = serde_json::from_str(&text).unwrap();
+            let wm = line.into_write().unwrap();
+            assert!(wm.data.as_ref() == data);
+            assert!(wm.metadata.is_empty());
+            let expected = ExpectedVersion::Exact(StreamPos::Sequential(0));
+            assert!(wm.expected_version == expected);
+        }
+    }
+}
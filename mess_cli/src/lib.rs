@@ -0,0 +1,32 @@
+//! `mess`, a command-line tool for operating a store on either backend.
+//!
+//! Commands which only read open the store read-only, a RocksDB store as a
+//! secondary instance and a SQLite file with a read-only connection, so
+//! they're safe to run next to the service writing to it, and `mess tail
+//! -f` follows its writes. `write`, `import` and `backup` open the store
+//! themselves and need it to themselves: stop the process serving a RocksDB
+//! store before running them.
+//!
+//! ```text
+//! mess -d data/mess info
+//! mess -d data/mess streams -c account -l
+//! mess -d data/mess read -s account-1 --format json
+//! mess -d data/mess tail -c account -f
+//! echo '{"amount": 10}' | mess -d data/mess write account-1 Deposited -e 3
+//! mess -d data/mess export -o account.jsonl -c account
+//! mess -d copy.db --backend sqlite import account.jsonl
+//! mess -d data/mess verify
+//! mess -d data/mess backup data/mess-2024-01-01
+//! mess -d data/mess tui
+//! ```
+
+pub mod cli;
+pub mod error;
+pub mod inspect;
+pub mod json;
+pub mod read;
+pub mod source;
+pub mod store;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod write;
@@ -0,0 +1,277 @@
+//! Printing and exporting messages.
+
+use std::{io::Write, str::FromStr, time::Duration};
+
+use mess_db::{
+    read::{Cursor, GetMessages, IntoCursor},
+    OwnedMessage, StreamPos,
+};
+
+use crate::{
+    error::{Error, Result},
+    json::MessageLine,
+    source::Source,
+};
+
+/// How many messages each read fetches.
+pub const PAGE_SIZE: usize = 1000;
+
+/// Which messages a command reads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    Stream(String),
+    Category(String),
+}
+
+impl Scope {
+    /// Returns the read of `limit` messages from `from`, which is a stream
+    /// position when reading a stream and a global position otherwise.
+    /// Backward reads start at the end without one.
+    pub fn cursor(
+        &self,
+        from: Option<u64>,
+        limit: usize,
+        backwards: bool,
+    ) -> Result<Cursor> {
+        let read = GetMessages::default().with_limit(limit);
+        let read = if backwards { read.backwards() } else { read };
+        let cursor = match (self, from) {
+            (Self::All, None) => read.into_cursor(),
+            (Self::All, Some(pos)) => read.from_global(pos).into_cursor(),
+            (Self::Stream(stream), None) => {
+                read.in_stream(stream).into_cursor()
+            }
+            (Self::Stream(stream), Some(pos)) => read
+                .in_stream(stream)
+                .from_stream_pos(StreamPos::Sequential(pos))
+                .into_cursor(),
+            (Self::Category(category), None) => {
+                read.in_category(category).into_cursor()
+            }
+            (Self::Category(category), Some(pos)) => {
+                read.in_category(category).from_global(pos).into_cursor()
+            }
+        };
+        Ok(cursor?)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Tab-separated global position, stream, stream position, type and
+    /// data, with data which isn't UTF-8 in base64.
+    #[default]
+    Text,
+    /// A [`MessageLine`] per line.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            s => Err(Error::Usage(format!("unknown format {s}"))),
+        }
+    }
+}
+
+impl Format {
+    pub fn write(self, out: &mut impl Write, msg: OwnedMessage) -> Result<()> {
+        match self {
+            Self::Text => {
+                use base64::{engine::general_purpose::STANDARD, Engine};
+
+                let data = match std::str::from_utf8(&msg.data) {
+                    Ok(text) => text.replace(['\n', '\t'], " "),
+                    Err(_) => STANDARD.encode(&msg.data),
+                };
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{data}",
+                    msg.global_position,
+                    msg.stream_name,
+                    msg.stream_position.position(),
+                    msg.message_type,
+                )?;
+            }
+            Self::Json => {
+                serde_json::to_writer(&mut *out, &MessageLine::from(msg))?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes each message until the messages end, returning how many there
+/// were.
+fn write_all(
+    out: &mut impl Write,
+    messages: impl Iterator<Item = Result<OwnedMessage>>,
+    format: Format,
+) -> Result<u64> {
+    let mut count = 0;
+    for msg in messages {
+        format.write(out, msg?)?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// Prints up to `limit` messages, or every one without a limit.
+pub fn read(
+    source: &Source,
+    out: &mut impl Write,
+    cursor: Cursor,
+    limit: Option<usize>,
+    format: Format,
+) -> Result<u64> {
+    let messages = source.messages(cursor);
+    match limit {
+        Some(limit) => write_all(out, messages.take(limit), format),
+        None => write_all(out, messages, format),
+    }
+}
+
+/// Prints the last `count` messages and, when following, every message
+/// written after them, catching up with the writer to check for new ones
+/// every `interval`. Following only ends with an error.
+pub async fn tail(
+    source: &Source,
+    out: &mut impl Write,
+    scope: &Scope,
+    count: usize,
+    follow: Option<Duration>,
+    format: Format,
+) -> Result<()> {
+    let mut last = None;
+    if count > 0 {
+        let messages = source.read(&scope.cursor(None, count, true)?)?;
+        for msg in messages.into_iter().rev() {
+            format.write(out, msg.clone())?;
+            last = Some(msg);
+        }
+        out.flush()?;
+    }
+    let Some(interval) = follow else {
+        return Ok(());
+    };
+    // Carry on right after the last message printed, or after the newest
+    // one if none was.
+    let from = match (&last, scope) {
+        (Some(msg), Scope::Stream(_)) => msg.stream_position.position() + 1,
+        (Some(msg), Scope::All | Scope::Category(_)) => msg.global_position + 1,
+        (None, Scope::Stream(stream)) => source
+            .stream_info(stream)?
+            .map_or(0, |info| info.position.position() + 1),
+        (None, Scope::All | Scope::Category(_)) => source.head()? + 1,
+    };
+    let mut cursor = scope.cursor(Some(from), PAGE_SIZE, false)?;
+    loop {
+        let (messages, next) = source.fetch_page(cursor)?;
+        cursor = next;
+        let count = messages.len();
+        for msg in messages {
+            format.write(out, msg)?;
+        }
+        out.flush()?;
+        if count < PAGE_SIZE {
+            tokio::time::sleep(interval).await;
+            source.refresh()?;
+        }
+    }
+}
+
+/// Writes every message the cursor reads as a [`MessageLine`], returning
+/// how many there were.
+pub fn export(
+    source: &Source,
+    out: &mut impl Write,
+    cursor: Cursor,
+) -> Result<u64> {
+    write_all(out, source.messages(cursor), Format::Json)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::test::new_store;
+    use crate::write::test::put;
+    use assert2::assert;
+    use std::path::Path;
+
+    fn source(path: &Path) -> Source {
+        Source::open(path, None).unwrap()
+    }
+
+    fn lines(out: &[u8]) -> Vec<&str> {
+        std::str::from_utf8(out).unwrap().lines().collect()
+    }
+
+    #[tokio::test]
+    async fn reads_print_a_line_per_message() {
+        let store = new_store();
+        put(&store.handle, "account-1", 3).await;
+        put(&store.handle, "billing-1", 1).await;
+        let source = source(&store.path);
+        let mut out = vec![];
+        let scope = Scope::Stream("account-1".into());
+        let cursor = scope.cursor(Some(1), PAGE_SIZE, false).unwrap();
+        read(&source, &mut out, cursor, None, Format::Text).unwrap();
+        assert!(
+            lines(&out)
+                == [
+                    "2\taccount-1\t1\tCounted\t1",
+                    "3\taccount-1\t2\tCounted\t2"
+                ]
+        );
+
+        let mut out = vec![];
+        let cursor = Scope::All.cursor(None, PAGE_SIZE, true).unwrap();
+        read(&source, &mut out, cursor, Some(1), Format::Json).unwrap();
+        let line: MessageLine = serde_json::from_slice(&out).unwrap();
+        assert!(line.stream_name == "billing-1");
+    }
+
+    #[tokio::test]
+    async fn tails_end_with_the_newest_messages() {
+        let store = new_store();
+        put(&store.handle, "account-1", 3).await;
+        put(&store.handle, "account-2", 1).await;
+        let mut out = vec![];
+        let scope = Scope::Category("account".into());
+        tail(&source(&store.path), &mut out, &scope, 2, None, Format::Text)
+            .await
+            .unwrap();
+        assert!(
+            lines(&out)
+                == [
+                    "3\taccount-1\t2\tCounted\t2",
+                    "4\taccount-2\t0\tCounted\t0"
+                ]
+        );
+    }
+
+    #[tokio::test]
+    async fn following_prints_new_messages() {
+        let store = new_store();
+        put(&store.handle, "account-1", 1).await;
+        let source = source(&store.path);
+        let mut out = vec![];
+        let scope = Scope::Stream("account-1".into());
+        let interval = Some(Duration::from_millis(10));
+        let tailed = tail(&source, &mut out, &scope, 0, interval, Format::Text);
+        let written = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            put(&store.handle, "account-1", 1).await;
+        };
+        let both = futures::future::join(tailed, written);
+        let _ = tokio::time::timeout(Duration::from_millis(200), both).await;
+        assert!(lines(&out) == ["2\taccount-1\t1\tCounted\t1"]);
+    }
+}
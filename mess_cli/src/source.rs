@@ -0,0 +1,417 @@
+//! Reading a store without writing to it, next to the service which does.
+//! Every command but those which write opens the store this way.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use ident::Id;
+use mess_db::{
+    read::{category, Cursor, GetMessages, IntoCursor},
+    rocks::db::DB,
+    storage::StorageReader,
+    OwnedMessage, StreamInfo,
+};
+use serde::Deserialize;
+
+use crate::{
+    error::Result,
+    read::{Scope, PAGE_SIZE},
+    store::{dir_size, Backend},
+};
+
+/// How many categories or streams a listing stops at.
+pub const LIST_LIMIT: usize = 10_000;
+
+/// How many messages either side of a message are searched for its
+/// neighbours.
+pub const NEIGHBOUR_WINDOW: u64 = 1000;
+
+enum Reader {
+    /// A secondary instance, whose files are removed once it's dropped.
+    Rocks { db: Arc<DB>, secondary: PathBuf },
+    #[cfg(feature = "rusqlite")]
+    Sqlite(mess_db::rusqlite::storage::SqliteReader),
+}
+
+/// A read-only view of a store, which may be open for writing by another
+/// process.
+pub struct Source {
+    reader: Reader,
+    backend: Backend,
+    path: PathBuf,
+}
+
+/// The messages linked to one by the `correlationStreamName` and
+/// `causationMessageGlobalPosition` metadata message-db uses.
+#[derive(Debug, Default)]
+pub struct Neighbours {
+    /// The message this one was written in response to.
+    pub cause: Option<OwnedMessage>,
+    /// The messages written in response to this one.
+    pub effects: Vec<OwnedMessage>,
+    /// Other messages with the same correlation stream.
+    pub correlated: Vec<OwnedMessage>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Links {
+    correlation_stream_name: Option<String>,
+    causation_message_global_position: Option<u64>,
+}
+
+impl Links {
+    fn of(msg: &OwnedMessage) -> Self {
+        msg.metadata
+            .as_deref()
+            .and_then(|meta| serde_json::from_slice(meta).ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Source {
+    /// Opens the store at `path` read-only. Without a backend it's told
+    /// from the path.
+    pub fn open(path: &Path, backend: Option<Backend>) -> Result<Self> {
+        let backend = match backend {
+            Some(backend) => backend,
+            None => Backend::detect(path)?,
+        };
+        let reader = match backend {
+            Backend::Rocksdb => {
+                let secondary = std::env::temp_dir()
+                    .join(format!("mess-tui-{}", Id::new()));
+                let db = Arc::new(DB::open_secondary(path, &secondary)?);
+                Reader::Rocks { db, secondary }
+            }
+            #[cfg(feature = "rusqlite")]
+            Backend::Sqlite => {
+                use mess_db::rusqlite::storage::SqliteReader;
+
+                Reader::Sqlite(SqliteReader::open(path)?)
+            }
+            #[cfg(not(feature = "rusqlite"))]
+            Backend::Sqlite => {
+                return Err(crate::error::Error::Usage(
+                    "the sqlite backend needs the rusqlite feature".into(),
+                ))
+            }
+        };
+        Ok(Self { reader, backend, path: path.to_owned() })
+    }
+
+    #[must_use]
+    pub const fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How many bytes the store takes on disk, counting SQLite's
+    /// write-ahead log.
+    pub fn size(&self) -> Result<u64> {
+        match self.backend {
+            Backend::Rocksdb => Ok(dir_size(&self.path)?),
+            Backend::Sqlite => {
+                let mut size = std::fs::metadata(&self.path)?.len();
+                let mut wal = self.path.clone().into_os_string();
+                wal.push("-wal");
+                if let Ok(meta) = std::fs::metadata(wal) {
+                    size += meta.len();
+                }
+                Ok(size)
+            }
+        }
+    }
+
+    fn reader(&self) -> &dyn StorageReader {
+        match &self.reader {
+            Reader::Rocks { db, .. } => db,
+            #[cfg(feature = "rusqlite")]
+            Reader::Sqlite(reader) => reader,
+        }
+    }
+
+    /// Catches up with what has been written since the last refresh.
+    pub fn refresh(&self) -> Result<()> {
+        match &self.reader {
+            Reader::Rocks { db, .. } => Ok(db.catch_up()?),
+            #[cfg(feature = "rusqlite")]
+            Reader::Sqlite(_) => Ok(()),
+        }
+    }
+
+    /// Returns a page of the read, failing with its first error.
+    pub fn read(&self, cursor: &Cursor) -> Result<Vec<OwnedMessage>> {
+        Ok(self.fetch_page(cursor.clone())?.0)
+    }
+
+    /// Returns a page of the read along with the cursor which continues it,
+    /// failing with its first error.
+    pub fn fetch_page(
+        &self,
+        cursor: Cursor,
+    ) -> Result<(Vec<OwnedMessage>, Cursor)> {
+        let messages = self.reader().read(&cursor);
+        let cursor = cursor.after_page(&messages);
+        let messages =
+            messages.into_iter().collect::<mess_db::error::Result<_>>()?;
+        Ok((messages, cursor))
+    }
+
+    /// Reads every message the cursor matches, page after page. Reading
+    /// ends after the first error.
+    pub fn messages(
+        &self,
+        cursor: Cursor,
+    ) -> impl Iterator<Item = Result<OwnedMessage>> + '_ {
+        let mut next = Some(cursor);
+        let mut page = Vec::new().into_iter();
+        std::iter::from_fn(move || loop {
+            if let Some(msg) = page.next() {
+                return Some(Ok(msg));
+            }
+            let cursor = next.take()?;
+            let limit = cursor.limit();
+            match self.fetch_page(cursor) {
+                Ok((messages, cursor)) => {
+                    if messages.len() >= limit {
+                        next = Some(cursor);
+                    }
+                    page = messages.into_iter();
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        })
+    }
+
+    /// Returns up to `limit` names of streams starting with `prefix`, after
+    /// the stream named `after` if given.
+    pub fn list_streams(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        Ok(self.reader().list_streams(prefix, after, limit)?)
+    }
+
+    /// Returns the message at the global position, if there is one.
+    pub fn message_at(&self, global: u64) -> Result<Option<OwnedMessage>> {
+        let cursor = GetMessages::default()
+            .from_global(global)
+            .with_limit(1)
+            .into_cursor()?;
+        let msg = self.read(&cursor)?.into_iter().next();
+        Ok(msg.filter(|msg| msg.global_position == global))
+    }
+
+    /// Returns the global position of the last message, or 0 if there is
+    /// none.
+    pub fn head(&self) -> Result<u64> {
+        let cursor = Scope::All.cursor(None, 1, true)?;
+        let last = self.read(&cursor)?.into_iter().next();
+        Ok(last.map_or(0, |msg| msg.global_position))
+    }
+
+    /// Returns the global position of the first message written at or
+    /// after `time`, if any was.
+    pub fn position_at(&self, time: SystemTime) -> Result<Option<u64>> {
+        Ok(self.reader().position_at_time(time.into())?)
+    }
+
+    pub fn stream_info(&self, stream: &str) -> Result<Option<StreamInfo>> {
+        Ok(self.reader().stream_info(stream)?)
+    }
+
+    /// Lists the categories streams are in, up to [`LIST_LIMIT`].
+    pub fn categories(&self) -> Result<Vec<String>> {
+        let mut categories = BTreeSet::new();
+        let mut after = None;
+        while categories.len() < LIST_LIMIT {
+            let names = self.reader().list_streams("", after.as_deref(), 1)?;
+            let Some(name) = names.into_iter().next() else {
+                break;
+            };
+            let cat = category(&name).to_owned();
+            // Skip the rest of the category, whose streams are listed
+            // together, but not whatever follows a stream named after it.
+            after = Some(if name.contains('-') {
+                format!("{cat}-{}", char::MAX)
+            } else {
+                name
+            });
+            categories.insert(cat);
+        }
+        Ok(categories.into_iter().collect())
+    }
+
+    /// Lists the streams in `cat`, up to [`LIST_LIMIT`].
+    pub fn streams(&self, cat: &str) -> Result<Vec<String>> {
+        let prefix = format!("{cat}-");
+        let mut streams = Vec::new();
+        if self.stream_info(cat)?.is_some() {
+            streams.push(cat.to_owned());
+        }
+        while streams.len() < LIST_LIMIT {
+            let after = streams.last().filter(|name| name.starts_with(&prefix));
+            let limit = (LIST_LIMIT - streams.len()).min(PAGE_SIZE);
+            let names = self.reader().list_streams(
+                &prefix,
+                after.map(String::as_str),
+                limit,
+            )?;
+            let done = names.len() < limit;
+            streams.extend(names);
+            if done {
+                break;
+            }
+        }
+        Ok(streams)
+    }
+
+    /// Finds the messages linked to `msg`, searching [`NEIGHBOUR_WINDOW`]
+    /// messages either side of it for the ones pointing back at it.
+    pub fn neighbours(&self, msg: &OwnedMessage) -> Result<Neighbours> {
+        let links = Links::of(msg);
+        let cause = match links.causation_message_global_position {
+            Some(global) => self.message_at(global)?,
+            None => None,
+        };
+        let mut neighbours = Neighbours { cause, ..Neighbours::default() };
+        let global = msg.global_position;
+        let from = global.saturating_sub(NEIGHBOUR_WINDOW).max(1);
+        let mut cursor = Scope::All.cursor(Some(from), PAGE_SIZE, false)?;
+        loop {
+            let page = self.read(&cursor)?;
+            for other in &page {
+                if other.global_position == global {
+                    continue;
+                }
+                let other_links = Links::of(other);
+                if other_links.causation_message_global_position == Some(global)
+                {
+                    neighbours.effects.push(other.clone());
+                }
+                if links.correlation_stream_name.is_some()
+                    && other_links.correlation_stream_name
+                        == links.correlation_stream_name
+                {
+                    neighbours.correlated.push(other.clone());
+                }
+            }
+            let Some(last) = page.last() else { break };
+            if last.global_position >= global + NEIGHBOUR_WINDOW {
+                break;
+            }
+            cursor = cursor.after(last);
+        }
+        let past = |other: &OwnedMessage| {
+            other.global_position <= global + NEIGHBOUR_WINDOW
+        };
+        neighbours.effects.retain(past);
+        neighbours.correlated.retain(past);
+        Ok(neighbours)
+    }
+}
+
+impl Drop for Source {
+    fn drop(&mut self) {
+        match &self.reader {
+            Reader::Rocks { secondary, .. } => {
+                let _ = std::fs::remove_dir_all(secondary);
+            }
+            #[cfg(feature = "rusqlite")]
+            Reader::Sqlite(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{store::test::new_store, write::test::put};
+    use assert2::assert;
+    use mess_db::write::{ExpectedVersion, WriteMessage};
+
+    fn linked(
+        stream: &str,
+        correlation: &str,
+        cause: Option<u64>,
+    ) -> WriteMessage<'static> {
+        let meta = serde_json::json!({
+            "correlationStreamName": correlation,
+            "causationMessageGlobalPosition": cause,
+        });
+        WriteMessage {
+            id: Id::new(),
+            stream_name: stream.to_owned().into(),
+            message_type: "Linked".into(),
+            data: b"{}".to_vec().into(),
+            metadata: serde_json::to_vec(&meta).unwrap().into(),
+            expected_version: ExpectedVersion::Any,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_lists_categories_and_their_streams() {
+        let store = new_store();
+        for stream in ["account", "account-1", "account-2", "accountx-1"] {
+            put(&store.handle, stream, 1).await;
+        }
+        put(&store.handle, "user:command-1", 2).await;
+        let source = Source::open(&store.path, None).unwrap();
+
+        let categories = source.categories().unwrap();
+        assert!(categories == ["account", "accountx", "user:command"]);
+        let streams = source.streams("account").unwrap();
+        assert!(streams == ["account", "account-1", "account-2"]);
+        assert!(source.head().unwrap() == 6);
+    }
+
+    #[tokio::test]
+    async fn it_catches_up_with_the_writer() {
+        let store = new_store();
+        put(&store.handle, "account-1", 1).await;
+        let source = Source::open(&store.path, None).unwrap();
+        put(&store.handle, "account-2", 1).await;
+        source.refresh().unwrap();
+        assert!(source.head().unwrap() == 2);
+        assert!(
+            source.message_at(2).unwrap().unwrap().stream_name == "account-2"
+        );
+        assert!(source.message_at(3).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn it_finds_neighbours() {
+        let store = new_store();
+        let db = &store.handle;
+        put(db, "other-1", 1).await;
+        let cause = db.put_message(linked("order-1", "reply-1", None)).await;
+        let cause = cause.unwrap().global;
+        db.put_message(linked("payment-1", "reply-1", Some(cause)))
+            .await
+            .unwrap();
+        db.put_message(linked("order-2", "reply-2", None)).await.unwrap();
+        let source = Source::open(&store.path, None).unwrap();
+
+        let msg = source.message_at(cause).unwrap().unwrap();
+        let neighbours = source.neighbours(&msg).unwrap();
+        assert!(neighbours.cause.is_none());
+        assert!(neighbours.effects.len() == 1);
+        assert!(neighbours.correlated.len() == 1);
+
+        let effect = &neighbours.effects[0];
+        let back = source.neighbours(effect).unwrap();
+        assert!(back.cause.unwrap().global_position == cause);
+        assert!(back.effects.is_empty());
+    }
+}
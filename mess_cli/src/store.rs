@@ -0,0 +1,137 @@
+//! Opening the store a command runs on.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use mess_db::svc::ActorHandle;
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Rocksdb,
+    Sqlite,
+}
+
+impl Backend {
+    /// Tells the backend from what is at `path`: RocksDB keeps a directory
+    /// and SQLite a single file.
+    pub fn detect(path: &Path) -> Result<Self> {
+        let meta = std::fs::metadata(path).map_err(|err| {
+            Error::Usage(format!(
+                "{}: {err} (pass --backend to create a store)",
+                path.display()
+            ))
+        })?;
+        Ok(if meta.is_dir() { Self::Rocksdb } else { Self::Sqlite })
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rocksdb" => Ok(Self::Rocksdb),
+            "sqlite" => Ok(Self::Sqlite),
+            s => Err(Error::Usage(format!("unknown backend {s}"))),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rocksdb => "rocksdb",
+            Self::Sqlite => "sqlite",
+        })
+    }
+}
+
+/// A store opened by this process to write to it, which has to be the only
+/// one writing to it. Commands which only read open a
+/// [`Source`](crate::source::Source) instead.
+pub struct Store {
+    pub handle: ActorHandle,
+    pub backend: Backend,
+    pub path: PathBuf,
+}
+
+impl Store {
+    /// Opens the store at `path`, creating it if it's missing and
+    /// `backend` is given. Without a backend it's told from the path.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        backend: Option<Backend>,
+    ) -> Result<Self> {
+        let path = path.into();
+        let backend = match backend {
+            Some(backend) => backend,
+            None => Backend::detect(&path)?,
+        };
+        let handle = match backend {
+            Backend::Rocksdb => {
+                use mess_db::rocks::{db::DB, storage::RocksStorage};
+
+                ActorHandle::new(RocksStorage::new(DB::new(&path)?))?
+            }
+            #[cfg(feature = "rusqlite")]
+            Backend::Sqlite => {
+                use mess_db::rusqlite::storage::SqliteStorage;
+
+                ActorHandle::new(SqliteStorage::open(&path)?)?
+            }
+            #[cfg(not(feature = "rusqlite"))]
+            Backend::Sqlite => {
+                return Err(Error::Usage(
+                    "the sqlite backend needs the rusqlite feature".into(),
+                ))
+            }
+        };
+        Ok(Self { handle, backend, path })
+    }
+
+    /// Syncs what was written and closes the store.
+    pub async fn close(self) -> Result<()> {
+        Ok(self.handle.shutdown().await?)
+    }
+}
+
+pub(crate) fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size +=
+            if meta.is_dir() { dir_size(&entry.path())? } else { meta.len() };
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use assert2::assert;
+    use ident::Id;
+
+    /// Opens a new RocksDB store in the temp directory.
+    pub(crate) fn new_store() -> Store {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        Store::open(path, Some(Backend::Rocksdb)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn the_backend_is_told_from_the_path() {
+        let store = new_store();
+        assert!(Backend::detect(&store.path).unwrap() == Backend::Rocksdb);
+        let file = std::env::temp_dir().join(format!("{}.db", Id::new()));
+        std::fs::write(&file, b"").unwrap();
+        assert!(Backend::detect(&file).unwrap() == Backend::Sqlite);
+        let missing = store.path.join("missing");
+        assert!(let Err(Error::Usage(_)) = Backend::detect(&missing));
+        assert!(let Err(Error::Usage(_)) = Store::open(missing, None));
+    }
+}
@@ -0,0 +1,632 @@
+//! What the terminal UI shows and how each key changes it.
+
+use std::time::{Duration, SystemTime};
+
+use mess_db::{read::category, OwnedMessage, StreamInfo};
+
+use crate::source::Source;
+use crate::{
+    error::{Error, Result},
+    read::{Scope, PAGE_SIZE},
+};
+
+/// How many messages are kept loaded before the oldest are let go.
+pub const MAX_LOADED: usize = 10 * PAGE_SIZE;
+
+/// What a key asks for, whatever the key was.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    /// Opens what is selected.
+    Enter,
+    Back,
+    /// Shows the global log.
+    All,
+    /// Shows every message in the selected category, or the selected
+    /// message's.
+    Category,
+    /// Starts or stops following new messages.
+    Follow,
+    /// Asks for a position or time to jump to.
+    Jump,
+    /// Reloads what is shown.
+    Reload,
+    Quit,
+    /// Types into the jump prompt.
+    Input(char),
+    Erase,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pane {
+    Categories,
+    Streams,
+    Messages,
+    /// A single message with its neighbours.
+    Message,
+}
+
+/// A list with one item selected.
+#[derive(Debug)]
+pub struct Selectable<T> {
+    pub items: Vec<T>,
+    pub selected: usize,
+}
+
+impl<T> Default for Selectable<T> {
+    fn default() -> Self {
+        Self { items: Vec::new(), selected: 0 }
+    }
+}
+
+impl<T> Selectable<T> {
+    fn new(items: Vec<T>) -> Self {
+        Self { items, selected: 0 }
+    }
+
+    #[must_use]
+    pub fn current(&self) -> Option<&T> {
+        self.items.get(self.selected)
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.items.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn select_last(&mut self) {
+        self.selected = self.items.len().saturating_sub(1);
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.selected + 1 >= self.items.len()
+    }
+}
+
+/// How a message in [`Detail`] is linked to the one shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Link {
+    Cause,
+    Effect,
+    Correlated,
+}
+
+/// The message the [`Pane::Message`] pane shows.
+#[derive(Debug)]
+pub struct Detail {
+    pub message: OwnedMessage,
+    pub neighbours: Selectable<(Link, OwnedMessage)>,
+}
+
+/// Where the jump prompt goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// A stream position within a stream, a global one otherwise.
+    Position(u64),
+    Time(SystemTime),
+}
+
+impl Target {
+    /// Parses a position, an RFC 3339 time such as
+    /// `2024-05-01T12:00:00Z`, or an age such as `15m`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Ok(pos) = s.parse() {
+            return Ok(Self::Position(pos));
+        }
+        if let Ok(time) = chrono::DateTime::parse_from_rfc3339(s) {
+            return Ok(Self::Time(time.into()));
+        }
+        let unit = match s.chars().last() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => 24 * 60 * 60,
+            _ => 0,
+        };
+        let age = s[..s.len().saturating_sub(1)].parse::<u64>().ok();
+        match age.filter(|_| unit > 0) {
+            Some(age) => Ok(Self::Time(
+                SystemTime::now() - Duration::from_secs(age * unit),
+            )),
+            None => Err(Error::Usage(format!(
+                "{s} isn't a position, an RFC 3339 time or an age like 15m"
+            ))),
+        }
+    }
+}
+
+pub struct App {
+    source: Source,
+    pub pane: Pane,
+    pub categories: Selectable<String>,
+    /// The streams of the category last opened.
+    pub streams: Selectable<String>,
+    /// The selected stream's summary.
+    pub stream_info: Option<StreamInfo>,
+    /// What the messages are read from.
+    pub scope: Scope,
+    pub messages: Selectable<OwnedMessage>,
+    pub detail: Option<Detail>,
+    pub following: bool,
+    /// The jump prompt's input, while it's open.
+    pub prompt: Option<String>,
+    /// The outcome of the last action, such as an error.
+    pub status: String,
+    /// The global position of the last message.
+    pub head: u64,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(source: Source) -> Result<Self> {
+        let categories = Selectable::new(source.categories()?);
+        let head = source.head()?;
+        Ok(Self {
+            source,
+            pane: Pane::Categories,
+            categories,
+            streams: Selectable::default(),
+            stream_info: None,
+            scope: Scope::All,
+            messages: Selectable::default(),
+            detail: None,
+            following: false,
+            prompt: None,
+            status: String::new(),
+            head,
+            quit: false,
+        })
+    }
+
+    /// Acts on a key, showing what went wrong in the status line.
+    pub fn handle(&mut self, action: Action) {
+        self.status.clear();
+        if let Err(err) = self.apply(action) {
+            self.status = err.to_string();
+        }
+    }
+
+    /// Reads new messages while following.
+    pub fn tick(&mut self) {
+        if !self.following {
+            return;
+        }
+        let res = self.source.refresh().and_then(|()| {
+            let at_end = self.messages.is_at_end();
+            self.load_newer()?;
+            if at_end {
+                self.messages.select_last();
+            }
+            self.head = self.source.head()?;
+            Ok(())
+        });
+        if let Err(err) = res {
+            self.status = err.to_string();
+        }
+    }
+
+    fn apply(&mut self, action: Action) -> Result<()> {
+        if let Some(input) = &mut self.prompt {
+            match action {
+                Action::Input(c) => input.push(c),
+                Action::Erase => {
+                    input.pop();
+                }
+                Action::Back => self.prompt = None,
+                Action::Quit => self.quit = true,
+                Action::Enter => {
+                    let target = Target::parse(input)?;
+                    self.prompt = None;
+                    self.jump(target)?;
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        match action {
+            Action::Up => self.move_by(-1)?,
+            Action::Down => self.move_by(1)?,
+            Action::PageUp => self.move_by(-20)?,
+            Action::PageDown => self.move_by(20)?,
+            Action::Top if self.pane == Pane::Messages => {
+                self.load_from(None)?;
+            }
+            Action::Bottom if self.pane == Pane::Messages => {
+                self.load_latest()?;
+            }
+            Action::Top => self.move_by(isize::MIN)?,
+            Action::Bottom => self.move_by(isize::MAX)?,
+            Action::Enter => self.enter()?,
+            Action::Back => self.back(),
+            Action::All => self.open(Scope::All)?,
+            Action::Category => {
+                let name = match (self.pane, self.messages.current()) {
+                    (Pane::Messages, Some(msg)) => {
+                        Some(category(&msg.stream_name))
+                    }
+                    _ => self.categories.current().map(String::as_str),
+                };
+                if let Some(name) = name.map(str::to_owned) {
+                    self.open(Scope::Category(name))?;
+                }
+            }
+            Action::Follow if self.following => self.following = false,
+            Action::Follow => {
+                let scope = match self.pane {
+                    Pane::Categories => self
+                        .categories
+                        .current()
+                        .map_or(Scope::All, |c| Scope::Category(c.clone())),
+                    Pane::Streams => self
+                        .streams
+                        .current()
+                        .map_or(Scope::All, |s| Scope::Stream(s.clone())),
+                    Pane::Messages | Pane::Message => self.scope.clone(),
+                };
+                self.open(scope)?;
+                self.following = true;
+            }
+            Action::Jump => self.prompt = Some(String::new()),
+            Action::Reload => self.reload()?,
+            Action::Quit => self.quit = true,
+            Action::Input(_) | Action::Erase => {}
+        }
+        Ok(())
+    }
+
+    fn move_by(&mut self, delta: isize) -> Result<()> {
+        match self.pane {
+            Pane::Categories => self.categories.move_by(delta),
+            Pane::Streams => {
+                self.streams.move_by(delta);
+                self.stream_info = match self.streams.current() {
+                    Some(stream) => self.source.stream_info(stream)?,
+                    None => None,
+                };
+            }
+            Pane::Messages => {
+                // Moving past either end reads the next page that way.
+                if delta < 0 && self.messages.selected == 0 {
+                    self.load_older()?;
+                } else if delta > 0 && self.messages.is_at_end() {
+                    self.load_newer()?;
+                }
+                self.messages.move_by(delta);
+            }
+            Pane::Message => {
+                if let Some(detail) = &mut self.detail {
+                    detail.neighbours.move_by(delta);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        match self.pane {
+            Pane::Categories => {
+                let Some(category) = self.categories.current() else {
+                    return Ok(());
+                };
+                self.streams = Selectable::new(self.source.streams(category)?);
+                self.pane = Pane::Streams;
+                self.move_by(0)?;
+            }
+            Pane::Streams => {
+                if let Some(stream) = self.streams.current() {
+                    self.open(Scope::Stream(stream.clone()))?;
+                }
+            }
+            Pane::Messages => {
+                if let Some(msg) = self.messages.current() {
+                    self.show(msg.clone())?;
+                }
+            }
+            Pane::Message => {
+                let linked = self
+                    .detail
+                    .as_ref()
+                    .and_then(|detail| detail.neighbours.current());
+                let Some((_, msg)) = linked.cloned() else {
+                    return Ok(());
+                };
+                self.scope = Scope::Stream(msg.stream_name.clone());
+                self.load_from(Some(msg.stream_position.position()))?;
+                self.show(msg)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn back(&mut self) {
+        self.pane = match self.pane {
+            Pane::Message => Pane::Messages,
+            Pane::Messages => {
+                self.following = false;
+                match self.scope {
+                    Scope::Stream(_) if !self.streams.items.is_empty() => {
+                        Pane::Streams
+                    }
+                    _ => Pane::Categories,
+                }
+            }
+            Pane::Streams | Pane::Categories => Pane::Categories,
+        };
+    }
+
+    /// Shows the newest messages of `scope`.
+    fn open(&mut self, scope: Scope) -> Result<()> {
+        self.scope = scope;
+        self.load_latest()
+    }
+
+    fn show(&mut self, message: OwnedMessage) -> Result<()> {
+        let found = self.source.neighbours(&message)?;
+        let neighbours = (found
+            .cause
+            .into_iter()
+            .map(|msg| (Link::Cause, msg)))
+        .chain(found.effects.into_iter().map(|msg| (Link::Effect, msg)))
+        .chain(found.correlated.into_iter().map(|msg| (Link::Correlated, msg)))
+        .collect();
+        self.detail =
+            Some(Detail { message, neighbours: Selectable::new(neighbours) });
+        self.pane = Pane::Message;
+        Ok(())
+    }
+
+    /// The position `msg` is read from in the current scope.
+    fn position(&self, msg: &OwnedMessage) -> u64 {
+        match self.scope {
+            Scope::Stream(_) => msg.stream_position.position(),
+            Scope::All | Scope::Category(_) => msg.global_position,
+        }
+    }
+
+    /// Shows a page of messages from `from`, or from the start without one.
+    fn load_from(&mut self, from: Option<u64>) -> Result<()> {
+        let cursor = self.scope.cursor(from, PAGE_SIZE, false)?;
+        self.messages = Selectable::new(self.source.read(&cursor)?);
+        self.pane = Pane::Messages;
+        Ok(())
+    }
+
+    /// Shows the newest page of messages, with the newest selected.
+    fn load_latest(&mut self) -> Result<()> {
+        let cursor = self.scope.cursor(None, PAGE_SIZE, true)?;
+        let mut messages = self.source.read(&cursor)?;
+        messages.reverse();
+        self.messages = Selectable::new(messages);
+        self.messages.select_last();
+        self.pane = Pane::Messages;
+        Ok(())
+    }
+
+    /// Adds the page of messages after the last one shown.
+    fn load_newer(&mut self) -> Result<()> {
+        let from =
+            self.messages.items.last().map(|last| self.position(last) + 1);
+        let cursor = self.scope.cursor(from, PAGE_SIZE, false)?;
+        self.messages.items.extend(self.source.read(&cursor)?);
+        let excess = self.messages.items.len().saturating_sub(MAX_LOADED);
+        self.messages.items.drain(..excess);
+        self.messages.selected = self.messages.selected.saturating_sub(excess);
+        Ok(())
+    }
+
+    /// Adds the page of messages before the first one shown.
+    fn load_older(&mut self) -> Result<()> {
+        let Some(first) = self.messages.items.first() else {
+            return Ok(());
+        };
+        let Some(before) = self.position(first).checked_sub(1) else {
+            return Ok(());
+        };
+        let cursor = self.scope.cursor(Some(before), PAGE_SIZE, true)?;
+        let mut older = self.source.read(&cursor)?;
+        older.reverse();
+        self.messages.selected += older.len();
+        older.append(&mut self.messages.items);
+        older.truncate(MAX_LOADED);
+        self.messages.items = older;
+        Ok(())
+    }
+
+    fn jump(&mut self, target: Target) -> Result<()> {
+        if self.pane != Pane::Messages && self.pane != Pane::Message {
+            self.scope = Scope::All;
+        }
+        let pos = match (target, self.scope.clone()) {
+            (Target::Position(pos), _) => pos,
+            (Target::Time(time), scope) => {
+                let Some(global) = self.source.position_at(time)? else {
+                    self.status = "no messages since then".into();
+                    return self.load_latest();
+                };
+                match scope {
+                    Scope::Stream(stream) => {
+                        match self.stream_position_at(&stream, global)? {
+                            Some(pos) => pos,
+                            None => {
+                                self.status = format!(
+                                    "no messages in {stream} since then"
+                                );
+                                return self.load_latest();
+                            }
+                        }
+                    }
+                    Scope::All | Scope::Category(_) => global,
+                }
+            }
+        };
+        self.following = false;
+        self.load_from(Some(pos))
+    }
+
+    /// Finds the position of the first message in `stream` at or after
+    /// the global position.
+    fn stream_position_at(
+        &self,
+        stream: &str,
+        global: u64,
+    ) -> Result<Option<u64>> {
+        let scope = Scope::Stream(stream.to_owned());
+        let mut cursor = scope.cursor(None, PAGE_SIZE, false)?;
+        loop {
+            let page = self.source.read(&cursor)?;
+            if let Some(msg) =
+                page.iter().find(|msg| msg.global_position >= global)
+            {
+                return Ok(Some(msg.stream_position.position()));
+            }
+            let Some(last) = page.last() else {
+                return Ok(None);
+            };
+            cursor = cursor.after(last);
+        }
+    }
+
+    /// Catches up with the writer and reads what is shown again, keeping
+    /// the selections where they can be.
+    fn reload(&mut self) -> Result<()> {
+        self.source.refresh()?;
+        self.head = self.source.head()?;
+        let selected = self.categories.current().cloned();
+        self.categories = Selectable::new(self.source.categories()?);
+        if let Some(selected) = selected {
+            if let Some(i) =
+                self.categories.items.iter().position(|c| *c == selected)
+            {
+                self.categories.selected = i;
+            }
+        }
+        if self.pane == Pane::Streams {
+            if let Some(category) = self.categories.current() {
+                let selected = self.streams.selected;
+                self.streams = Selectable::new(self.source.streams(category)?);
+                self.streams.selected = selected;
+                self.move_by(0)?;
+            }
+        }
+        if self.pane == Pane::Messages {
+            self.load_newer()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{store::test::new_store, write::test::put};
+    use assert2::assert;
+
+    fn positions(app: &App) -> Vec<u64> {
+        app.messages.items.iter().map(|msg| msg.global_position).collect()
+    }
+
+    #[tokio::test]
+    async fn it_drills_down_from_categories_to_a_message() {
+        let store = new_store();
+        put(&store.handle, "account-1", 2).await;
+        put(&store.handle, "user-1", 1).await;
+        let mut app =
+            App::new(Source::open(&store.path, None).unwrap()).unwrap();
+        assert!(app.categories.items == ["account", "user"]);
+        assert!(app.head == 3);
+
+        app.handle(Action::Enter);
+        assert!(app.pane == Pane::Streams);
+        assert!(app.streams.items == ["account-1"]);
+        assert!(app.stream_info.as_ref().unwrap().count == 2);
+
+        app.handle(Action::Enter);
+        assert!(app.pane == Pane::Messages);
+        assert!(positions(&app) == [1, 2]);
+        assert!(app.messages.selected == 1);
+
+        app.handle(Action::Enter);
+        assert!(app.pane == Pane::Message);
+        assert!(app.detail.as_ref().unwrap().message.global_position == 2);
+
+        app.handle(Action::Back);
+        app.handle(Action::Back);
+        assert!(app.pane == Pane::Streams);
+        app.handle(Action::Back);
+        app.handle(Action::Down);
+        app.handle(Action::Category);
+        assert!(app.scope == Scope::Category("user".into()));
+        assert!(positions(&app) == [3]);
+    }
+
+    #[tokio::test]
+    async fn it_pages_through_the_log() {
+        let store = new_store();
+        put(&store.handle, "account-1", PAGE_SIZE as u64 + 5).await;
+        let mut app =
+            App::new(Source::open(&store.path, None).unwrap()).unwrap();
+        app.handle(Action::All);
+        assert!(app.messages.items.len() == PAGE_SIZE);
+        assert!(app.messages.current().unwrap().global_position == 1005);
+
+        app.handle(Action::Top);
+        assert!(app.messages.current().unwrap().global_position == 1);
+        app.handle(Action::Bottom);
+        app.handle(Action::Up);
+        app.messages.selected = 0;
+        app.handle(Action::Up);
+        assert!(app.messages.items.len() == PAGE_SIZE + 5);
+        assert!(app.messages.current().unwrap().global_position == 5);
+    }
+
+    #[tokio::test]
+    async fn it_jumps_and_follows() {
+        let store = new_store();
+        put(&store.handle, "account-1", 3).await;
+        let mut app =
+            App::new(Source::open(&store.path, None).unwrap()).unwrap();
+        app.handle(Action::Jump);
+        for c in "2".chars() {
+            app.handle(Action::Input(c));
+        }
+        app.handle(Action::Enter);
+        assert!(app.prompt.is_none());
+        assert!(positions(&app) == [2, 3]);
+
+        app.handle(Action::Jump);
+        app.handle(Action::Input('x'));
+        app.handle(Action::Enter);
+        assert!(app.status.contains("isn't a position"));
+        app.handle(Action::Back);
+
+        app.handle(Action::Follow);
+        assert!(app.following);
+        put(&store.handle, "account-1", 1).await;
+        app.tick();
+        assert!(app.head == 4);
+        assert!(app.messages.current().unwrap().global_position == 4);
+    }
+
+    #[test]
+    fn targets_parse() {
+        assert!(let Ok(Target::Position(12)) = Target::parse("12"));
+        let Ok(Target::Time(time)) = Target::parse("2024-05-01T12:00:00Z")
+        else {
+            panic!("expected a time");
+        };
+        let secs = time.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        assert!(secs.as_secs() == 1_714_564_800);
+        let Ok(Target::Time(time)) = Target::parse("15m") else {
+            panic!("expected an age");
+        };
+        assert!(time < SystemTime::now() - Duration::from_secs(899));
+        assert!(let Err(Error::Usage(_)) = Target::parse("15y"));
+        assert!(let Err(Error::Usage(_)) = Target::parse(""));
+    }
+}
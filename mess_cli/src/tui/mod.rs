@@ -0,0 +1,98 @@
+//! A terminal UI for browsing a store by category, stream and message.
+//!
+//! The store is opened read-only, so the UI can run next to the service
+//! writing to it: a RocksDB store as a secondary instance which catches up
+//! with the writer when reloading or following, and a SQLite file with a
+//! read-only connection.
+
+mod app;
+mod ui;
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+    },
+    DefaultTerminal,
+};
+
+use self::app::{Action, App};
+use crate::{error::Result, source::Source, store::Backend};
+
+/// Runs the UI on the store at `path` until it's quit, reading new
+/// messages every `interval` while following.
+pub fn run(
+    path: &Path,
+    backend: Option<Backend>,
+    interval: Duration,
+) -> Result<()> {
+    let mut app = App::new(Source::open(path, backend)?)?;
+    let title = path.display().to_string();
+    let mut terminal = ratatui::try_init()?;
+    let res = event_loop(&mut terminal, &mut app, &title, interval);
+    ratatui::restore();
+    res
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    title: &str,
+    interval: Duration,
+) -> Result<()> {
+    let mut last_tick = Instant::now();
+    while !app.quit {
+        terminal.draw(|frame| ui::draw(frame, app, title))?;
+        let timeout = interval.saturating_sub(last_tick.elapsed());
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if let Some(action) = action(key, app.prompt.is_some()) {
+                    app.handle(action);
+                }
+            }
+        }
+        if last_tick.elapsed() >= interval {
+            app.tick();
+            last_tick = Instant::now();
+        }
+    }
+    Ok(())
+}
+
+/// Maps a key to its action. Letters are typed while the jump prompt is
+/// open.
+fn action(key: KeyEvent, prompting: bool) -> Option<Action> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        return (key.code == KeyCode::Char('c')).then_some(Action::Quit);
+    }
+    let action = match key.code {
+        KeyCode::Char(c) if prompting => Action::Input(c),
+        KeyCode::Backspace if prompting => Action::Erase,
+        KeyCode::Up | KeyCode::Char('k') => Action::Up,
+        KeyCode::Down | KeyCode::Char('j') => Action::Down,
+        KeyCode::PageUp => Action::PageUp,
+        KeyCode::PageDown => Action::PageDown,
+        KeyCode::Home => Action::Top,
+        KeyCode::End => Action::Bottom,
+        KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => Action::Enter,
+        KeyCode::Esc
+        | KeyCode::Backspace
+        | KeyCode::Left
+        | KeyCode::Char('h') => Action::Back,
+        KeyCode::Char('a') => Action::All,
+        KeyCode::Char('c') => Action::Category,
+        KeyCode::Char('f') => Action::Follow,
+        KeyCode::Char('g') => Action::Jump,
+        KeyCode::Char('r') => Action::Reload,
+        KeyCode::Char('q') => Action::Quit,
+        _ => return None,
+    };
+    Some(action)
+}
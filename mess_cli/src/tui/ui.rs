@@ -0,0 +1,271 @@
+//! Drawing the [`App`].
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, SecondsFormat};
+use mess_db::{clock::Tick, OwnedMessage, StreamInfo};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use super::app::{App, Link, Pane};
+use crate::read::Scope;
+
+const KEYS: &[(&str, &str)] = &[
+    ("↑/↓ j/k", "move"),
+    ("PgUp/PgDn", "move a page"),
+    ("Home/End", "first or last"),
+    ("enter", "open"),
+    ("esc", "back"),
+    ("a", "global log"),
+    ("c", "category's messages"),
+    ("f", "follow new messages"),
+    ("g", "jump to a position, time or age like 15m"),
+    ("r", "reload"),
+    ("q", "quit"),
+];
+
+fn selected() -> Style {
+    Style::new().add_modifier(Modifier::REVERSED)
+}
+
+fn dim() -> Style {
+    Style::new().fg(Color::DarkGray)
+}
+
+/// Pretty-prints JSON, falling back to the text, or base64 if it isn't
+/// UTF-8.
+#[must_use]
+pub fn pretty(bytes: &[u8]) -> String {
+    if let Ok(value) = serde_json::from_slice::<serde_json::Value>(bytes) {
+        if let Ok(text) = serde_json::to_string_pretty(&value) {
+            return text;
+        }
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_owned(),
+        Err(_) => STANDARD.encode(bytes),
+    }
+}
+
+fn time(tick: Tick) -> String {
+    let secs = tick.to_secs_f64();
+    let nanos = (secs.fract() * 1e9) as u32;
+    DateTime::from_timestamp(secs.trunc() as i64, nanos).map_or_else(
+        || "?".into(),
+        |time| time.to_rfc3339_opts(SecondsFormat::Millis, true),
+    )
+}
+
+fn scope_name(scope: &Scope) -> String {
+    match scope {
+        Scope::All => "global log".into(),
+        Scope::Stream(stream) => format!("stream {stream}"),
+        Scope::Category(category) => format!("category {category}"),
+    }
+}
+
+pub fn draw(frame: &mut Frame, app: &App, title: &str) {
+    let [top, main, bottom] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let mut header = format!("mess {title}  head {}", app.head);
+    if app.following {
+        header.push_str("  following");
+    }
+    frame.render_widget(Paragraph::new(header).style(selected()), top);
+
+    match app.pane {
+        Pane::Categories => categories(frame, app, main),
+        Pane::Streams => streams(frame, app, main),
+        Pane::Messages => messages(frame, app, main),
+        Pane::Message => message(frame, app, main),
+    }
+
+    let status = if let Some(input) = &app.prompt {
+        Line::from(format!("jump to: {input}"))
+    } else if !app.status.is_empty() {
+        Line::styled(app.status.as_str(), Style::new().fg(Color::Red))
+    } else {
+        Line::styled("enter open  esc back  g jump  f follow  q quit", dim())
+    };
+    frame.render_widget(Paragraph::new(status), bottom);
+}
+
+fn list(
+    frame: &mut Frame,
+    area: Rect,
+    title: String,
+    items: Vec<ListItem<'_>>,
+    selected_item: usize,
+) {
+    let list = List::new(items)
+        .block(Block::bordered().title(title))
+        .highlight_style(selected());
+    let mut state = ListState::default().with_selected(Some(selected_item));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn categories(frame: &mut Frame, app: &App, area: Rect) {
+    let [left, right] = Layout::horizontal([
+        Constraint::Percentage(40),
+        Constraint::Percentage(60),
+    ])
+    .areas(area);
+    let items = app
+        .categories
+        .items
+        .iter()
+        .map(|category| ListItem::new(category.as_str()))
+        .collect();
+    let title = format!("categories ({})", app.categories.items.len());
+    list(frame, left, title, items, app.categories.selected);
+
+    let keys: Vec<Line> = KEYS
+        .iter()
+        .map(|(key, does)| {
+            Line::from(vec![
+                Span::styled(format!("{key:>10}  "), dim()),
+                Span::raw(*does),
+            ])
+        })
+        .collect();
+    let help = Paragraph::new(keys).block(Block::bordered().title("keys"));
+    frame.render_widget(help, right);
+}
+
+fn streams(frame: &mut Frame, app: &App, area: Rect) {
+    let [left, right] = Layout::horizontal([
+        Constraint::Percentage(40),
+        Constraint::Percentage(60),
+    ])
+    .areas(area);
+    let items = app
+        .streams
+        .items
+        .iter()
+        .map(|stream| ListItem::new(stream.as_str()))
+        .collect();
+    let category = app.categories.current().map_or("", String::as_str);
+    let title = format!("{category} ({})", app.streams.items.len());
+    list(frame, left, title, items, app.streams.selected);
+
+    let lines = app.stream_info.as_ref().map_or_else(Vec::new, info_lines);
+    let info = Paragraph::new(lines).block(Block::bordered().title("stream"));
+    frame.render_widget(info, right);
+}
+
+fn info_lines(info: &StreamInfo) -> Vec<Line<'static>> {
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{name:>16}  "), dim()),
+            value.into(),
+        ])
+    };
+    vec![
+        field("position", info.position.position().to_string()),
+        field("messages", info.count.to_string()),
+        field("first global", info.first_global.to_string()),
+        field("last global", info.last_global.to_string()),
+        field("first written", time(info.first_time)),
+        field("last written", time(info.last_time)),
+    ]
+}
+
+fn message_line(msg: &OwnedMessage) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{:>10} ", msg.global_position), dim()),
+        Span::raw(format!(
+            "{}/{} ",
+            msg.stream_name,
+            msg.stream_position.position()
+        )),
+        Span::styled(msg.message_type.clone(), Style::new().fg(Color::Cyan)),
+    ])
+}
+
+fn messages(frame: &mut Frame, app: &App, area: Rect) {
+    let [top, bottom] = Layout::vertical([
+        Constraint::Percentage(60),
+        Constraint::Percentage(40),
+    ])
+    .areas(area);
+    let items = app
+        .messages
+        .items
+        .iter()
+        .map(|msg| ListItem::new(message_line(msg)))
+        .collect();
+    list(frame, top, scope_name(&app.scope), items, app.messages.selected);
+
+    let data = app
+        .messages
+        .current()
+        .map_or_else(String::new, |msg| pretty(&msg.data));
+    let preview = Paragraph::new(data)
+        .wrap(Wrap { trim: false })
+        .block(Block::bordered().title("data"));
+    frame.render_widget(preview, bottom);
+}
+
+fn message(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(detail) = &app.detail else { return };
+    let [left, right] = Layout::horizontal([
+        Constraint::Percentage(60),
+        Constraint::Percentage(40),
+    ])
+    .areas(area);
+
+    let msg = &detail.message;
+    let mut text = Text::from(message_line(msg));
+    text.push_line(Line::default());
+    text.push_line(Line::styled("data", dim()));
+    text.extend(Text::from(pretty(&msg.data)));
+    if let Some(metadata) = &msg.metadata {
+        text.push_line(Line::default());
+        text.push_line(Line::styled("metadata", dim()));
+        text.extend(Text::from(pretty(metadata)));
+    }
+    let body = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(Block::bordered().title("message"));
+    frame.render_widget(body, left);
+
+    let items = detail
+        .neighbours
+        .items
+        .iter()
+        .map(|(link, msg)| {
+            let link = match link {
+                Link::Cause => "caused by ",
+                Link::Effect => "caused    ",
+                Link::Correlated => "correlated",
+            };
+            let mut line = message_line(msg);
+            line.spans.insert(0, Span::styled(link, dim()));
+            ListItem::new(line)
+        })
+        .collect();
+    let title = format!("neighbours ({})", detail.neighbours.items.len());
+    list(frame, right, title, items, detail.neighbours.selected);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn data_is_pretty_printed_when_it_can_be() {
+        assert!(pretty(br#"{"a":[1]}"#) == "{\n  \"a\": [\n    1\n  ]\n}");
+        assert!(pretty(b"not json") == "not json");
+        assert!(pretty(&[0xff]) == "/w==");
+    }
+}
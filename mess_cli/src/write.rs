@@ -0,0 +1,182 @@
+//! Appending and importing messages.
+
+use std::io::{BufRead, Write};
+
+use ident::Id;
+use mess_db::{
+    svc::ActorHandle,
+    write::{ExpectedVersion, WriteMessage},
+    StreamPos,
+};
+use serde_json::Value;
+
+use crate::{
+    error::{Error, Result},
+    json::{MessageLine, PositionLine},
+};
+
+/// Parses `any`, `no_stream`, `stream_exists` or the exact position of the
+/// stream's last message.
+pub fn parse_expected_version(s: &str) -> Result<ExpectedVersion> {
+    match s {
+        "any" => Ok(ExpectedVersion::Any),
+        "no_stream" => Ok(ExpectedVersion::NoStream),
+        "stream_exists" => Ok(ExpectedVersion::StreamExists),
+        pos => pos
+            .parse()
+            .map(|pos| ExpectedVersion::Exact(StreamPos::Sequential(pos)))
+            .map_err(|_| {
+                Error::Usage(format!("invalid expected version {pos}"))
+            }),
+    }
+}
+
+/// A message to append, with its data and metadata as JSON text.
+#[derive(Clone, Debug)]
+pub struct Append<'a> {
+    pub stream: &'a str,
+    pub message_type: &'a str,
+    pub data: &'a str,
+    pub metadata: Option<&'a str>,
+    pub expected_version: ExpectedVersion,
+    pub id: Option<Id>,
+}
+
+/// Stores JSON text the way the HTTP server does, as serde_json writes it.
+fn json_bytes(name: &str, text: &str) -> Result<Vec<u8>> {
+    let json: Value = serde_json::from_str(text)
+        .map_err(|err| Error::Usage(format!("{name} is not JSON: {err}")))?;
+    Ok(json.to_string().into_bytes())
+}
+
+/// Appends the message and prints its position as JSON.
+pub async fn write(
+    db: &ActorHandle,
+    out: &mut impl Write,
+    append: Append<'_>,
+) -> Result<()> {
+    let metadata = match append.metadata {
+        Some(metadata) => json_bytes("metadata", metadata)?,
+        None => vec![],
+    };
+    let wm = WriteMessage {
+        id: match append.id {
+            Some(id) => id,
+            None => Id::new(),
+        },
+        stream_name: append.stream.into(),
+        message_type: append.message_type.into(),
+        data: json_bytes("data", append.data)?.into(),
+        metadata: metadata.into(),
+        expected_version: append.expected_version,
+    };
+    let pos = db.put_message(wm).await?;
+    serde_json::to_writer(&mut *out, &PositionLine::from(pos))?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes every [`MessageLine`] read from `input`, such as an export, in
+/// order. Each message expects its stream to end right before it, so the
+/// import stops at the first message its stream already has. Returns how
+/// many were written.
+pub async fn import(db: &ActorHandle, input: impl BufRead) -> Result<u64> {
+    let mut count = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let failed = |reason: String| Error::Import { line: i + 1, reason };
+        let msg: MessageLine = serde_json::from_str(&line)
+            .map_err(|err| failed(err.to_string()))?;
+        let wm = msg.into_write().map_err(|err| failed(err.to_string()))?;
+        db.put_message(wm).await.map_err(|err| failed(err.to_string()))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::{read, source::Source, store::test::new_store};
+    use assert2::assert;
+
+    /// Appends `count` messages to the stream, each with its stream
+    /// position as data.
+    pub(crate) async fn put(db: &ActorHandle, stream: &str, count: u64) {
+        for _ in 0..count {
+            let next = db
+                .stream_info(stream)
+                .await
+                .unwrap()
+                .map_or(0, |info| info.position.position() + 1);
+            let data = next.to_string();
+            let append = Append {
+                stream,
+                message_type: "Counted",
+                data: &data,
+                metadata: None,
+                expected_version: ExpectedVersion::Any,
+                id: None,
+            };
+            write(db, &mut vec![], append).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn expected_versions_are_keywords_or_positions() {
+        let parse = |s| parse_expected_version(s).unwrap();
+        assert!(parse("no_stream") == ExpectedVersion::NoStream);
+        let exact = ExpectedVersion::Exact(StreamPos::Sequential(4));
+        assert!(parse("4") == exact);
+        assert!(let Err(Error::Usage(_)) = parse_expected_version("-1"));
+    }
+
+    #[tokio::test]
+    async fn writes_print_the_position() {
+        let store = new_store();
+        let mut out = vec![];
+        let append = Append {
+            stream: "account-1",
+            message_type: "Opened",
+            data: r#"{ "a": 1 }"#,
+            metadata: Some("{}"),
+            expected_version: ExpectedVersion::NoStream,
+            id: None,
+        };
+        write(&store.handle, &mut out, append.clone()).await.unwrap();
+        assert!(out == b"{\"global_position\":1,\"stream_position\":0}\n");
+        let err =
+            write(&store.handle, &mut out, append.clone()).await.unwrap_err();
+        assert!(let Error::Db(mess_db::error::Error::WrongStreamPosition { .. }) = err);
+        let append = Append { data: "{", ..append };
+        assert!(let Err(Error::Usage(_)) = write(&store.handle, &mut out, append).await);
+    }
+
+    #[tokio::test]
+    async fn exports_import_into_another_store() {
+        let from = new_store();
+        put(&from.handle, "account-1", 2).await;
+        put(&from.handle, "billing-1", 1).await;
+        let mut exported = vec![];
+        let cursor =
+            read::Scope::All.cursor(None, read::PAGE_SIZE, false).unwrap();
+        let source = Source::open(&from.path, None).unwrap();
+        let count = read::export(&source, &mut exported, cursor).unwrap();
+        assert!(count == 3);
+
+        let to = new_store();
+        assert!(import(&to.handle, &exported[..]).await.unwrap() == 3);
+        let mut copied = vec![];
+        let cursor =
+            read::Scope::All.cursor(None, read::PAGE_SIZE, false).unwrap();
+        let source = Source::open(&to.path, None).unwrap();
+        read::export(&source, &mut copied, cursor).unwrap();
+        assert!(copied == exported);
+
+        let err = import(&to.handle, &exported[..]).await.unwrap_err();
+        assert!(let Error::Import { line: 1, .. } = err);
+    }
+}
@@ -0,0 +1,547 @@
+//! A client for a store served over gRPC with `mess_grpc`, such as by
+//! `mess-server`.
+//!
+//! [`Client`] implements [`MessageStore`], as the embedded
+//! [`ActorHandle`](mess_db::svc::ActorHandle) does, so code written against
+//! the trait runs on either:
+//!
+//! ```no_run
+//! # async fn run() -> mess_db::error::Result<()> {
+//! use mess_client::Client;
+//! use mess_db::{read::GetMessages, store::MessageStore};
+//!
+//! let client = Client::builder("http://127.0.0.1:50051")?
+//!     .token("change-me")
+//!     .build()?;
+//! let read = GetMessages::default().in_stream("account-1");
+//! let messages = client.fetch_messages(read).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Calls which fail to reach the server are retried, and streamed reads and
+//! subscriptions reconnect where they left off. An append is safe to retry
+//! since the store keeps each message's id, so a retry of a message the
+//! server already wrote returns its position rather than writing it again.
+//! A conflict with the expected version or with an id used in another stream
+//! fails at once.
+
+mod pool;
+mod status;
+
+use std::future::Future;
+
+use futures::Stream;
+use mess_db::{
+    error::{Error, Result},
+    read::{Cursor, IntoCursor},
+    store::MessageStore,
+    svc::{Page, SubscribeFilter},
+    write::WriteMessage,
+    OwnedMessage, Position, StreamInfo,
+};
+use mess_grpc::pb;
+use tokio::sync::mpsc;
+use tonic::{transport::Endpoint, Code, Status, Streaming};
+use tracing::debug;
+
+use crate::{
+    pool::{Grpc, Pool},
+    status::{error, retryable, write_error},
+};
+pub use mess_db::retry::Retry;
+
+/// How many connections a client opens unless told otherwise.
+pub const DEFAULT_CONNECTIONS: usize = 4;
+
+/// How many messages a streamed read or subscription buffers ahead of its
+/// consumer.
+const STREAM_BUFFER: usize = 256;
+
+pub struct ClientBuilder {
+    endpoint: Endpoint,
+    connections: usize,
+    token: Option<String>,
+    retry: Retry,
+}
+
+impl ClientBuilder {
+    /// Open this many connections and spread calls over them. At least one
+    /// is opened.
+    #[must_use]
+    pub const fn connections(mut self, connections: usize) -> Self {
+        self.connections = connections;
+        self
+    }
+
+    /// Send `token` as the bearer token of every call.
+    #[must_use]
+    pub fn token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_owned());
+        self
+    }
+
+    #[must_use]
+    pub const fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Returns the client. Its connections are opened on first use.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the token can't be sent as a header.
+    pub fn build(self) -> Result<Client> {
+        let pool =
+            Pool::new(&self.endpoint, self.connections, self.token.as_deref())?;
+        Ok(Client { pool, retry: self.retry })
+    }
+}
+
+/// A remote store. Clones share the same connections.
+#[derive(Clone)]
+pub struct Client {
+    pool: Pool,
+    retry: Retry,
+}
+
+impl Client {
+    /// Starts building a client of the server at `url`, such as
+    /// `http://127.0.0.1:50051`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `url` isn't a valid URI.
+    pub fn builder(url: &str) -> Result<ClientBuilder> {
+        let endpoint = Endpoint::from_shared(url.to_owned())
+            .map_err(|err| Error::Other(format!("invalid url {url}: {err}")))?;
+        Ok(ClientBuilder {
+            endpoint,
+            connections: DEFAULT_CONNECTIONS,
+            token: None,
+            retry: Retry::default(),
+        })
+    }
+
+    /// Calls the server until it answers, or until a failure which retrying
+    /// won't help or the retries run out.
+    async fn retrying<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: FnMut(Grpc) -> Fut,
+        Fut: Future<Output = core::result::Result<T, Status>>,
+    {
+        self.retrying_with(call, error).await
+    }
+
+    /// Calls the server as [`Self::retrying`] does, failing with the error
+    /// `err` makes of the last status.
+    async fn retrying_with<T, F, Fut>(
+        &self,
+        mut call: F,
+        err: impl FnOnce(&Status) -> Error,
+    ) -> Result<T>
+    where
+        F: FnMut(Grpc) -> Fut,
+        Fut: Future<Output = core::result::Result<T, Status>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match call(self.pool.get()).await {
+                Ok(res) => return Ok(res),
+                Err(status)
+                    if retryable(&status)
+                        && attempt < self.retry.max_retries =>
+                {
+                    attempt += 1;
+                    debug!(attempt, %status, "retrying");
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                }
+                Err(status) => return Err(err(&status)),
+            }
+        }
+    }
+
+    /// Feeds the messages of a streamed read or subscription into a stream,
+    /// reconnecting after the last message received when the connection
+    /// fails.
+    fn follow(
+        &self,
+        follow: Result<Follow>,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        let (send, recv) = mpsc::channel(STREAM_BUFFER);
+        let client = self.clone();
+        tokio::spawn(async move {
+            let res = match follow {
+                Ok(follow) => client.feed(follow, &send).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = res {
+                let _ = send.send(Err(err)).await;
+            }
+        });
+        futures::stream::unfold(recv, |mut recv| async move {
+            recv.recv().await.map(|msg| (msg, recv))
+        })
+    }
+
+    async fn feed(
+        &self,
+        mut follow: Follow,
+        send: &mpsc::Sender<Result<OwnedMessage>>,
+    ) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            let status = match follow.open(self.pool.get()).await {
+                Ok(mut messages) => loop {
+                    match messages.message().await {
+                        Ok(Some(msg)) => {
+                            attempt = 0;
+                            let msg = OwnedMessage::from(msg);
+                            follow.advance(&msg);
+                            if send.send(Ok(msg)).await.is_err() {
+                                // Nobody is listening anymore.
+                                return Ok(());
+                            }
+                        }
+                        Ok(None) => return Ok(()),
+                        Err(status) => break status,
+                    }
+                },
+                Err(status) => status,
+            };
+            if !reconnects(&status) || attempt >= self.retry.max_retries {
+                return Err(error(&status));
+            }
+            attempt += 1;
+            debug!(attempt, %status, "reconnecting");
+            tokio::time::sleep(self.retry.backoff(attempt)).await;
+        }
+    }
+}
+
+/// Returns whether a stream which failed may continue on a new connection.
+/// A stream which broke off mid-way fails as `UNKNOWN` rather than
+/// `UNAVAILABLE`.
+fn reconnects(status: &Status) -> bool {
+    retryable(status) || status.code() == Code::Unknown
+}
+
+/// A streamed read or subscription, which continues after the last message
+/// it received when it reconnects.
+enum Follow {
+    Read(Cursor),
+    Subscribe(pb::SubscribeRequest),
+}
+
+impl Follow {
+    fn subscribe(from_global: u64, filter: &SubscribeFilter) -> Self {
+        Self::Subscribe(pb::SubscribeRequest {
+            from_global,
+            stream: filter.stream().map(str::to_owned),
+            categories: filter.categories().to_vec(),
+            types: filter.types().map(<[String]>::to_vec).unwrap_or_default(),
+        })
+    }
+
+    async fn open(
+        &self,
+        mut grpc: Grpc,
+    ) -> core::result::Result<Streaming<pb::Message>, Status> {
+        let res = match self {
+            Self::Read(cursor) => {
+                grpc.read(pb::ReadRequest::from(cursor.clone())).await
+            }
+            Self::Subscribe(req) => grpc.subscribe(req.clone()).await,
+        };
+        res.map(tonic::Response::into_inner)
+    }
+
+    fn advance(&mut self, msg: &OwnedMessage) {
+        match self {
+            Self::Read(cursor) => *cursor = cursor.clone().after(msg),
+            Self::Subscribe(req) => req.from_global = msg.global_position + 1,
+        }
+    }
+}
+
+impl MessageStore for Client {
+    async fn put_message(&self, wm: WriteMessage<'_>) -> Result<Position> {
+        let (stream, expected) =
+            (wm.stream_name.clone().into_owned(), wm.expected_version);
+        let req = pb::AppendRequest { message: Some(wm.into()) };
+        let res = self
+            .retrying_with(
+                |mut grpc| {
+                    let req = req.clone();
+                    async move { grpc.append(req).await }
+                },
+                |status| write_error(status, stream, expected),
+            )
+            .await?;
+        let pos = res.into_inner().position;
+        pos.map(Position::from).ok_or(Error::SvcResponse)
+    }
+
+    async fn fetch_messages(
+        &self,
+        read: impl IntoCursor + Send,
+    ) -> Result<Vec<Result<OwnedMessage>>> {
+        let page = self.fetch_page(read).await?;
+        Ok(page.messages)
+    }
+
+    async fn fetch_page(&self, read: impl IntoCursor + Send) -> Result<Page> {
+        let req = pb::ReadRequest::from(read.into_cursor()?);
+        let page = self
+            .retrying(|mut grpc| {
+                let req = req.clone();
+                async move { grpc.fetch(req).await }
+            })
+            .await?
+            .into_inner();
+        let messages =
+            page.messages.into_iter().map(|msg| Ok(msg.into())).collect();
+        Ok(Page { messages, cursor: page.cursor.parse()? })
+    }
+
+    async fn stream_info(&self, stream: &str) -> Result<Option<StreamInfo>> {
+        let req = pb::StreamInfoRequest { stream_name: stream.to_owned() };
+        self.retrying(|mut grpc| {
+            let req = req.clone();
+            async move {
+                match grpc.get_stream_info(req).await {
+                    Ok(info) => Ok(Some(info.into_inner().into())),
+                    Err(status) if status.code() == Code::NotFound => Ok(None),
+                    Err(status) => Err(status),
+                }
+            }
+        })
+        .await
+    }
+
+    fn stream_messages(
+        &self,
+        read: impl IntoCursor,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        self.follow(read.into_cursor().map(Follow::Read))
+    }
+
+    fn subscribe(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        self.follow(Ok(Follow::subscribe(from_global, &filter)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+    use futures::StreamExt;
+    use ident::Id;
+    use mess_db::{
+        read::GetMessages,
+        rocks::{db::DB, storage::RocksStorage},
+        svc::ActorHandle,
+        write::ExpectedVersion,
+        StreamPos,
+    };
+    use mess_ecs::EventDB;
+    use mess_grpc::MessService;
+    use std::{borrow::Cow, net::SocketAddr, time::Duration};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    fn new_handle() -> ActorHandle {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        ActorHandle::new(RocksStorage::new(DB::new(path).unwrap())).unwrap()
+    }
+
+    fn serve_on(listener: TcpListener, db: &ActorHandle) {
+        let service = MessService::new(db.clone()).into_server();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+    }
+
+    fn client(addr: SocketAddr) -> Client {
+        let retry = Retry {
+            max_retries: 20,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        };
+        Client::builder(&format!("http://{addr}"))
+            .unwrap()
+            .connections(2)
+            .retry(retry)
+            .build()
+            .unwrap()
+    }
+
+    async fn serve() -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        serve_on(listener, &new_handle());
+        client(addr)
+    }
+
+    fn message(
+        stream: &str,
+        n: u8,
+        version: ExpectedVersion,
+    ) -> WriteMessage<'_> {
+        WriteMessage {
+            id: Id::new(),
+            stream_name: stream.to_owned().into(),
+            message_type: "Counted".into(),
+            data: vec![n].into(),
+            metadata: vec![].into(),
+            expected_version: version,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_writes_and_reads_like_the_embedded_handle() {
+        let client = serve().await;
+        for n in 0..3 {
+            let msg = message("count-1", n, ExpectedVersion::Any);
+            let pos = client.put_message(msg).await.unwrap();
+            assert!(pos.stream == StreamPos::Sequential(n.into()));
+        }
+        let read = GetMessages::default().in_stream("count-1").with_limit(2);
+        let page = client.fetch_page(read).await.unwrap();
+        assert!(page.messages.len() == 2);
+        let next = client
+            .fetch_messages(GetMessages::resume(page.cursor))
+            .await
+            .unwrap();
+        assert!(next[0].as_ref().unwrap().data == [2]);
+
+        let info = client.stream_info("count-1").await.unwrap().unwrap();
+        assert!(info.count == 3);
+        assert!(client.stream_info("none-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn a_conflict_is_the_embedded_error() {
+        let client = serve().await;
+        let msg = message("count-1", 0, ExpectedVersion::Any);
+        client.put_message(msg).await.unwrap();
+        let msg = message("count-1", 1, ExpectedVersion::NoStream);
+        let err = client.put_message(msg).await.unwrap_err();
+        assert!(
+            let Error::WrongStreamPosition {
+                expected: ExpectedVersion::NoStream,
+                got: Some(StreamPos::Sequential(0)),
+                ..
+            } = err
+        );
+    }
+
+    #[tokio::test]
+    async fn streamed_reads_cross_pages() {
+        let client = serve().await;
+        for n in 0..5 {
+            let stream = format!("count-{n}");
+            let msg = message(&stream, n, ExpectedVersion::Any);
+            client.put_message(msg).await.unwrap();
+        }
+        let read = GetMessages::default().in_category("count").with_limit(2);
+        let messages: Vec<_> = client.stream_messages(read).collect().await;
+        let data: Vec<_> =
+            messages.into_iter().map(|msg| msg.unwrap().data[0]).collect();
+        assert!(data == [0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn it_waits_for_the_server_to_come_up() {
+        // Find a free port, and leave it closed for now.
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = client(addr);
+        let filter = SubscribeFilter::in_stream("count-1");
+        let mut sub = Box::pin(client.subscribe(0, filter));
+        let reader = client.clone();
+        let info =
+            tokio::spawn(async move { reader.stream_info("count-1").await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let db = new_handle();
+        serve_on(TcpListener::bind(addr).await.unwrap(), &db);
+        let msg = message("count-1", 7, ExpectedVersion::Any);
+        client.put_message(msg).await.unwrap();
+
+        let first = sub.next().await.unwrap().unwrap();
+        assert!(first.data == [7]);
+        assert!(let Ok(Ok(_)) = info.await);
+    }
+
+    #[tokio::test]
+    async fn an_append_waits_for_the_server_to_come_up() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = client(addr);
+        let writer = client.clone();
+        let put = tokio::spawn(async move {
+            let msg = message("count-1", 0, ExpectedVersion::NoStream);
+            writer.put_message(msg).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        serve_on(TcpListener::bind(addr).await.unwrap(), &new_handle());
+        let pos = put.await.unwrap().unwrap();
+        assert!(pos.stream == StreamPos::Sequential(0));
+
+        let mut msg = message("count-1", 1, ExpectedVersion::Any);
+        let id = msg.id;
+        client.put_message(msg.clone()).await.unwrap();
+        msg.stream_name = "count-2".into();
+        assert!(msg.id == id);
+        let err = client.put_message(msg).await.unwrap_err();
+        assert!(let Error::Remote(_) = err);
+    }
+
+    #[tokio::test]
+    async fn event_db_runs_on_a_remote_store() {
+        struct Counted(u8);
+
+        impl mess_ecs::Event for Counted {
+            fn name<'a>(&self) -> Cow<'a, str> {
+                "Counted".into()
+            }
+
+            fn data<'a>(
+                &self,
+            ) -> core::result::Result<Cow<'a, [u8]>, mess_ecs::error::Error<'_>>
+            {
+                Ok(vec![self.0].into())
+            }
+
+            fn metadata<'a>(
+                &self,
+            ) -> core::result::Result<Cow<'a, [u8]>, mess_ecs::error::Error<'_>>
+            {
+                Ok(Cow::Borrowed(&[]))
+            }
+        }
+
+        let events = EventDB::new(serve().await);
+        events
+            .put("count-1", &Counted(1), ExpectedVersion::NoStream)
+            .await
+            .unwrap();
+        let version = events.version("count-1").await.unwrap();
+        assert!(version.is_some());
+    }
+}
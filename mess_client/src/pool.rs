@@ -0,0 +1,70 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use mess_db::error::{Error, Result};
+use mess_grpc::pb::message_store_client::MessageStoreClient;
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    service::{interceptor::InterceptedService, Interceptor},
+    transport::{Channel, Endpoint},
+    Request, Status,
+};
+
+pub(crate) type Grpc = MessageStoreClient<InterceptedService<Channel, Auth>>;
+
+/// Adds the bearer token, if there is one, to every call.
+#[derive(Clone)]
+pub(crate) struct Auth(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for Auth {
+    fn call(
+        &mut self,
+        mut req: Request<()>,
+    ) -> core::result::Result<Request<()>, Status> {
+        if let Some(token) = &self.0 {
+            req.metadata_mut().insert("authorization", token.clone());
+        }
+        Ok(req)
+    }
+}
+
+/// Connections to the server, handed out in turn.
+///
+/// Each one is its own HTTP/2 connection, which carries many calls at once.
+/// They connect on first use and reconnect after failing, so a pool outlives
+/// restarts of the server.
+#[derive(Clone)]
+pub(crate) struct Pool {
+    clients: Arc<[Grpc]>,
+    next: Arc<AtomicUsize>,
+}
+
+impl Pool {
+    pub(crate) fn new(
+        endpoint: &Endpoint,
+        connections: usize,
+        token: Option<&str>,
+    ) -> Result<Self> {
+        let token = token
+            .map(|token| format!("Bearer {token}").parse())
+            .transpose()
+            .map_err(|_| Error::Other("the token is not valid ASCII".into()))?;
+        let clients = (0..connections.max(1))
+            .map(|_| {
+                let channel = endpoint.connect_lazy();
+                MessageStoreClient::with_interceptor(
+                    channel,
+                    Auth(token.clone()),
+                )
+            })
+            .collect();
+        Ok(Self { clients, next: Arc::new(AtomicUsize::new(0)) })
+    }
+
+    pub(crate) fn get(&self) -> Grpc {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        self.clients[next % self.clients.len()].clone()
+    }
+}
@@ -0,0 +1,40 @@
+use mess_db::{error::Error, write::ExpectedVersion, StreamPos};
+use mess_grpc::status::CURRENT_POSITION;
+use tonic::{Code, Status};
+
+/// Returns whether a call may succeed if tried again: the server couldn't
+/// be reached, or was too busy or shutting down.
+pub(crate) fn retryable(status: &Status) -> bool {
+    status.code() == Code::Unavailable
+}
+
+/// Returns the error a status stands for.
+pub(crate) fn error(status: &Status) -> Error {
+    match status.code() {
+        Code::Unavailable => Error::Unavailable(status.message().to_owned()),
+        code => Error::Remote(format!("{code}: {}", status.message())),
+    }
+}
+
+/// Returns the error a failed write stands for, which is the conflict the
+/// embedded store would report when its expected version didn't hold.
+pub(crate) fn write_error(
+    status: &Status,
+    stream: String,
+    expected: ExpectedVersion,
+) -> Error {
+    if status.code() != Code::Aborted {
+        return error(status);
+    }
+    let got = status
+        .metadata()
+        .get(CURRENT_POSITION)
+        .and_then(|pos| pos.to_str().ok()?.parse().ok())
+        .map(|pos| match expected {
+            ExpectedVersion::Exact(StreamPos::Relaxed(_)) => {
+                StreamPos::Relaxed(pos)
+            }
+            _ => StreamPos::Sequential(pos),
+        });
+    Error::WrongStreamPosition { stream, expected, got }
+}
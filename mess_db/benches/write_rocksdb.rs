@@ -59,7 +59,7 @@ fn msg_to_write(expect: Option<u64>) -> WriteMessage<'static> {
         message_type: "someMsgType".into(),
         data: Cow::Borrowed(data),
         metadata: Cow::Borrowed(metadata),
-        expected_stream_position: expect.map(mess_db::StreamPos::Sequential),
+        expected_version: expect.map(mess_db::StreamPos::Sequential).into(),
     }
 }
 
@@ -75,7 +75,7 @@ fn write_a_message(conn: &Connection, expect: Option<StreamPos>) {
         black_box("SomethingHappened"),
         black_box(&data),
         black_box(meta.as_ref()),
-        black_box(expect),
+        black_box(expect.into()),
     )
     .unwrap();
 }
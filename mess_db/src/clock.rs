@@ -14,10 +14,27 @@ pub fn time_to_tick(time: Duration) -> u64 {
     (((time.as_secs_f64() - SECOND_EPOCH as f64) * 20.0) as u64) << 16
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub struct Tick(u64);
 
 impl Tick {
+    /// A tick from its raw value, as stored in a message's `ord`.
+    #[must_use]
+    pub const fn from_u64(value: u64) -> Self {
+        Self(value)
+    }
+
     #[must_use]
     pub const fn to_u64(self) -> u64 {
         self.0
@@ -41,6 +58,15 @@ impl From<Duration> for Tick {
     }
 }
 
+/// Convert from a point in time, such as "yesterday at midnight"
+impl From<SystemTime> for Tick {
+    fn from(value: SystemTime) -> Self {
+        let since_epoch =
+            value.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        Self::from(since_epoch)
+    }
+}
+
 /// Convert from a tuple of (Duration, offset)
 impl From<(Duration, u16)> for Tick {
     fn from(value: (Duration, u16)) -> Self {
@@ -104,7 +130,8 @@ where
         let now = Sys::now();
         let current = Tick::from(now);
         let last = self.last();
-        if last > current {
+        // Never hand out the same tick twice within one clock resolution.
+        if last >= current {
             Tick(last.0 + 1)
         } else {
             current
@@ -228,6 +255,17 @@ mod test_clock {
         assert!(clock.next() == Tick::from(dur_from_ms(1_000_000)));
     }
 
+    #[rstest::rstest]
+    fn next_increments_last_if_current_is_the_same() {
+        let clock = Clock {
+            time: PhantomData::<BrokenClock>,
+            last: Default::default(),
+        };
+        TIME_MS.store(1_000_000, Ordering::SeqCst);
+        clock.observe(clock.next());
+        assert!(clock.next() == Tick::from((dur_from_ms(1_000_000), 1)));
+    }
+
     #[rstest::rstest]
     fn next_increments_last_if_current_is_behind() {
         let clock = Clock {
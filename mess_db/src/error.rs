@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::{write::ExpectedVersion, StreamPos};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("{0}")]
@@ -42,8 +44,8 @@ pub enum Error {
     )]
     WrongStreamPosition {
         stream: String,
-        expected: Option<u64>,
-        got: Option<u64>,
+        expected: ExpectedVersion,
+        got: Option<StreamPos>,
     },
     // #[error("the data for key `{0}` is not available")]
     // Redaction(String),
@@ -51,6 +53,15 @@ pub enum Error {
     // InvalidHeader { expected: String, found: String },
     // #[error("unknown data store error")]
     // Unknown,
+    /// A message with the same id was already written to another stream.
+    #[error("message {id} was already written to {stream}")]
+    IdInUse { id: String, stream: String },
+
+    /// RocksDB can't yet write a message expecting a relaxed stream
+    /// position.
+    #[error("expected a relaxed position in {stream}, which is unsupported")]
+    UnsupportedRelaxed { stream: String },
+
     #[error("could not get prepared statement {}", key)]
     PreparedStmtError { key: usize },
 
@@ -74,12 +85,47 @@ pub enum Error {
 
     #[error("kill was triggered, so action cancelled")]
     Cancelled,
+
+    #[error("request handler panicked: {0}")]
+    Panicked(String),
+
+    #[error("timed out waiting for global position {global_pos}")]
+    Timeout { global_pos: u64 },
+
+    #[error("invalid consumer group member {member} of size {size}")]
+    InvalidConsumerGroup { member: u32, size: u32 },
+
+    #[error("invalid read: {0}")]
+    InvalidRead(&'static str),
+
+    /// A remote store could not be reached, or is shutting down. Reads may
+    /// be tried again.
+    #[error("store unavailable: {0}")]
+    Unavailable(String),
+
+    /// A remote store failed the request.
+    #[error("remote store: {0}")]
+    Remote(String),
 }
 
 impl Error {
     // pub fn external(err: Box<dyn std::error::Error>) -> Self {
     //     Self::External(err)
     // }
+
+    /// For a write which expected an exact stream position, returns how many
+    /// messages were written to the stream since that position.
+    #[must_use]
+    pub const fn messages_behind(&self) -> Option<u64> {
+        match self {
+            Self::WrongStreamPosition {
+                expected: ExpectedVersion::Exact(expected),
+                got: Some(got),
+                ..
+            } => Some(got.position().saturating_sub(expected.position())),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = ::core::result::Result<T, Error>;
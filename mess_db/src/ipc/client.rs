@@ -0,0 +1,244 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use futures::Stream;
+use tokio::{
+    net::UnixStream,
+    sync::{mpsc, oneshot},
+};
+use tracing::debug;
+
+use super::wire::{encode, read_frame, write_frame, Call, Frame, Reply};
+use crate::{
+    error::{Error, Result},
+    read::IntoCursor,
+    store::MessageStore,
+    svc::{Page, SubscribeFilter},
+    write::WriteMessage,
+    OwnedMessage, Position, StreamInfo,
+};
+
+/// How many calls may wait to be written to the socket.
+const CALL_BUFFER: usize = 64;
+
+/// Calls waiting for their reply, or `None` once the connection closed.
+type Pending = Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Reply>>>>>;
+
+fn closed() -> Error {
+    Error::Unavailable("ipc connection closed".into())
+}
+
+async fn open(path: &Path) -> Result<UnixStream> {
+    UnixStream::connect(path)
+        .await
+        .map_err(|err| Error::Unavailable(format!("{}: {err}", path.display())))
+}
+
+/// A store served by another process on this host with
+/// [`serve`](super::serve).
+///
+/// Calls share one connection, which is opened again on the next call after
+/// it closes, while each streamed read or subscription has a connection of
+/// its own. Nothing is retried: a write whose connection closed before it
+/// was answered fails with [`Error::Unavailable`] and may or may not have
+/// been written.
+#[derive(Clone)]
+pub struct IpcClient {
+    path: Arc<Path>,
+    connection: Arc<tokio::sync::Mutex<Option<Connection>>>,
+}
+
+impl IpcClient {
+    /// Connects to the store served at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Unavailable`] if nothing is serving at `path`.
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        let path: Arc<Path> = path.as_ref().into();
+        let connection = Connection::open(&path).await?;
+        Ok(Self {
+            path,
+            connection: Arc::new(tokio::sync::Mutex::new(Some(connection))),
+        })
+    }
+
+    async fn call(&self, call: &Call<'_>) -> Result<Reply> {
+        let connection = {
+            let mut current = self.connection.lock().await;
+            match &*current {
+                Some(connection) if connection.is_open() => connection.clone(),
+                _ => {
+                    debug!(path = ?self.path, "reopening ipc connection");
+                    let connection = Connection::open(&self.path).await?;
+                    current.insert(connection).clone()
+                }
+            }
+        };
+        match connection.call(call).await? {
+            Reply::Failed(failure) => Err(failure.into()),
+            reply => Ok(reply),
+        }
+    }
+
+    /// Streams the messages replied to `call` on a connection of its own,
+    /// which dropping the stream hangs up.
+    fn follow(
+        &self,
+        call: Result<Call<'_>>,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        enum Follow {
+            Start(Arc<Path>, Result<Vec<u8>>),
+            Open(UnixStream),
+        }
+
+        let frame = call.and_then(|call| encode(0, &call));
+        let start = Follow::Start(self.path.clone(), frame);
+        futures::stream::unfold(Some(start), |state| async move {
+            let mut socket = match state? {
+                Follow::Start(path, frame) => {
+                    let opened = async {
+                        let mut socket = open(&path).await?;
+                        write_frame(&mut socket, &frame?)
+                            .await
+                            .map_err(|_| closed())?;
+                        Ok(socket)
+                    };
+                    match opened.await {
+                        Ok(socket) => socket,
+                        Err(err) => return Some((Err(err), None)),
+                    }
+                }
+                Follow::Open(socket) => socket,
+            };
+            match read_frame(&mut socket).await {
+                Ok(Some(Frame { body: Reply::Message(msg), .. })) => {
+                    Some((Ok(msg), Some(Follow::Open(socket))))
+                }
+                Ok(Some(Frame { body: Reply::End, .. })) => None,
+                Ok(Some(Frame { body: Reply::Failed(failure), .. })) => {
+                    Some((Err(failure.into()), None))
+                }
+                Ok(Some(_)) => Some((Err(Error::SvcResponse), None)),
+                Ok(None) => Some((Err(closed()), None)),
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+    }
+}
+
+/// The connection calls share. Its tasks stop once every clone is dropped.
+#[derive(Clone)]
+struct Connection {
+    next_id: Arc<AtomicU64>,
+    pending: Pending,
+    frames: mpsc::Sender<Vec<u8>>,
+}
+
+impl Connection {
+    async fn open(path: &Path) -> Result<Self> {
+        let (mut reader, mut writer) = open(path).await?.into_split();
+        let (frames, mut outbox) = mpsc::channel::<Vec<u8>>(CALL_BUFFER);
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        tokio::spawn(async move {
+            while let Some(frame) = outbox.recv().await {
+                if write_frame(&mut writer, &frame).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let replies = pending.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(frame)) = read_frame::<Reply>(&mut reader).await {
+                let waiting = replies
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .as_mut()
+                    .and_then(|pending| pending.remove(&frame.id));
+                if let Some(waiting) = waiting {
+                    let _ = waiting.send(frame.body);
+                }
+            }
+            // Dropping the senders fails every call still waiting.
+            replies.lock().unwrap_or_else(PoisonError::into_inner).take();
+        });
+        Ok(Self { next_id: Arc::default(), pending, frames })
+    }
+
+    fn is_open(&self) -> bool {
+        let pending =
+            self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.is_some() && !self.frames.is_closed()
+    }
+
+    async fn call(&self, call: &Call<'_>) -> Result<Reply> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let frame = encode(id, call)?;
+        let (send, recv) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+            .ok_or_else(closed)?
+            .insert(id, send);
+        self.frames.send(frame).await.map_err(|_| closed())?;
+        recv.await.map_err(|_| closed())
+    }
+}
+
+impl MessageStore for IpcClient {
+    async fn put_message(&self, wm: WriteMessage<'_>) -> Result<Position> {
+        match self.call(&Call::Put(wm.into())).await? {
+            Reply::Position(pos) => Ok(pos),
+            _ => Err(Error::SvcResponse),
+        }
+    }
+
+    async fn fetch_messages(
+        &self,
+        read: impl IntoCursor + Send,
+    ) -> Result<Vec<Result<OwnedMessage>>> {
+        Ok(self.fetch_page(read).await?.messages)
+    }
+
+    async fn fetch_page(&self, read: impl IntoCursor + Send) -> Result<Page> {
+        match self.call(&Call::Fetch(read.into_cursor()?)).await? {
+            Reply::Page { messages, cursor } => {
+                let messages = messages
+                    .into_iter()
+                    .map(|msg| msg.map_err(Into::into))
+                    .collect();
+                Ok(Page { messages, cursor })
+            }
+            _ => Err(Error::SvcResponse),
+        }
+    }
+
+    async fn stream_info(&self, stream: &str) -> Result<Option<StreamInfo>> {
+        match self.call(&Call::StreamInfo(stream.into())).await? {
+            Reply::StreamInfo(info) => Ok(info),
+            _ => Err(Error::SvcResponse),
+        }
+    }
+
+    fn stream_messages(
+        &self,
+        read: impl IntoCursor,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        self.follow(read.into_cursor().map(Call::Read))
+    }
+
+    fn subscribe(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        self.follow(Ok(Call::Subscribe { from_global, filter }))
+    }
+}
@@ -0,0 +1,185 @@
+//! Sharing a store with other processes on the same host over a Unix domain
+//! socket.
+//!
+//! Only one process can open a RocksDB store for writing. That process
+//! [`serve`]s its [`ActorHandle`](crate::svc::ActorHandle) on a socket, and
+//! the others reach it through an [`IpcClient`], which is a
+//! [`MessageStore`](crate::store::MessageStore) like the handle itself.
+//! Who may connect is up to the socket file's mode, set by [`bind`].
+
+mod client;
+mod server;
+mod wire;
+
+pub use client::IpcClient;
+pub use server::{bind, serve};
+
+#[cfg(all(test, feature = "rocksdb"))]
+mod test {
+    use super::*;
+    use crate::{
+        error::Error,
+        read::GetMessages,
+        rocks::{db::DB, storage::RocksStorage},
+        store::MessageStore,
+        svc::{ActorHandle, SubscribeFilter},
+        write::{ExpectedVersion, WriteMessage},
+        StreamPos,
+    };
+    use assert2::assert;
+    use futures::StreamExt;
+    use ident::Id;
+    use std::{os::unix::fs::PermissionsExt, path::PathBuf, time::Duration};
+    use tokio_util::sync::CancellationToken;
+
+    fn socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!("{}.sock", Id::new()))
+    }
+
+    fn new_handle() -> ActorHandle {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        ActorHandle::new(RocksStorage::new(DB::new(path).unwrap())).unwrap()
+    }
+
+    /// Serves a new store at `path` until the returned token is cancelled.
+    fn start(path: &PathBuf) -> CancellationToken {
+        let listener = bind(path, 0o600).unwrap();
+        let token = CancellationToken::new();
+        let stop = token.clone();
+        tokio::spawn(async move {
+            serve(new_handle(), listener, stop.cancelled_owned()).await;
+        });
+        token
+    }
+
+    fn message(
+        stream: &str,
+        n: u8,
+        version: ExpectedVersion,
+    ) -> WriteMessage<'_> {
+        WriteMessage {
+            id: Id::new(),
+            stream_name: stream.to_owned().into(),
+            message_type: "Counted".into(),
+            data: vec![n].into(),
+            metadata: vec![].into(),
+            expected_version: version,
+        }
+    }
+
+    #[tokio::test]
+    async fn the_socket_has_the_given_mode() {
+        let path = socket_path();
+        drop(bind(&path, 0o600).unwrap());
+        // A second bind replaces the socket left behind.
+        let _listener = bind(&path, 0o660).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert!(mode & 0o777 == 0o660);
+    }
+
+    #[tokio::test]
+    async fn a_live_socket_is_not_replaced() {
+        let path = socket_path();
+        let _listener = bind(&path, 0o600).unwrap();
+        let err = bind(&path, 0o600).unwrap_err();
+        assert!(err.kind() == std::io::ErrorKind::AddrInUse);
+    }
+
+    #[tokio::test]
+    async fn other_files_are_not_removed() {
+        let path = socket_path();
+        std::fs::write(&path, "keep").unwrap();
+        let err = bind(&path, 0o600).unwrap_err();
+        assert!(err.kind() == std::io::ErrorKind::AlreadyExists);
+        assert!(std::fs::read_to_string(&path).unwrap() == "keep");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn clients_write_and_read_like_the_handle() {
+        let path = socket_path();
+        let _server = start(&path).drop_guard();
+        let client = IpcClient::connect(&path).await.unwrap();
+        let writes = (0..3).map(|n| {
+            let client = client.clone();
+            async move {
+                let msg = message("count-1", n, ExpectedVersion::Any);
+                client.put_message(msg).await.unwrap()
+            }
+        });
+        let written = futures::future::join_all(writes).await;
+        assert!(written.len() == 3);
+
+        let read = GetMessages::default().in_stream("count-1").with_limit(2);
+        let page = client.fetch_page(read).await.unwrap();
+        assert!(page.messages.len() == 2);
+        let rest =
+            client.fetch_messages(GetMessages::resume(page.cursor)).await;
+        assert!(rest.unwrap().len() == 1);
+
+        let info = client.stream_info("count-1").await.unwrap().unwrap();
+        assert!(info.position == StreamPos::Sequential(2));
+        assert!(client.stream_info("none-1").await.unwrap().is_none());
+
+        let read = GetMessages::default().in_stream("count-1").with_limit(1);
+        let streamed: Vec<_> = client.stream_messages(read).collect().await;
+        assert!(streamed.len() == 3);
+    }
+
+    #[tokio::test]
+    async fn conflicts_come_back_as_conflicts() {
+        let path = socket_path();
+        let _server = start(&path).drop_guard();
+        let client = IpcClient::connect(&path).await.unwrap();
+        let msg = message("count-1", 0, ExpectedVersion::NoStream);
+        client.put_message(msg).await.unwrap();
+        let msg = message("count-1", 1, ExpectedVersion::NoStream);
+        let err = client.put_message(msg).await.unwrap_err();
+        assert!(err.messages_behind().is_none());
+        assert!(
+            let Error::WrongStreamPosition {
+                got: Some(StreamPos::Sequential(0)),
+                ..
+            } = err
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_new_messages() {
+        let path = socket_path();
+        let _server = start(&path).drop_guard();
+        let client = IpcClient::connect(&path).await.unwrap();
+        let filter = SubscribeFilter::in_stream("count-1");
+        let mut sub = Box::pin(client.subscribe(0, filter));
+        for n in 0..2 {
+            let msg = message("count-1", n, ExpectedVersion::Any);
+            client.put_message(msg).await.unwrap();
+            let received = sub.next().await.unwrap().unwrap();
+            assert!(received.data == [n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_reconnect_after_the_server_comes_back() {
+        let path = socket_path();
+        let server = start(&path);
+        let client = IpcClient::connect(&path).await.unwrap();
+        assert!(client.stream_info("count-1").await.is_ok());
+
+        server.cancel();
+        let mut failed = false;
+        for _ in 0..50 {
+            if let Err(Error::Unavailable(_)) =
+                client.stream_info("count-1").await
+            {
+                failed = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(failed);
+
+        let _server = start(&path).drop_guard();
+        assert!(let Ok(None) = client.stream_info("count-1").await);
+    }
+}
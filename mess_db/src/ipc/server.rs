@@ -0,0 +1,222 @@
+use std::{
+    fs::{DirBuilder, Permissions},
+    future::Future,
+    io::{Error, ErrorKind},
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    path::Path,
+    sync::Arc,
+};
+
+use futures::{Stream, StreamExt};
+use ident::Id;
+use tokio::{
+    net::{UnixListener, UnixStream},
+    sync::{mpsc, Semaphore},
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use super::wire::{encode, read_frame, write_frame, Call, Reply};
+use crate::{
+    error::Result, store::MessageStore, write::WriteMessage, OwnedMessage,
+};
+
+/// How many encoded replies a connection queues before its calls wait on
+/// the socket.
+const REPLY_BUFFER: usize = 64;
+
+/// How many calls a connection may have in flight, subscriptions included,
+/// before the server stops reading its next ones.
+const MAX_IN_FLIGHT: usize = 128;
+
+/// Binds a socket at `path` with the given mode, such as `0o660` to let the
+/// owner's group connect. A socket left behind by a stopped server is
+/// replaced.
+///
+/// The socket is bound in a directory only the owner can enter and moved to
+/// `path` once its mode is set, so it's never reachable with another mode.
+///
+/// # Errors
+///
+/// Fails with [`ErrorKind::AddrInUse`] if a server answers on `path`, with
+/// [`ErrorKind::AlreadyExists`] if something other than a socket is there,
+/// or if the socket can't be bound or its mode can't be set.
+pub fn bind(
+    path: impl AsRef<Path>,
+    mode: u32,
+) -> std::io::Result<UnixListener> {
+    let path = path.as_ref();
+    remove_stale(path)?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = parent.join(format!(".mess-{}", Id::new()));
+    DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("sock");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, Permissions::from_mode(mode))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    bound
+}
+
+/// Removes the socket at `path` if no server answers on it.
+fn remove_stale(path: &Path) -> std::io::Result<()> {
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if !meta.file_type().is_socket() {
+        let msg = format!("{} exists and is not a socket", path.display());
+        return Err(Error::new(ErrorKind::AlreadyExists, msg));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        let msg = format!("a server is listening on {}", path.display());
+        return Err(Error::new(ErrorKind::AddrInUse, msg));
+    }
+    std::fs::remove_file(path)
+}
+
+/// Serves `store` to the processes connecting to `listener` until
+/// `shutdown` resolves, then hangs up on them.
+///
+/// ```no_run
+/// # async fn serve(db: mess_db::svc::ActorHandle) -> std::io::Result<()> {
+/// let listener = mess_db::ipc::bind("/run/mess/mess.sock", 0o660)?;
+/// mess_db::ipc::serve(db, listener, tokio::signal::ctrl_c()).await;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn serve<S: MessageStore, T>(
+    store: S,
+    listener: UnixListener,
+    shutdown: impl Future<Output = T>,
+) {
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            Some(_) = connections.join_next() => {}
+            accepted = listener.accept() => match accepted {
+                Ok((socket, _)) => {
+                    connections.spawn(connection(store.clone(), socket));
+                }
+                Err(err) => warn!(?err, "failed to accept ipc connection"),
+            },
+        }
+    }
+    debug!("ipc server stopping");
+    connections.shutdown().await;
+}
+
+/// Answers the calls on one connection, each in a task of its own, up to
+/// [`MAX_IN_FLIGHT`] at a time, and stops them all once the caller hangs up.
+async fn connection(store: impl MessageStore, socket: UnixStream) {
+    let (mut reader, mut writer) = socket.into_split();
+    let (send, mut recv) = mpsc::channel::<Vec<u8>>(REPLY_BUFFER);
+    let token = CancellationToken::new();
+    let replies = tokio::spawn(async move {
+        while let Some(frame) = recv.recv().await {
+            if let Err(err) = write_frame(&mut writer, &frame).await {
+                debug!(?err, "ipc caller went away");
+                break;
+            }
+        }
+    });
+    let _stop = token.clone().drop_guard();
+    let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+    loop {
+        let frame = match read_frame::<Call>(&mut reader).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(err) => {
+                warn!(?err, "dropping ipc connection");
+                break;
+            }
+        };
+        let Ok(permit) = in_flight.clone().acquire_owned().await else {
+            break;
+        };
+        let (store, send, token) = (store.clone(), send.clone(), token.clone());
+        tokio::spawn(async move {
+            let _permit = permit;
+            tokio::select! {
+                () = token.cancelled() => {}
+                () = answer(&store, frame.id, frame.body, &send) => {}
+            }
+        });
+    }
+    drop(send);
+    replies.abort();
+}
+
+/// Sends a reply, returning whether the connection still takes them.
+async fn reply(send: &mpsc::Sender<Vec<u8>>, id: u64, body: &Reply) -> bool {
+    match encode(id, body) {
+        Ok(frame) => send.send(frame).await.is_ok(),
+        Err(err) => {
+            let failed = Reply::Failed(err.into());
+            let frame = encode(id, &failed).expect("failures fit in a frame");
+            send.send(frame).await.is_ok()
+        }
+    }
+}
+
+async fn answer(
+    store: &impl MessageStore,
+    id: u64,
+    call: Call<'static>,
+    send: &mpsc::Sender<Vec<u8>>,
+) {
+    let body = match call {
+        Call::Put(write) => {
+            let written = match WriteMessage::try_from(write) {
+                Ok(wm) => store.put_message(wm).await,
+                Err(err) => Err(err),
+            };
+            written.map(Reply::Position)
+        }
+        Call::Fetch(cursor) => store.fetch_page(cursor).await.map(|page| {
+            let messages = page
+                .messages
+                .into_iter()
+                .map(|msg| msg.map_err(Into::into))
+                .collect();
+            Reply::Page { messages, cursor: page.cursor }
+        }),
+        Call::StreamInfo(stream) => {
+            store.stream_info(&stream).await.map(Reply::StreamInfo)
+        }
+        Call::Read(cursor) => stream(store.stream_messages(cursor), id, send)
+            .await
+            .map(|()| Reply::End),
+        Call::Subscribe { from_global, filter } => {
+            let messages = store.subscribe(from_global, filter);
+            stream(messages, id, send).await.map(|()| Reply::End)
+        }
+    };
+    let body = body.unwrap_or_else(|err| Reply::Failed(err.into()));
+    reply(send, id, &body).await;
+}
+
+/// Sends each message as a reply until the first error, which is returned
+/// to end the stream with.
+async fn stream(
+    messages: impl Stream<Item = Result<OwnedMessage>>,
+    id: u64,
+    send: &mpsc::Sender<Vec<u8>>,
+) -> Result<()> {
+    futures::pin_mut!(messages);
+    while let Some(msg) = messages.next().await {
+        if !reply(send, id, &Reply::Message(msg?)).await {
+            break;
+        }
+    }
+    Ok(())
+}
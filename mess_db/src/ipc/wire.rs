@@ -0,0 +1,242 @@
+//! The frames both ends exchange.
+//!
+//! A frame is its length as a big-endian `u32` followed by that many bytes
+//! of postcard. Every call carries an id which its replies repeat, so calls
+//! on one connection can be answered out of order.
+
+use std::borrow::Cow;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    error::{Error, Result},
+    read::Cursor,
+    svc::SubscribeFilter,
+    write::{ExpectedVersion, WriteMessage},
+    OwnedMessage, Position, StreamInfo, StreamPos,
+};
+
+/// Frames larger than this are refused rather than allocated.
+pub(crate) const MAX_FRAME: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Frame<T> {
+    pub(crate) id: u64,
+    pub(crate) body: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Call<'a> {
+    Put(Write<'a>),
+    Fetch(Cursor),
+    StreamInfo(Cow<'a, str>),
+    /// Answered by a [`Reply::Message`] per message and then [`Reply::End`].
+    Read(Cursor),
+    /// Answered by a [`Reply::Message`] per message until either end hangs
+    /// up.
+    Subscribe {
+        from_global: u64,
+        filter: SubscribeFilter,
+    },
+}
+
+/// A [`WriteMessage`] with its id as text, as the rocks records keep it.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Write<'a> {
+    id: Cow<'a, str>,
+    stream_name: Cow<'a, str>,
+    message_type: Cow<'a, str>,
+    data: Cow<'a, [u8]>,
+    metadata: Cow<'a, [u8]>,
+    expected_version: ExpectedVersion,
+}
+
+impl<'a> From<WriteMessage<'a>> for Write<'a> {
+    fn from(wm: WriteMessage<'a>) -> Self {
+        Self {
+            id: wm.id.to_string().into(),
+            stream_name: wm.stream_name,
+            message_type: wm.message_type,
+            data: wm.data,
+            metadata: wm.metadata,
+            expected_version: wm.expected_version,
+        }
+    }
+}
+
+impl<'a> TryFrom<Write<'a>> for WriteMessage<'a> {
+    type Error = Error;
+
+    fn try_from(write: Write<'a>) -> Result<Self> {
+        let id = write.id.parse().map_err(|_| {
+            Error::DeserError(format!("invalid message id {}", write.id))
+        })?;
+        Ok(Self {
+            id,
+            stream_name: write.stream_name,
+            message_type: write.message_type,
+            data: write.data,
+            metadata: write.metadata,
+            expected_version: write.expected_version,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Reply {
+    Position(Position),
+    Page {
+        messages: Vec<core::result::Result<OwnedMessage, Failure>>,
+        cursor: Cursor,
+    },
+    StreamInfo(Option<StreamInfo>),
+    Message(OwnedMessage),
+    End,
+    Failed(Failure),
+}
+
+/// An [`Error`] as far as the other end can act on it.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Failure {
+    WrongStreamPosition {
+        stream: String,
+        expected: ExpectedVersion,
+        got: Option<StreamPos>,
+    },
+    Timeout {
+        global_pos: u64,
+    },
+    InvalidConsumerGroup {
+        member: u32,
+        size: u32,
+    },
+    Unavailable(String),
+    Other(String),
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::WrongStreamPosition { stream, expected, got } => {
+                Self::WrongStreamPosition { stream, expected, got }
+            }
+            Error::Timeout { global_pos } => Self::Timeout { global_pos },
+            Error::InvalidConsumerGroup { member, size } => {
+                Self::InvalidConsumerGroup { member, size }
+            }
+            Error::Cancelled | Error::Unavailable(_) => {
+                Self::Unavailable(err.to_string())
+            }
+            err => Self::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Self {
+        match failure {
+            Failure::WrongStreamPosition { stream, expected, got } => {
+                Self::WrongStreamPosition { stream, expected, got }
+            }
+            Failure::Timeout { global_pos } => Self::Timeout { global_pos },
+            Failure::InvalidConsumerGroup { member, size } => {
+                Self::InvalidConsumerGroup { member, size }
+            }
+            Failure::Unavailable(reason) => Self::Unavailable(reason),
+            Failure::Other(reason) => Self::Remote(reason),
+        }
+    }
+}
+
+pub(crate) fn encode<T: Serialize>(id: u64, body: &T) -> Result<Vec<u8>> {
+    let frame = Frame { id, body };
+    // Leave room for the length, which is known once the body is written.
+    let mut bytes = postcard::to_extend(&frame, vec![0; 4])
+        .map_err(|err| Error::SerError(err.to_string()))?;
+    let len = u32::try_from(bytes.len() - 4)
+        .ok()
+        .filter(|&len| len as usize <= MAX_FRAME)
+        .ok_or_else(|| Error::SerError("frame too large".into()))?;
+    bytes[..4].copy_from_slice(&len.to_be_bytes());
+    Ok(bytes)
+}
+
+pub(crate) async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    frame: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(frame).await?;
+    writer.flush().await
+}
+
+/// Reads the next frame, or returns `None` once the other end hung up
+/// between frames.
+pub(crate) async fn read_frame<T: DeserializeOwned>(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<Option<Frame<T>>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Ok(None)
+        }
+        Err(err) => return Err(Error::ReadError(err.to_string())),
+    };
+    if len > MAX_FRAME {
+        return Err(Error::DeserError(format!("frame of {len} bytes")));
+    }
+    let mut bytes = vec![0; len];
+    reader
+        .read_exact(&mut bytes)
+        .await
+        .map_err(|err| Error::ReadError(err.to_string()))?;
+    postcard::from_bytes(&bytes)
+        .map(Some)
+        .map_err(|err| Error::DeserError(err.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+
+    #[tokio::test]
+    async fn frames_round_trip() {
+        let call = Call::StreamInfo("account-1".into());
+        let bytes = encode(7, &call).unwrap();
+        assert!(
+            bytes[..4] == u32::try_from(bytes.len() - 4).unwrap().to_be_bytes()
+        );
+
+        let mut reader = &bytes[..];
+        let frame: Frame<Call> =
+            read_frame(&mut reader).await.unwrap().unwrap();
+        assert!(frame.id == 7);
+        let Call::StreamInfo(stream) = frame.body else {
+            panic!("expected a stream info call");
+        };
+        assert!(stream == "account-1");
+        assert!(let Ok(None) = read_frame::<Call>(&mut reader).await);
+    }
+
+    #[tokio::test]
+    async fn oversized_frames_are_refused() {
+        let bytes = u32::MAX.to_be_bytes();
+        let result = read_frame::<Call>(&mut &bytes[..]).await;
+        assert!(let Err(Error::DeserError(_)) = result);
+    }
+
+    #[test]
+    fn conflicts_cross_intact() {
+        let err = Error::WrongStreamPosition {
+            stream: "account-1".into(),
+            expected: ExpectedVersion::NoStream,
+            got: Some(StreamPos::Sequential(3)),
+        };
+        let err = Error::from(Failure::from(err));
+        assert!(err.to_string().contains("account-1"));
+        assert!(let Error::WrongStreamPosition { got: Some(StreamPos::Sequential(3)), .. } = err);
+        assert!(let Error::Unavailable(_) = Error::from(Failure::from(Error::Cancelled)));
+        assert!(let Error::Remote(_) = Error::from(Failure::from(Error::SendFailed)));
+    }
+}
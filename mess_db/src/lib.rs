@@ -2,10 +2,21 @@
 
 use std::borrow::Cow;
 
+use clock::Tick;
+
+pub mod clock;
 pub mod error;
+#[cfg(unix)]
+pub mod ipc;
+pub mod metrics;
+pub mod position;
 pub mod read;
+pub mod retry;
+#[cfg(feature = "rocksdb")]
 pub mod rocks;
 pub mod rusqlite;
+pub mod storage;
+pub mod store;
 pub mod svc;
 pub mod write;
 
@@ -17,7 +28,17 @@ pub mod write;
 ///
 /// The reason for this distinction is to prevent mixing of the
 /// types within a single stream.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum StreamPos {
     Sequential(u64),
     Relaxed(u64),
@@ -78,7 +99,9 @@ const _: () = {
     qed::const_assert!(Relaxed(0b111).position() == 0b111);
 };
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
 pub struct Position {
     pub global: u64,
     pub stream: StreamPos,
@@ -91,6 +114,22 @@ impl Position {
     }
 }
 
+/// A summary of a stream's messages, read without their data.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub struct StreamInfo {
+    /// The position of the last message, which the next write expects.
+    pub position: StreamPos,
+    pub count: u64,
+    pub first_global: u64,
+    pub last_global: u64,
+    /// When the first message was recorded.
+    pub first_time: Tick,
+    /// When the last message was recorded.
+    pub last_time: Tick,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "sqlx", derive(::sqlx::FromRow))]
 pub struct Message<'a> {
@@ -103,7 +142,7 @@ pub struct Message<'a> {
     pub metadata: Option<Cow<'a, [u8]>>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[cfg_attr(feature = "sqlx", derive(::sqlx::FromRow))]
 pub struct OwnedMessage {
     pub global_position: u64,
@@ -152,8 +191,12 @@ impl TryFrom<&::rusqlite::Row<'_>> for Message<'_> {
             // time_ms: row.get(2)?,
             stream_name: Cow::Owned(row.get(3)?),
             message_type: Cow::Owned(row.get(4)?),
-            data: Cow::Owned(row.get(5)?),
-            metadata: row.get::<_, Option<Vec<u8>>>(6)?.map(Cow::Owned),
+            // data and metadata are stored as JSON text
+            data: Cow::Owned(row.get_ref(5)?.as_bytes()?.to_vec()),
+            metadata: row
+                .get_ref(6)?
+                .as_bytes_or_null()?
+                .map(|meta| Cow::Owned(meta.to_vec())),
             // id: row.get(7)?,
         })
     }
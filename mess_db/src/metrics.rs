@@ -0,0 +1,176 @@
+//! What the store records through the [`metrics`] facade. Nothing is kept
+//! until the application installs a recorder, such as the Prometheus one in
+//! [`prometheus`] with the `prometheus` feature.
+//!
+//! | Name | Kind | Labels |
+//! |------|------|--------|
+//! | [`APPENDS`] | counter | |
+//! | [`CONFLICTS`] | counter | |
+//! | [`APPEND_SECONDS`] | histogram | |
+//! | [`READ_SECONDS`] | histogram | `request` |
+//! | [`READ_BATCH`] | histogram | |
+//! | [`QUEUED`] | gauge | `lane`: `reads` or `writes` |
+//! | [`QUEUE_SECONDS`] | histogram | `lane` |
+//! | [`CONSUMER_LAG`] | gauge | `consumer`, `member` |
+//! | [`MEMTABLE_BYTES`] | gauge | `cf` |
+//! | [`PENDING_COMPACTION_BYTES`] | gauge | `cf` |
+//! | [`RUNNING_COMPACTIONS`] | gauge | |
+//!
+//! The last four are sampled by the actor every [`SAMPLE_INTERVAL`] rather
+//! than as they change.
+
+use std::time::Duration;
+
+use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+
+pub const APPENDS: &str = "mess_appends_total";
+pub const CONFLICTS: &str = "mess_append_conflicts_total";
+pub const APPEND_SECONDS: &str = "mess_append_seconds";
+pub const READ_SECONDS: &str = "mess_read_seconds";
+pub const READ_BATCH: &str = "mess_read_batch_messages";
+pub const QUEUED: &str = "mess_requests_queued";
+pub const QUEUE_SECONDS: &str = "mess_request_queue_seconds";
+pub const CONSUMER_LAG: &str = "mess_consumer_lag_messages";
+pub const MEMTABLE_BYTES: &str = "mess_rocksdb_memtable_bytes";
+pub const PENDING_COMPACTION_BYTES: &str =
+    "mess_rocksdb_pending_compaction_bytes";
+pub const RUNNING_COMPACTIONS: &str = "mess_rocksdb_running_compactions";
+
+/// How often the actor samples consumer lag and the backend's own stats.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Describes the metrics to the installed recorder. Recorders which render
+/// help text need it called after they are installed.
+pub fn describe() {
+    describe_counter!(APPENDS, "Messages written.");
+    describe_counter!(
+        CONFLICTS,
+        "Writes rejected because the stream was not at the expected version."
+    );
+    describe_histogram!(
+        APPEND_SECONDS,
+        Unit::Seconds,
+        "Time taken to write a message."
+    );
+    describe_histogram!(
+        READ_SECONDS,
+        Unit::Seconds,
+        "Time taken to answer a read request."
+    );
+    describe_histogram!(
+        READ_BATCH,
+        Unit::Count,
+        "Messages returned by a read."
+    );
+    describe_gauge!(
+        QUEUED,
+        Unit::Count,
+        "Requests waiting for the writer or the reader pool."
+    );
+    describe_histogram!(
+        QUEUE_SECONDS,
+        Unit::Seconds,
+        "Time requests waited before being handled."
+    );
+    describe_gauge!(
+        CONSUMER_LAG,
+        Unit::Count,
+        "Messages written after a consumer's saved position."
+    );
+    describe_gauge!(
+        MEMTABLE_BYTES,
+        Unit::Bytes,
+        "Size of RocksDB's memtables."
+    );
+    describe_gauge!(
+        PENDING_COMPACTION_BYTES,
+        Unit::Bytes,
+        "Bytes RocksDB estimates compaction has to rewrite."
+    );
+    describe_gauge!(
+        RUNNING_COMPACTIONS,
+        Unit::Count,
+        "Compactions RocksDB is running."
+    );
+}
+
+/// Renders the metrics in the Prometheus text format.
+#[cfg(feature = "prometheus")]
+pub mod prometheus {
+    use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+    pub use metrics_exporter_prometheus::{
+        PrometheusHandle, PrometheusRecorder,
+    };
+
+    use super::{describe, READ_BATCH};
+    use crate::error::{Error, Result};
+
+    const SECONDS_BUCKETS: &[f64] = &[
+        0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01,
+        0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+    ];
+
+    const BATCH_BUCKETS: &[f64] =
+        &[1.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10_000.0];
+
+    fn other(err: impl ToString) -> Error {
+        Error::Other(err.to_string())
+    }
+
+    /// Returns an exporter with buckets for the histograms, for applications
+    /// which configure it further.
+    ///
+    /// # Errors
+    ///
+    /// Never fails with these buckets, but the exporter checks them.
+    pub fn builder() -> Result<PrometheusBuilder> {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Suffix("_seconds".into()),
+                SECONDS_BUCKETS,
+            )
+            .and_then(|builder| {
+                builder.set_buckets_for_metric(
+                    Matcher::Full(READ_BATCH.into()),
+                    BATCH_BUCKETS,
+                )
+            })
+            .map_err(other)
+    }
+
+    /// Installs the exporter as the process's recorder, returning the handle
+    /// which renders what it recorded.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a recorder was already installed.
+    pub fn install() -> Result<PrometheusHandle> {
+        let handle = builder()?.install_recorder().map_err(other)?;
+        describe();
+        Ok(handle)
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::metrics::{APPENDS, APPEND_SECONDS};
+        use assert2::assert;
+
+        #[test]
+        fn it_renders_the_text_format() {
+            let recorder = builder().unwrap().build_recorder();
+            let handle = recorder.handle();
+            metrics::with_local_recorder(&recorder, || {
+                describe();
+                metrics::counter!(APPENDS).increment(2);
+                metrics::histogram!(APPEND_SECONDS).record(0.003);
+            });
+            let text = handle.render();
+            assert!(
+                text.contains("# HELP mess_appends_total Messages written.")
+            );
+            assert!(text.contains("mess_appends_total 2"));
+            assert!(text.contains("mess_append_seconds_bucket{le=\"0.005\"} 1"));
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Durable consumer positions.
+//!
+//! A consumer records the global position of the last message it handled so
+//! it can pick up from there after a restart. Positions are kept in the
+//! database itself, keyed by the consumer's name and, for consumer groups,
+//! its member number. Saving a position in the same batch as a write makes
+//! "process and emit" exactly-once within the store.
+
+use serde::{Deserialize, Serialize};
+
+/// Identifies whose position is being stored.
+#[derive(
+    Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct ConsumerId {
+    name: String,
+    member: Option<u32>,
+}
+
+impl ConsumerId {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_owned(), member: None }
+    }
+
+    /// The position of one member of a consumer group, stored separately
+    /// from the other members.
+    #[must_use]
+    pub fn member(self, member: u32) -> Self {
+        Self { member: Some(member), ..self }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn group_member(&self) -> Option<u32> {
+        self.member
+    }
+
+    /// Returns the key used by the RocksDB backend: `name|` followed by the
+    /// big-endian member number, if any.
+    #[must_use]
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut key = Vec::with_capacity(self.name.len() + 5);
+        key.extend_from_slice(self.name.as_bytes());
+        key.push(b'|');
+        if let Some(member) = self.member {
+            key.extend_from_slice(&member.to_be_bytes());
+        }
+        key
+    }
+}
+
+/// Tracks a consumer's position in memory and decides when it is due to be
+/// saved, so that not every handled message costs a write.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    consumer: ConsumerId,
+    save_interval: u64,
+    saved: Option<u64>,
+    current: Option<u64>,
+    /// How many messages were handled since the last save.
+    unsaved_count: u64,
+}
+
+impl Checkpoint {
+    /// Starts tracking from the last `saved` position, saving every time
+    /// `save_interval` messages were handled since the last save, however
+    /// far apart their global positions are. An interval of 0 or 1 saves
+    /// after every message.
+    #[must_use]
+    pub const fn new(
+        consumer: ConsumerId,
+        saved: Option<u64>,
+        save_interval: u64,
+    ) -> Self {
+        Self {
+            consumer,
+            save_interval,
+            saved,
+            current: saved,
+            unsaved_count: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn consumer(&self) -> &ConsumerId {
+        &self.consumer
+    }
+
+    /// The last handled global position.
+    #[must_use]
+    pub const fn position(&self) -> Option<u64> {
+        self.current
+    }
+
+    /// The global position to resume reading from.
+    #[must_use]
+    pub const fn next(&self) -> u64 {
+        match self.current {
+            Some(pos) => pos + 1,
+            None => 0,
+        }
+    }
+
+    /// Records that the message at `global_pos` was handled, returning the
+    /// position to save if one is due.
+    pub fn handled(&mut self, global_pos: u64) -> Option<u64> {
+        self.current = Some(global_pos);
+        self.unsaved_count += 1;
+        let due = self.saved.is_none()
+            || self.unsaved_count >= self.save_interval.max(1);
+        due.then_some(global_pos)
+    }
+
+    /// Records that `global_pos` was saved.
+    pub const fn saved(&mut self, global_pos: u64) {
+        self.saved = Some(global_pos);
+        self.unsaved_count = 0;
+    }
+
+    /// Returns the position to save if it moved since the last save, for
+    /// flushing when a consumer stops.
+    #[must_use]
+    pub const fn unsaved(&self) -> Option<u64> {
+        match (self.current, self.saved) {
+            (Some(current), Some(saved)) if current <= saved => None,
+            (current, _) => current,
+        }
+    }
+
+    /// Forgets the tracked position, as after resetting the stored one.
+    pub const fn reset(&mut self) {
+        self.saved = None;
+        self.current = None;
+        self.unsaved_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod test_checkpoint {
+    use super::*;
+    use assert2::assert;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0, &[1, 2, 3], &[Some(1), Some(2), Some(3)])]
+    #[case(3, &[1, 2, 3, 4, 7], &[Some(1), None, None, Some(4), None])]
+    #[case(2, &[5, 90, 150, 151], &[Some(5), None, Some(150), None])]
+    fn it_saves_on_the_interval(
+        #[case] interval: u64,
+        #[case] handled: &[u64],
+        #[case] expected: &[Option<u64>],
+    ) {
+        let mut cp = Checkpoint::new(ConsumerId::new("c"), None, interval);
+        let due: Vec<_> = handled
+            .iter()
+            .map(|pos| {
+                let due = cp.handled(*pos);
+                if let Some(pos) = due {
+                    cp.saved(pos);
+                }
+                due
+            })
+            .collect();
+        assert!(due == expected);
+    }
+
+    #[test]
+    fn it_resumes_after_the_saved_position() {
+        let mut cp = Checkpoint::new(ConsumerId::new("c"), Some(41), 5);
+        assert!(cp.next() == 42);
+        assert!(cp.unsaved().is_none());
+        assert!(cp.handled(42).is_none());
+        assert!(cp.unsaved() == Some(42));
+        cp.reset();
+        assert!(cp.next() == 0);
+    }
+
+    #[test]
+    fn members_have_distinct_keys() {
+        let consumer = ConsumerId::new("c");
+        assert!(consumer.as_bytes() == b"c|");
+        assert!(consumer.clone().member(1).as_bytes() == b"c|\0\0\0\x01");
+        let other = consumer.clone().member(2);
+        assert!(consumer.member(1).as_bytes() != other.as_bytes());
+    }
+}
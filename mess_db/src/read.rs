@@ -1,6 +1,10 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt::Display, str::FromStr};
 
-use crate::StreamPos;
+use crate::{
+    clock::Tick,
+    error::{Error, Result},
+    OwnedMessage, StreamPos,
+};
 
 pub const LIMIT_MAX: usize = 10_000;
 pub const LIMIT_DEFAULT: usize = 1_000;
@@ -37,6 +41,39 @@ pub struct OptStream<'a>(pub(crate) Cow<'a, str>);
 pub struct OptGlobalPos(pub(crate) u64);
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct OptStreamPos(pub(crate) StreamPos);
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptCursor(pub(crate) Cursor);
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptCategory<'a>(pub(crate) Cow<'a, str>);
+/// Messages whose `ord` is in `start..end`, from `global_pos` onward.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub struct OptTimeRange {
+    pub(crate) start: u64,
+    pub(crate) end: u64,
+    pub(crate) global_pos: u64,
+}
+
+/// The order messages are read in.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum Direction {
+    /// Oldest first, from the start position onward.
+    #[default]
+    Forward,
+    /// Newest first, from the start position back to the beginning.
+    Backward,
+}
 
 #[derive(Clone, PartialEq, PartialOrd)]
 pub struct GetMessages<Strm, Gpos, Spos> {
@@ -44,6 +81,8 @@ pub struct GetMessages<Strm, Gpos, Spos> {
     pub(crate) start_stream_position: Spos,
     pub(crate) limit: usize,
     pub(crate) stream: Strm,
+    pub(crate) filter: ReadFilter,
+    pub(crate) direction: Direction,
 }
 
 impl<Strm, Gpos, Spos> GetMessages<Strm, Gpos, Spos> {
@@ -68,6 +107,49 @@ impl<Strm, Gpos, Spos> GetMessages<Strm, Gpos, Spos> {
             start_stream_position: self.start_stream_position,
             limit: self.limit,
             stream: self.stream,
+            filter: self.filter,
+            direction: self.direction,
+        }
+    }
+}
+
+impl<Strm, Gpos, Spos> GetMessages<Strm, Gpos, Spos> {
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn from_stream_pos(
+        self,
+        position: StreamPos,
+    ) -> GetMessages<Strm, Gpos, OptStreamPos> {
+        GetMessages {
+            start_global_position: self.start_global_position,
+            start_stream_position: OptStreamPos(position),
+            limit: self.limit,
+            stream: self.stream,
+            filter: self.filter,
+            direction: self.direction,
+        }
+    }
+}
+
+impl<Strm, Gpos, Spos> GetMessages<Strm, Gpos, Spos> {
+    /// Read the messages recorded from `start` up to, but not including,
+    /// `end`. The backend seeks straight to `start` by the messages' `ord`.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn between(
+        self,
+        start: Tick,
+        end: Tick,
+    ) -> GetMessages<Strm, OptTimeRange, Spos> {
+        GetMessages {
+            start_global_position: OptTimeRange {
+                start: start.to_u64(),
+                end: end.to_u64(),
+                global_pos: 0,
+            },
+            start_stream_position: self.start_stream_position,
+            limit: self.limit,
+            stream: self.stream,
+            filter: self.filter,
+            direction: self.direction,
         }
     }
 }
@@ -80,6 +162,98 @@ impl<Strm, Gpos, Spos> GetMessages<Strm, Gpos, Spos> {
             start_stream_position: self.start_stream_position,
             limit: self.limit,
             stream: OptStream(name.into()),
+            filter: self.filter,
+            direction: self.direction,
+        }
+    }
+}
+
+impl<Strm, Gpos, Spos> GetMessages<Strm, Gpos, Spos> {
+    /// Read the messages of every stream in a category, in global order.
+    /// A stream is in the category [`category`] returns for its name, so a
+    /// stream named just `category` is in it too.
+    pub fn in_category(
+        self,
+        category: &str,
+    ) -> GetMessages<OptCategory<'_>, Gpos, Spos> {
+        let category = category.to_string();
+        GetMessages {
+            start_global_position: self.start_global_position,
+            start_stream_position: self.start_stream_position,
+            limit: self.limit,
+            stream: OptCategory(category.into()),
+            filter: self.filter,
+            direction: self.direction,
+        }
+    }
+}
+
+impl<Strm, Gpos, Spos> GetMessages<Strm, Gpos, Spos> {
+    /// Only read messages whose type is one of `types`. The backend applies
+    /// the filter, and the limit counts matching messages only.
+    #[must_use]
+    pub fn of_types(mut self, types: &[impl AsRef<str>]) -> Self {
+        let types = types.iter().map(|t| t.as_ref().to_owned()).collect();
+        self.filter.types = Some(types);
+        self
+    }
+
+    /// Read newest first. The start positions are then the newest messages
+    /// to read, and without one the read starts at the end.
+    #[must_use]
+    pub const fn backwards(mut self) -> Self {
+        self.direction = Direction::Backward;
+        self
+    }
+}
+
+impl<Gpos, Spos> GetMessages<Unset, Gpos, Spos> {
+    /// Only read messages whose `correlationStreamName` metadata is in the
+    /// given category.
+    #[must_use]
+    pub fn correlation(mut self, category: &str) -> Self {
+        self.filter.correlation = Some(category.to_owned());
+        self
+    }
+}
+
+impl<Gpos, Spos> GetMessages<OptCategory<'_>, Gpos, Spos> {
+    /// Only read messages whose `correlationStreamName` metadata is in the
+    /// given category.
+    #[must_use]
+    pub fn correlation(mut self, category: &str) -> Self {
+        self.filter.correlation = Some(category.to_owned());
+        self
+    }
+
+    /// Only read the streams assigned to `member` of a consumer group with
+    /// `size` members.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `size` is zero or `member` is not less than `size`.
+    pub fn consumer_group(mut self, member: u32, size: u32) -> Result<Self> {
+        self.filter.consumer_group = Some(ConsumerGroup::new(member, size)?);
+        Ok(self)
+    }
+}
+
+impl GetMessages<Unset, Unset, Unset> {
+    /// Continue reading exactly where the page which returned the cursor
+    /// ended, with the same read kind, filters and limit.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn resume(mut cursor: Cursor) -> GetMessages<OptCursor, Unset, Unset> {
+        let limit = cursor.limit;
+        let filter = std::mem::take(&mut cursor.filter);
+        let direction = cursor.direction;
+        GetMessages {
+            start_global_position: Unset,
+            start_stream_position: Unset,
+            limit,
+            stream: OptCursor(cursor),
+            filter,
+            direction,
         }
     }
 }
@@ -91,10 +265,600 @@ impl Default for GetMessages<Unset, Unset, Unset> {
             start_stream_position: Default::default(),
             limit: LIMIT_DEFAULT,
             stream: Default::default(),
+            filter: ReadFilter::default(),
+            direction: Direction::Forward,
+        }
+    }
+}
+
+/// What a [`Cursor`] continues reading and where from.
+///
+/// Backward reads continue before their position rather than from it, so
+/// the oldest message can be read without a position below it.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) enum CursorRead {
+    Global { stream: Option<String>, global_pos: u64 },
+    Stream { stream: String, stream_pos: Option<StreamPos> },
+    Category { category: String, global_pos: u64 },
+    Between { category: Option<String>, range: OptTimeRange },
+}
+
+/// An opaque continuation token returned with each page of messages.
+///
+/// It encodes the read kind, its filters and the next position to read from,
+/// and round-trips through its string form, so it can be handed to HTTP
+/// clients or stored by jobs which need to resume after a crash.
+///
+/// ```
+/// use mess_db::read::{Cursor, GetMessages};
+/// let cursor = Cursor::global(None, 42, 100);
+/// let token = cursor.to_string();
+/// let cursor: Cursor = token.parse().unwrap();
+/// let _next_page = GetMessages::resume(cursor);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Cursor {
+    pub(crate) read: CursorRead,
+    pub(crate) limit: usize,
+    pub(crate) filter: ReadFilter,
+    pub(crate) direction: Direction,
+}
+
+/// Filters which a backend applies while reading, before counting messages
+/// toward the limit.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct ReadFilter {
+    pub(crate) types: Option<Vec<String>>,
+    pub(crate) correlation: Option<String>,
+    pub(crate) consumer_group: Option<ConsumerGroup>,
+}
+
+impl ReadFilter {
+    /// A filter which lets every message through.
+    pub(crate) const fn none() -> Self {
+        Self { types: None, correlation: None, consumer_group: None }
+    }
+
+    pub(crate) fn wants_stream(&self, stream_name: &str) -> bool {
+        match &self.consumer_group {
+            Some(group) => group.is_assigned(stream_name),
+            None => true,
+        }
+    }
+
+    pub(crate) fn wants_type(&self, message_type: &str) -> bool {
+        match &self.types {
+            Some(types) => types.iter().any(|t| t == message_type),
+            None => true,
+        }
+    }
+
+    /// Returns whether the message metadata passes the correlation filter.
+    /// Metadata which is missing or not JSON never matches a correlation.
+    pub(crate) fn wants_metadata(&self, metadata: Option<&[u8]>) -> bool {
+        #[derive(serde::Deserialize)]
+        struct Correlation<'a> {
+            #[serde(rename = "correlationStreamName", borrow)]
+            stream_name: Option<Cow<'a, str>>,
         }
+
+        let Some(correlation) = &self.correlation else {
+            return true;
+        };
+        metadata
+            .and_then(|meta| serde_json::from_slice::<Correlation>(meta).ok())
+            .and_then(|meta| meta.stream_name)
+            .is_some_and(|name| category(&name) == correlation)
     }
 }
 
+/// Returns the category of a stream, which is the part of its name before
+/// the first `-`, or the whole name if it has no id.
+#[must_use]
+pub fn category(stream_name: &str) -> &str {
+    stream_name.split_once('-').map_or(stream_name, |(category, _)| category)
+}
+
+/// Returns the id of a stream, which is the part of its name after the first
+/// `-`, or the whole name if it has no id. This matches the `stream_id`
+/// column of the SQLite schema.
+#[must_use]
+pub fn stream_id(stream_name: &str) -> &str {
+    stream_name.split_once('-').map_or(stream_name, |(_, id)| id)
+}
+
+/// Returns the cardinal id of a stream, which is the part of its id before
+/// the first `+`, or the whole id if it is not compound.
+#[must_use]
+pub fn cardinal_id(stream_name: &str) -> &str {
+    let id = stream_id(stream_name);
+    match id.split_once('+') {
+        Some((cardinal, _)) if !cardinal.is_empty() => cardinal,
+        _ => id,
+    }
+}
+
+/// A stable 63-bit FNV-1a hash, so that every process and both backends
+/// assign streams to the same consumer group members.
+#[must_use]
+pub const fn hash_64(value: &str) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let bytes = value.as_bytes();
+    let mut hash = OFFSET;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(PRIME);
+        i += 1;
+    }
+    // SQLite integers are signed, so drop the top bit.
+    hash >> 1
+}
+
+/// One member of a group of consumers which split a category between them.
+/// Each stream is assigned to exactly one member by the hash of its cardinal
+/// id, so all messages of a stream are read by the same member.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct ConsumerGroup {
+    pub(crate) member: u32,
+    pub(crate) size: u32,
+}
+
+impl ConsumerGroup {
+    /// # Errors
+    ///
+    /// Fails if `size` is zero or `member` is not less than `size`.
+    pub const fn new(member: u32, size: u32) -> Result<Self> {
+        if member >= size {
+            return Err(Error::InvalidConsumerGroup { member, size });
+        }
+        Ok(Self { member, size })
+    }
+
+    #[must_use]
+    pub const fn member(&self) -> u32 {
+        self.member
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns whether the stream is assigned to this member.
+    #[must_use]
+    pub fn is_assigned(&self, stream_name: &str) -> bool {
+        hash_64(cardinal_id(stream_name)) % u64::from(self.size)
+            == u64::from(self.member)
+    }
+}
+
+impl Cursor {
+    #[must_use]
+    pub const fn global(
+        stream: Option<String>,
+        global_pos: u64,
+        limit: usize,
+    ) -> Self {
+        Self {
+            read: CursorRead::Global { stream, global_pos },
+            limit,
+            filter: ReadFilter::none(),
+            direction: Direction::Forward,
+        }
+    }
+
+    #[must_use]
+    pub const fn stream(
+        stream: String,
+        stream_pos: Option<StreamPos>,
+        limit: usize,
+    ) -> Self {
+        Self {
+            read: CursorRead::Stream { stream, stream_pos },
+            limit,
+            filter: ReadFilter::none(),
+            direction: Direction::Forward,
+        }
+    }
+
+    #[must_use]
+    pub const fn category(
+        category: String,
+        global_pos: u64,
+        limit: usize,
+    ) -> Self {
+        Self {
+            read: CursorRead::Category { category, global_pos },
+            limit,
+            filter: ReadFilter::none(),
+            direction: Direction::Forward,
+        }
+    }
+
+    /// A read of the messages recorded from `start` up to `end`, optionally
+    /// in a single category.
+    #[must_use]
+    pub const fn between(
+        category: Option<String>,
+        start: Tick,
+        end: Tick,
+        limit: usize,
+    ) -> Self {
+        let range = OptTimeRange {
+            start: start.to_u64(),
+            end: end.to_u64(),
+            global_pos: 0,
+        };
+        Self {
+            read: CursorRead::Between { category, range },
+            limit,
+            filter: ReadFilter::none(),
+            direction: Direction::Forward,
+        }
+    }
+
+    #[allow(clippy::missing_const_for_fn)]
+    pub(crate) fn with_filter(mut self, filter: ReadFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    #[must_use]
+    pub const fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Returns the cursor which continues after the given message.
+    #[must_use]
+    pub fn after(self, last: &OwnedMessage) -> Self {
+        let (global_pos, stream_pos) = match self.direction {
+            Direction::Forward => {
+                (last.global_position + 1, last.stream_position.next())
+            }
+            Direction::Backward => (last.global_position, last.stream_position),
+        };
+        let read = match self.read {
+            CursorRead::Global { stream, .. } => {
+                CursorRead::Global { stream, global_pos }
+            }
+            CursorRead::Stream { stream, .. } => {
+                CursorRead::Stream { stream, stream_pos: Some(stream_pos) }
+            }
+            CursorRead::Category { category, .. } => {
+                CursorRead::Category { category, global_pos }
+            }
+            CursorRead::Between { category, range } => CursorRead::Between {
+                category,
+                range: OptTimeRange { global_pos, ..range },
+            },
+        };
+        Self { read, ..self }
+    }
+
+    /// Returns the cursor which continues after a page of messages. Reading
+    /// resumes at the first error, if there is one.
+    #[must_use]
+    pub fn after_page(self, messages: &[Result<OwnedMessage>]) -> Self {
+        match messages.iter().map_while(|res| res.as_ref().ok()).last() {
+            Some(last) => self.after(last),
+            None => self,
+        }
+    }
+
+    #[must_use]
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Checks what a decoded token could get wrong, which a cursor built by
+    /// this crate never does.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the limit is out of range or the time range ends before it
+    /// starts.
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=LIMIT_MAX).contains(&self.limit) {
+            return Err(Error::InvalidRead("the limit is out of range"));
+        }
+        if let CursorRead::Between { range, .. } = &self.read {
+            if range.end < range.start {
+                return Err(Error::InvalidRead("the time range is reversed"));
+            }
+        }
+        // A decoded cursor's group didn't go through `ConsumerGroup::new`.
+        if let Some(group) = self.filter.consumer_group {
+            ConsumerGroup::new(group.member, group.size)?;
+        }
+        Ok(())
+    }
+
+    /// Builds the cursor for the start of a read from the parts of a
+    /// [`GetMessages`], rejecting the combinations no read can honour.
+    fn from_parts(
+        scope: Scope,
+        start: Start,
+        stream_pos: Option<StreamPos>,
+        limit: usize,
+        filter: ReadFilter,
+        direction: Direction,
+    ) -> Result<Self> {
+        let global_pos = |pos: Option<u64>| match (direction, pos) {
+            (Direction::Forward, pos) => pos.unwrap_or(0),
+            (Direction::Backward, Some(pos)) => pos.saturating_add(1),
+            (Direction::Backward, None) => u64::MAX,
+        };
+        let time_range = |range: OptTimeRange| match direction {
+            Direction::Forward => range,
+            Direction::Backward => {
+                OptTimeRange { global_pos: u64::MAX, ..range }
+            }
+        };
+        let read = match (scope, start, stream_pos) {
+            (Scope::Cursor(cursor), Start::Unset, None) => {
+                if cursor.direction != direction {
+                    return Err(Error::InvalidRead(
+                        "a resumed read keeps its direction",
+                    ));
+                }
+                cursor.read
+            }
+            (Scope::Cursor(_), _, _) => {
+                return Err(Error::InvalidRead(
+                    "a resumed read already has a start position",
+                ))
+            }
+            (Scope::All | Scope::Category(_), _, Some(_)) => {
+                return Err(Error::InvalidRead(
+                    "a stream position needs a stream",
+                ))
+            }
+            (Scope::Stream(_), Start::Global(_) | Start::Time(_), Some(_)) => {
+                return Err(Error::InvalidRead(
+                    "a read starts from a global or a stream position, not both",
+                ))
+            }
+            (Scope::Stream(_), Start::Time(_), None) => {
+                return Err(Error::InvalidRead(
+                    "time ranges read the global log or a category",
+                ))
+            }
+            (Scope::All, Start::Unset, None) => {
+                CursorRead::Global { stream: None, global_pos: global_pos(None) }
+            }
+            (Scope::All, Start::Global(pos), None) => CursorRead::Global {
+                stream: None,
+                global_pos: global_pos(Some(pos)),
+            },
+            (Scope::All, Start::Time(range), None) => {
+                CursorRead::Between { category: None, range: time_range(range) }
+            }
+            (Scope::Stream(stream), Start::Unset, stream_pos) => {
+                let stream_pos = match direction {
+                    Direction::Forward => stream_pos,
+                    Direction::Backward => stream_pos.map(StreamPos::next),
+                };
+                CursorRead::Stream { stream, stream_pos }
+            }
+            (Scope::Stream(stream), Start::Global(pos), None) => {
+                CursorRead::Global {
+                    stream: Some(stream),
+                    global_pos: global_pos(Some(pos)),
+                }
+            }
+            (Scope::Category(category), Start::Unset, None) => {
+                CursorRead::Category { category, global_pos: global_pos(None) }
+            }
+            (Scope::Category(category), Start::Global(pos), None) => {
+                CursorRead::Category {
+                    category,
+                    global_pos: global_pos(Some(pos)),
+                }
+            }
+            (Scope::Category(category), Start::Time(range), None) => {
+                CursorRead::Between {
+                    category: Some(category),
+                    range: time_range(range),
+                }
+            }
+        };
+        let cursor = Self { read, limit, filter, direction };
+        cursor.validate()?;
+        Ok(cursor)
+    }
+
+    /// Encode the cursor as an opaque, URL-safe token.
+    #[must_use]
+    pub fn encode(&self) -> String {
+        let bytes = postcard::to_allocvec(self)
+            .expect("cursor serialization cannot fail");
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Decode a cursor from a token made by [`Cursor::encode`], rejecting
+    /// one no read can honour.
+    pub fn decode(token: &str) -> Result<Self> {
+        let invalid = || Error::DeserError("invalid cursor".to_string());
+        if token.len() & 1 == 1 || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+            .collect::<core::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let cursor: Self =
+            postcard::from_bytes(&bytes).map_err(|_| invalid())?;
+        cursor.validate()?;
+        Ok(cursor)
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.encode())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::decode(s)
+    }
+}
+
+/// Where a read looks, from the first type state of a [`GetMessages`].
+enum Scope {
+    All,
+    Stream(String),
+    Category(String),
+    Cursor(Cursor),
+}
+
+impl From<Unset> for Scope {
+    fn from(_: Unset) -> Self {
+        Self::All
+    }
+}
+
+impl From<OptStream<'_>> for Scope {
+    fn from(stream: OptStream<'_>) -> Self {
+        Self::Stream(stream.0.into_owned())
+    }
+}
+
+impl From<OptCategory<'_>> for Scope {
+    fn from(category: OptCategory<'_>) -> Self {
+        Self::Category(category.0.into_owned())
+    }
+}
+
+impl From<OptCursor> for Scope {
+    fn from(cursor: OptCursor) -> Self {
+        Self::Cursor(cursor.0)
+    }
+}
+
+/// Where a read starts, from the second type state of a [`GetMessages`].
+enum Start {
+    Unset,
+    Global(u64),
+    Time(OptTimeRange),
+}
+
+impl From<Unset> for Start {
+    fn from(_: Unset) -> Self {
+        Self::Unset
+    }
+}
+
+impl From<OptGlobalPos> for Start {
+    fn from(pos: OptGlobalPos) -> Self {
+        Self::Global(pos.0)
+    }
+}
+
+impl From<OptTimeRange> for Start {
+    fn from(range: OptTimeRange) -> Self {
+        Self::Time(range)
+    }
+}
+
+impl From<Unset> for Option<StreamPos> {
+    fn from(_: Unset) -> Self {
+        None
+    }
+}
+
+impl From<OptStreamPos> for Option<StreamPos> {
+    fn from(pos: OptStreamPos) -> Self {
+        Some(pos.0)
+    }
+}
+
+/// A complete read: every [`GetMessages`] the builder allows, or a
+/// [`Cursor`] to resume. Both backends and the service execute the cursor
+/// it turns into.
+pub trait IntoCursor {
+    /// # Errors
+    ///
+    /// Fails with [`Error::InvalidRead`] for a combination of options no
+    /// read can honour, such as a stream position without a stream.
+    fn into_cursor(self) -> Result<Cursor>;
+}
+
+impl IntoCursor for Cursor {
+    fn into_cursor(self) -> Result<Cursor> {
+        self.validate()?;
+        Ok(self)
+    }
+}
+
+macro_rules! impl_into_cursor {
+    ($($strm:ty, $gpos:ty, $spos:ty;)*) => {$(
+        impl IntoCursor for GetMessages<$strm, $gpos, $spos> {
+            fn into_cursor(self) -> Result<Cursor> {
+                Cursor::from_parts(
+                    self.stream.into(),
+                    self.start_global_position.into(),
+                    self.start_stream_position.into(),
+                    self.limit,
+                    self.filter,
+                    self.direction,
+                )
+            }
+        }
+    )*};
+}
+
+impl_into_cursor! {
+    Unset, Unset, Unset;
+    Unset, Unset, OptStreamPos;
+    Unset, OptGlobalPos, Unset;
+    Unset, OptGlobalPos, OptStreamPos;
+    Unset, OptTimeRange, Unset;
+    Unset, OptTimeRange, OptStreamPos;
+    OptStream<'_>, Unset, Unset;
+    OptStream<'_>, Unset, OptStreamPos;
+    OptStream<'_>, OptGlobalPos, Unset;
+    OptStream<'_>, OptGlobalPos, OptStreamPos;
+    OptStream<'_>, OptTimeRange, Unset;
+    OptStream<'_>, OptTimeRange, OptStreamPos;
+    OptCategory<'_>, Unset, Unset;
+    OptCategory<'_>, Unset, OptStreamPos;
+    OptCategory<'_>, OptGlobalPos, Unset;
+    OptCategory<'_>, OptGlobalPos, OptStreamPos;
+    OptCategory<'_>, OptTimeRange, Unset;
+    OptCategory<'_>, OptTimeRange, OptStreamPos;
+    OptCursor, Unset, Unset;
+    OptCursor, Unset, OptStreamPos;
+    OptCursor, OptGlobalPos, Unset;
+    OptCursor, OptGlobalPos, OptStreamPos;
+    OptCursor, OptTimeRange, Unset;
+    OptCursor, OptTimeRange, OptStreamPos;
+}
+
 pub(crate) enum GetMessagesOptions<'a> {
     Global {
         start_position: u64,
@@ -196,6 +960,80 @@ mod test {
             }
         }
 
+        mod from_stream_pos {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[rstest]
+            async fn default_is_unset() {
+                let get = GetMessages::default();
+                assert_eq!(get.start_stream_position, Unset);
+            }
+
+            #[rstest]
+            async fn it_sets_given_position() {
+                let get = GetMessages::default()
+                    .in_stream("a-stream")
+                    .from_stream_pos(StreamPos::Sequential(7));
+                assert_eq!(
+                    get.start_stream_position,
+                    OptStreamPos(StreamPos::Sequential(7))
+                );
+            }
+        }
+
+        mod resume {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[rstest]
+            async fn it_uses_the_cursor_limit() {
+                let get = GetMessages::resume(Cursor::global(None, 9, 25));
+                assert_eq!(get.limit, 25);
+            }
+
+            #[rstest]
+            async fn the_limit_can_be_overridden() {
+                let get = GetMessages::resume(Cursor::global(None, 9, 25))
+                    .with_limit(5);
+                assert_eq!(get.limit, 5);
+                assert_eq!(get.stream.0, Cursor::global(None, 9, 25));
+            }
+        }
+
+        mod of_types {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[rstest]
+            async fn default_is_none() {
+                let get = GetMessages::default();
+                assert_eq!(get.filter, ReadFilter::default());
+            }
+
+            #[rstest]
+            async fn it_sets_the_given_types() {
+                let get = GetMessages::default()
+                    .from_global(0)
+                    .of_types(&["Opened", "Closed"]);
+                let expected = vec!["Opened".to_owned(), "Closed".to_owned()];
+                assert_eq!(get.filter.types, Some(expected));
+            }
+
+            #[rstest]
+            async fn resume_keeps_the_cursor_types() {
+                let filter = ReadFilter {
+                    types: Some(vec!["Opened".to_owned()]),
+                    ..Default::default()
+                };
+                let cursor =
+                    Cursor::global(None, 9, 25).with_filter(filter.clone());
+                let get = GetMessages::resume(cursor);
+                assert_eq!(get.filter, filter);
+                assert_eq!(get.stream.0.filter, ReadFilter::default());
+            }
+        }
+
         mod with_limit {
             use super::*;
             use pretty_assertions::assert_eq;
@@ -219,4 +1057,333 @@ mod test {
             }
         }
     }
+
+    mod test_read_filter {
+        use super::*;
+        use assert2::assert;
+
+        fn correlated(category: &str) -> ReadFilter {
+            ReadFilter {
+                correlation: Some(category.to_owned()),
+                ..Default::default()
+            }
+        }
+
+        #[rstest]
+        #[case("account-123", "account")]
+        #[case("account:command-123", "account:command")]
+        #[case("account-123+456", "account")]
+        #[case("account", "account")]
+        fn category_is_the_part_before_the_first_dash(
+            #[case] stream_name: &str,
+            #[case] expected: &str,
+        ) {
+            assert!(category(stream_name) == expected);
+        }
+
+        #[rstest]
+        #[case(br#"{"correlationStreamName":"replies-1"}"#, true)]
+        #[case(br#"{"correlationStreamName":"replies"}"#, true)]
+        #[case(br#"{"correlationStreamName":"repliesX-1"}"#, false)]
+        #[case(br#"{"correlationStreamName":"other-1"}"#, false)]
+        #[case(br#"{"causationStreamName":"replies-1"}"#, false)]
+        #[case(b"not json", false)]
+        fn it_matches_the_correlation_category(
+            #[case] metadata: &[u8],
+            #[case] expected: bool,
+        ) {
+            let filter = correlated("replies");
+            assert!(filter.wants_metadata(Some(metadata)) == expected);
+        }
+
+        #[rstest]
+        #[case("account-123", "123")]
+        #[case("account-123+456", "123")]
+        #[case("account-+456", "+456")]
+        #[case("account:command-abc-def", "abc-def")]
+        #[case("account", "account")]
+        fn cardinal_id_is_the_stream_id_before_the_first_plus(
+            #[case] stream_name: &str,
+            #[case] expected: &str,
+        ) {
+            assert!(cardinal_id(stream_name) == expected);
+        }
+
+        #[rstest]
+        fn hash_64_is_stable() {
+            assert!(hash_64("") == 0xcbf2_9ce4_8422_2325 >> 1);
+            assert!(hash_64("a") == 0xaf63_dc4c_8601_ec8c >> 1);
+        }
+
+        #[rstest]
+        #[case(0, 0)]
+        #[case(2, 2)]
+        #[case(3, 2)]
+        fn invalid_consumer_groups_are_rejected(
+            #[case] member: u32,
+            #[case] size: u32,
+        ) {
+            assert!(ConsumerGroup::new(member, size).is_err());
+        }
+
+        #[rstest]
+        fn each_stream_is_assigned_to_exactly_one_member() {
+            let members: Vec<_> =
+                (0..3).map(|i| ConsumerGroup::new(i, 3).unwrap()).collect();
+            for i in 0..100 {
+                let stream_name = format!("account-{i}+extra");
+                let assigned = members
+                    .iter()
+                    .filter(|group| group.is_assigned(&stream_name))
+                    .count();
+                assert!(assigned == 1);
+            }
+        }
+
+        #[rstest]
+        fn compound_ids_go_to_the_same_member_as_their_cardinal_id() {
+            let group = ConsumerGroup::new(1, 4).unwrap();
+            for i in 0..20 {
+                let cardinal = format!("account-{i}");
+                let compound = format!("account-{i}+other");
+                assert!(
+                    group.is_assigned(&cardinal)
+                        == group.is_assigned(&compound)
+                );
+            }
+        }
+
+        #[rstest]
+        fn missing_metadata_is_never_correlated() {
+            assert!(!correlated("replies").wants_metadata(None));
+            assert!(ReadFilter::default().wants_metadata(None));
+        }
+    }
+
+    mod test_into_cursor {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn read(cursor: Result<Cursor>) -> CursorRead {
+            cursor.unwrap().read
+        }
+
+        #[rstest]
+        fn it_starts_each_read_where_its_builder_says() {
+            let get = GetMessages::default;
+            let stream = || Some("s-1".to_owned());
+            let pos = StreamPos::Sequential(4);
+            assert_eq!(
+                read(get().into_cursor()),
+                CursorRead::Global { stream: None, global_pos: 0 }
+            );
+            assert_eq!(
+                read(get().in_stream("s-1").from_global(9).into_cursor()),
+                CursorRead::Global { stream: stream(), global_pos: 9 }
+            );
+            assert_eq!(
+                read(get().in_stream("s-1").from_stream_pos(pos).into_cursor()),
+                CursorRead::Stream {
+                    stream: "s-1".into(),
+                    stream_pos: Some(pos)
+                }
+            );
+            assert_eq!(
+                read(get().in_category("s").from_global(9).into_cursor()),
+                CursorRead::Category { category: "s".into(), global_pos: 9 }
+            );
+        }
+
+        #[rstest]
+        fn backward_reads_start_after_the_given_position() {
+            let get = || GetMessages::default().backwards();
+            let pos = StreamPos::Sequential(4);
+            assert_eq!(
+                read(get().into_cursor()),
+                CursorRead::Global { stream: None, global_pos: u64::MAX }
+            );
+            assert_eq!(
+                read(get().from_global(9).into_cursor()),
+                CursorRead::Global { stream: None, global_pos: 10 }
+            );
+            assert_eq!(
+                read(get().in_stream("s-1").from_stream_pos(pos).into_cursor()),
+                CursorRead::Stream {
+                    stream: "s-1".into(),
+                    stream_pos: Some(StreamPos::Sequential(5))
+                }
+            );
+        }
+
+        #[rstest]
+        #[case(GetMessages::default().from_stream_pos(StreamPos::Sequential(1)).into_cursor())]
+        #[case(GetMessages::default()
+            .in_category("s")
+            .from_stream_pos(StreamPos::Sequential(1))
+            .into_cursor())]
+        #[case(GetMessages::default()
+            .in_stream("s-1")
+            .from_global(1)
+            .from_stream_pos(StreamPos::Sequential(1))
+            .into_cursor())]
+        #[case(GetMessages::default()
+            .in_stream("s-1")
+            .between(Tick::from_u64(0), Tick::from_u64(1))
+            .into_cursor())]
+        #[case(GetMessages::default()
+            .between(Tick::from_u64(2), Tick::from_u64(1))
+            .into_cursor())]
+        #[case(GetMessages::resume(Cursor::global(None, 0, 10))
+            .from_global(3)
+            .into_cursor())]
+        #[case(GetMessages::resume(Cursor::global(None, 0, 10))
+            .backwards()
+            .into_cursor())]
+        #[case(Cursor::global(None, 0, 0).into_cursor())]
+        fn it_rejects_reads_it_cannot_honour(#[case] cursor: Result<Cursor>) {
+            assert!(matches!(cursor, Err(Error::InvalidRead(_))));
+        }
+    }
+
+    mod test_cursor {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[rstest]
+        #[case(Cursor::global(None, 0, 1))]
+        #[case(Cursor::global(Some("stream-1".into()), 1 << 40, LIMIT_MAX))]
+        #[case(Cursor::stream("stream-1".into(), None, 10))]
+        #[case(Cursor::stream(
+            "stream-1".into(),
+            Some(StreamPos::Relaxed(77)),
+            10
+        ))]
+        #[case(Cursor::global(None, 5, 10).with_filter(ReadFilter {
+            types: Some(vec!["A".into()]),
+            correlation: Some("replies".into()),
+            consumer_group: Some(ConsumerGroup { member: 1, size: 3 }),
+        }))]
+        #[case(Cursor::category("account".into(), 3, 10))]
+        #[case(Cursor::between(
+            Some("account".into()),
+            Tick::from_u64(1 << 30),
+            Tick::from_u64(1 << 31),
+            10
+        ))]
+        fn it_round_trips_through_a_string(#[case] cursor: Cursor) {
+            let token = cursor.to_string();
+            assert_eq!(token.parse::<Cursor>().unwrap(), cursor);
+        }
+
+        #[rstest]
+        #[case(ConsumerGroup { member: 0, size: 0 })]
+        #[case(ConsumerGroup { member: 3, size: 3 })]
+        fn it_rejects_a_decoded_invalid_consumer_group(
+            #[case] group: ConsumerGroup,
+        ) {
+            let cursor = Cursor::category("account".into(), 0, 10).with_filter(
+                ReadFilter {
+                    consumer_group: Some(group),
+                    ..ReadFilter::none()
+                },
+            );
+            let res = cursor.to_string().parse::<Cursor>();
+            assert!(matches!(res, Err(Error::InvalidConsumerGroup { .. })));
+            assert!(matches!(
+                cursor.validate(),
+                Err(Error::InvalidConsumerGroup { .. })
+            ));
+        }
+
+        fn message(global_position: u64, stream_pos: u64) -> OwnedMessage {
+            OwnedMessage {
+                global_position,
+                stream_position: StreamPos::Sequential(stream_pos),
+                stream_name: "stream-1".into(),
+                message_type: "X".into(),
+                data: vec![],
+                metadata: None,
+            }
+        }
+
+        #[rstest]
+        fn after_page_continues_after_the_last_message() {
+            let page = [Ok(message(3, 0)), Ok(message(8, 1))];
+            let global = Cursor::global(None, 0, 2).after_page(&page);
+            assert_eq!(global, Cursor::global(None, 9, 2));
+            let stream =
+                Cursor::stream("stream-1".into(), None, 2).after_page(&page);
+            assert_eq!(
+                stream,
+                Cursor::stream(
+                    "stream-1".into(),
+                    Some(StreamPos::Sequential(2)),
+                    2
+                )
+            );
+        }
+
+        #[rstest]
+        fn after_page_stops_at_the_first_error() {
+            let page = [
+                Ok(message(3, 0)),
+                Err(Error::ReadError("x".into())),
+                Ok(message(8, 1)),
+            ];
+            let cursor = Cursor::global(None, 0, 3).after_page(&page);
+            assert_eq!(cursor, Cursor::global(None, 4, 3));
+        }
+
+        #[rstest]
+        fn after_an_empty_page_is_unchanged() {
+            let cursor = Cursor::global(None, 7, 3).after_page(&[]);
+            assert_eq!(cursor, Cursor::global(None, 7, 3));
+        }
+
+        #[rstest]
+        fn backward_reads_continue_before_the_last_message() {
+            let page = [Ok(message(8, 1)), Ok(message(3, 0))];
+            let global = GetMessages::default().backwards().with_limit(2);
+            let global = global.into_cursor().unwrap().after_page(&page);
+            assert_eq!(
+                global.read,
+                CursorRead::Global { stream: None, global_pos: 3 }
+            );
+            let stream = GetMessages::default().in_stream("stream-1");
+            let stream = stream.backwards().into_cursor().unwrap();
+            assert_eq!(
+                stream.after_page(&page).read,
+                CursorRead::Stream {
+                    stream: "stream-1".into(),
+                    stream_pos: Some(StreamPos::Sequential(0))
+                }
+            );
+        }
+
+        #[rstest]
+        fn the_direction_round_trips_and_resumes() {
+            let cursor = GetMessages::default()
+                .in_category("account")
+                .backwards()
+                .into_cursor()
+                .unwrap();
+            let token = cursor.to_string();
+            let decoded: Cursor = token.parse().unwrap();
+            assert_eq!(decoded.direction(), Direction::Backward);
+            let resumed = GetMessages::resume(decoded).into_cursor().unwrap();
+            assert_eq!(resumed, cursor);
+        }
+
+        #[rstest]
+        #[case("")]
+        #[case("abc")]
+        #[case("zz")]
+        #[case("ff00ff00ff00ff00")]
+        fn it_rejects_invalid_tokens(#[case] token: &str) {
+            assert!(matches!(
+                token.parse::<Cursor>(),
+                Err(Error::DeserError(_))
+            ));
+        }
+    }
 }
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+/// How a caller retries failures worth retrying, doubling the wait after
+/// every attempt. Clients use it to retry calls and reconnect, and
+/// projection runners to retry transient failures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retry {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Retry {
+    /// Never retry.
+    pub const NEVER: Self = Self {
+        max_retries: 0,
+        initial_backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+    };
+
+    #[must_use]
+    #[inline]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1));
+        factor
+            .map_or(self.max_backoff, |f| {
+                self.initial_backoff.saturating_mul(f)
+            })
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for Retry {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn the_backoff_doubles_up_to_its_max() {
+        let retry = Retry {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        let waits: Vec<_> =
+            (1..=6).map(|n| retry.backoff(n).as_millis()).collect();
+        assert!(waits == [100, 200, 400, 800, 1000, 1000]);
+        assert!(retry.backoff(100) == retry.max_backoff);
+    }
+}
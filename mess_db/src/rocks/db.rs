@@ -1,15 +1,33 @@
 use std::{
     ops::{Deref, DerefMut},
     path::Path,
+    sync::atomic::AtomicU64,
+    time::{Duration, SystemTime},
 };
 
-use rocksdb::{ColumnFamilyDescriptor, ColumnFamilyRef, Options};
-use tracing::debug;
+use rocksdb::{
+    ColumnFamilyDescriptor, ColumnFamilyRef, IteratorMode, Options, WriteBatch,
+};
+use tracing::{debug, info};
+
+use super::{keys::GlobalKey, record::GlobalRecord};
+use crate::{
+    clock::{Clock, Tick},
+    error::{Error, Result},
+};
 
-use crate::error::Result;
+/// How often a secondary catches up while waiting for a position.
+pub const CATCH_UP_INTERVAL: Duration = Duration::from_millis(10);
 
 pub struct DB {
     db: ::rocksdb::DB,
+    // Whether this is a secondary instance, which has to catch up to see
+    // new writes.
+    secondary: bool,
+    // The last written global position, or 0 until it has been read.
+    pub(crate) last_global: AtomicU64,
+    // Stamps each message's ord, which only ever increases.
+    pub(crate) clock: Clock<SystemTime>,
 }
 
 fn opts() -> Options {
@@ -21,6 +39,9 @@ fn opts() -> Options {
     opts
 }
 
+/// How many index entries a backfill writes at a time.
+const BACKFILL_BATCH: usize = 10_000;
+
 fn new_cf(name: &str) -> ColumnFamilyDescriptor {
     ColumnFamilyDescriptor::new(name, opts())
 }
@@ -34,9 +55,153 @@ impl DB {
         let db = rocksdb::DB::open_cf_descriptors(
             &db_opts,
             path,
-            vec![new_cf("global"), new_cf("stream")],
+            vec![
+                new_cf("global"),
+                new_cf("stream"),
+                new_cf("position"),
+                new_cf("time"),
+                new_cf("id"),
+            ],
+        )?;
+        let db = Self {
+            db,
+            secondary: false,
+            last_global: AtomicU64::new(0),
+            clock: Clock::default(),
+        };
+        db.observe_last_ord()?;
+        db.backfill(db.time(), "time", |_, record| record.ord.to_be_bytes())?;
+        db.backfill(db.id(), "id", |_, record| record.id.to_string())?;
+        Ok(db)
+    }
+
+    /// Opens the database at `path` next to the process writing to it, as a
+    /// secondary instance keeping its own files at `secondary_path`. It
+    /// can't write, and doesn't see what is written after it opens until
+    /// [`DB::catch_up`].
+    pub fn open_secondary(
+        path: impl AsRef<Path>,
+        secondary_path: impl AsRef<Path>,
+    ) -> Result<Self> {
+        debug!(path = %path.as_ref().to_string_lossy(), "opened secondary db");
+
+        let mut db_opts = opts();
+        // Secondaries have to keep every table file open.
+        db_opts.set_max_open_files(-1);
+        let db = rocksdb::DB::open_cf_descriptors_as_secondary(
+            &db_opts,
+            path.as_ref(),
+            secondary_path.as_ref(),
+            vec![
+                new_cf("global"),
+                new_cf("stream"),
+                new_cf("position"),
+                new_cf("time"),
+                new_cf("id"),
+            ],
         )?;
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            secondary: true,
+            last_global: AtomicU64::new(0),
+            clock: Clock::default(),
+        })
+    }
+
+    /// Brings a secondary instance up to date with what the writer has
+    /// written.
+    pub fn catch_up(&self) -> Result<()> {
+        self.db.try_catch_up_with_primary()?;
+        Ok(())
+    }
+
+    /// Waits until the message at `global_pos` can be read, such as after a
+    /// write through the writer returned its position. A secondary can't
+    /// hear of another process's writes, so it catches up every
+    /// [`CATCH_UP_INTERVAL`] until it sees the message.
+    pub async fn wait_for_global(
+        &self,
+        global_pos: u64,
+        timeout: Duration,
+    ) -> Result<()> {
+        let wait = async {
+            let mut interval = tokio::time::interval(CATCH_UP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if self.secondary {
+                    self.catch_up()?;
+                }
+                if self.last_written()? >= global_pos {
+                    return Ok(());
+                }
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .unwrap_or(Err(Error::Timeout { global_pos }))
+    }
+
+    /// Returns the global position of the last message this instance can
+    /// read, without the cache the writer keeps.
+    fn last_written(&self) -> Result<u64> {
+        let last = self
+            .iterator_cf(self.global(), IteratorMode::End)
+            .next()
+            .transpose()?;
+        match last {
+            Some((key, _)) => Ok(GlobalKey::from_bytes(&key)?.0),
+            None => Ok(0),
+        }
+    }
+
+    /// Fills an index of the global CF for the messages written before the
+    /// index existed, keying each message's global key by `key`. The newest
+    /// are indexed first, so the index holds the oldest message only once
+    /// it's complete, and an interrupted backfill starts over on the next
+    /// open.
+    fn backfill<K: AsRef<[u8]>>(
+        &self,
+        index: ColumnFamilyRef<'_>,
+        name: &str,
+        key: impl Fn(&[u8], &GlobalRecord) -> K,
+    ) -> Result<()> {
+        let Some((global, value)) = self
+            .iterator_cf(self.global(), IteratorMode::Start)
+            .next()
+            .transpose()?
+        else {
+            return Ok(());
+        };
+        let oldest = GlobalRecord::from_bytes(&value)?;
+        if self.get_cf(&index, key(&global, &oldest))?.is_some() {
+            return Ok(());
+        }
+        info!(index = name, "indexing the messages written before it");
+        let mut batch = WriteBatch::default();
+        for entry in self.iterator_cf(self.global(), IteratorMode::End) {
+            let (global, value) = entry?;
+            let record = GlobalRecord::from_bytes(&value)?;
+            batch.put_cf(&index, key(&global, &record), &global);
+            if batch.len() >= BACKFILL_BATCH {
+                self.write(std::mem::take(&mut batch))?;
+            }
+        }
+        self.write(batch)?;
+        Ok(())
+    }
+
+    /// Moves the clock past the newest message, so ords keep increasing
+    /// across restarts even if the system clock went backwards.
+    fn observe_last_ord(&self) -> Result<()> {
+        let last = self
+            .iterator_cf(self.global(), IteratorMode::End)
+            .next()
+            .transpose()?;
+        if let Some((_, value)) = last {
+            let record = GlobalRecord::from_bytes(&value)?;
+            self.clock.observe(Tick::from_u64(record.ord));
+        }
+        Ok(())
     }
 
     #[must_use]
@@ -48,6 +213,30 @@ impl DB {
     pub fn stream(&self) -> ColumnFamilyRef<'_> {
         self.db.cf_handle("stream").expect("no stream column family")
     }
+
+    #[must_use]
+    pub fn position(&self) -> ColumnFamilyRef<'_> {
+        self.db.cf_handle("position").expect("no position column family")
+    }
+
+    /// The time index, mapping each message's ord to its global position.
+    #[must_use]
+    pub fn time(&self) -> ColumnFamilyRef<'_> {
+        self.db.cf_handle("time").expect("no time column family")
+    }
+
+    /// The id index, mapping each message's id to its global position.
+    #[must_use]
+    pub fn id(&self) -> ColumnFamilyRef<'_> {
+        self.db.cf_handle("id").expect("no id column family")
+    }
+
+    /// Syncs the write-ahead log to disk and closes the database.
+    pub fn close(self) -> Result<()> {
+        self.db.flush_wal(true)?;
+        drop(self);
+        Ok(())
+    }
 }
 
 impl Deref for DB {
@@ -66,9 +255,10 @@ impl DerefMut for DB {
 
 #[cfg(test)]
 pub(crate) mod test {
+    use assert2::assert;
     use ident::Id;
 
-    use super::DB;
+    use super::{Duration, DB};
 
     pub(crate) struct SelfDestructingDB(Option<DB>);
 
@@ -101,4 +291,111 @@ pub(crate) mod test {
             ::rocksdb::DB::destroy(&rocksdb::Options::default(), path).unwrap();
         }
     }
+
+    #[test]
+    fn messages_written_before_the_time_index_are_indexed() {
+        use crate::{
+            clock::Tick,
+            rocks::{
+                read::position_at_time,
+                write::{write_mess, WriteSerializer},
+            },
+            write::{ExpectedVersion, WriteMessage},
+        };
+        use rocksdb::IteratorMode;
+
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        let db = DB::new(&path).unwrap();
+        let mut ser = WriteSerializer::new();
+        for _ in 0..3 {
+            let message = WriteMessage {
+                id: Id::new(),
+                stream_name: "account-1".into(),
+                message_type: "Opened".into(),
+                data: b"{}"[..].into(),
+                metadata: b""[..].into(),
+                expected_version: ExpectedVersion::Any,
+            };
+            write_mess(&db, message, &mut ser).unwrap();
+        }
+        // As if the messages were written before the index existed.
+        let keys: Vec<_> = db
+            .iterator_cf(db.time(), IteratorMode::Start)
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        for key in keys {
+            db.delete_cf(db.time(), key).unwrap();
+        }
+        assert!(position_at_time(&db, Tick::from_u64(0)).unwrap().is_none());
+        db.close().unwrap();
+
+        let db = DB::new(&path).unwrap();
+        assert!(position_at_time(&db, Tick::from_u64(0)).unwrap() == Some(1));
+        let count = db.iterator_cf(db.time(), IteratorMode::Start).count();
+        assert!(count == 3);
+        drop(db);
+        ::rocksdb::DB::destroy(&rocksdb::Options::default(), path).unwrap();
+    }
+
+    #[test]
+    fn secondaries_see_writes_after_catching_up() {
+        use crate::{
+            rocks::{
+                read::stream_info,
+                write::{write_mess, WriteSerializer},
+            },
+            write::{ExpectedVersion, WriteMessage},
+        };
+
+        let db = SelfDestructingDB::new_tmp();
+        let mut ser = WriteSerializer::new();
+        let message = || WriteMessage {
+            id: Id::new(),
+            stream_name: "account-1".into(),
+            message_type: "Opened".into(),
+            data: b"{}"[..].into(),
+            metadata: b""[..].into(),
+            expected_version: ExpectedVersion::Any,
+        };
+        write_mess(&db, message(), &mut ser).unwrap();
+
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        let secondary = DB::open_secondary(db.path(), &path).unwrap();
+        assert!(stream_info(&secondary, "account-1").unwrap().is_some());
+        write_mess(&db, message(), &mut ser).unwrap();
+        secondary.catch_up().unwrap();
+        let info = stream_info(&secondary, "account-1").unwrap().unwrap();
+        assert!(info.count == 2);
+        assert!(write_mess(&secondary, message(), &mut ser).is_err());
+    }
+
+    #[tokio::test]
+    async fn secondaries_wait_for_a_position() {
+        use crate::{
+            error::Error,
+            rocks::write::{write_mess, WriteSerializer},
+            write::{ExpectedVersion, WriteMessage},
+        };
+
+        let db = SelfDestructingDB::new_tmp();
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        let secondary = DB::open_secondary(db.path(), &path).unwrap();
+        let message = WriteMessage {
+            id: Id::new(),
+            stream_name: "account-1".into(),
+            message_type: "Opened".into(),
+            data: b"{}"[..].into(),
+            metadata: b""[..].into(),
+            expected_version: ExpectedVersion::Any,
+        };
+        let pos = write_mess(&db, message, &mut WriteSerializer::new())
+            .unwrap()
+            .global;
+        let timeout = Duration::from_secs(5);
+        secondary.wait_for_global(pos, timeout).await.unwrap();
+
+        let res =
+            secondary.wait_for_global(pos + 1, Duration::from_millis(30)).await;
+        assert!(matches!(res, Err(Error::Timeout { global_pos: 2 })));
+    }
 }
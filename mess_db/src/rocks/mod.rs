@@ -1,6 +1,7 @@
-pub mod clock;
 pub mod db;
 pub mod keys;
+pub mod position;
 pub mod read;
 pub mod record;
+pub mod storage;
 pub mod write;
@@ -0,0 +1,121 @@
+use super::db::DB;
+use crate::{
+    error::{Error, Result},
+    position::ConsumerId,
+};
+
+/// Returns the consumer's last saved global position, if it has one.
+pub fn load_position(db: &DB, consumer: &ConsumerId) -> Result<Option<u64>> {
+    let Some(bytes) = db.get_cf(db.position(), consumer.as_bytes())? else {
+        return Ok(None);
+    };
+    let bytes: [u8; 8] = bytes.as_slice().try_into().map_err(|_| {
+        Error::ReadError(format!("position of {}", consumer.name()))
+    })?;
+    Ok(Some(u64::from_be_bytes(bytes)))
+}
+
+/// Saves the consumer's position on its own. Use
+/// [`write_mess_with_position`](super::write::write_mess_with_position) to
+/// save it along with a write instead.
+pub fn save_position(
+    db: &DB,
+    consumer: &ConsumerId,
+    global_pos: u64,
+) -> Result<()> {
+    db.put_cf(db.position(), consumer.as_bytes(), global_pos.to_be_bytes())?;
+    Ok(())
+}
+
+/// Forgets the consumer's position so it starts again from the beginning.
+pub fn reset_position(db: &DB, consumer: &ConsumerId) -> Result<()> {
+    db.delete_cf(db.position(), consumer.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use assert2::assert;
+    use ident::Id;
+
+    use super::*;
+    use crate::{
+        rocks::{
+            db::test::SelfDestructingDB,
+            write::{write_mess_with_position, WriteSerializer},
+        },
+        write::{ExpectedVersion, WriteMessage},
+        StreamPos,
+    };
+
+    #[test]
+    fn it_loads_what_was_saved() {
+        let db = SelfDestructingDB::new_tmp();
+        let consumer = ConsumerId::new("projector");
+        assert!(load_position(&db, &consumer).unwrap().is_none());
+        save_position(&db, &consumer, 7).unwrap();
+        save_position(&db, &consumer, 12).unwrap();
+        assert!(load_position(&db, &consumer).unwrap() == Some(12));
+        reset_position(&db, &consumer).unwrap();
+        assert!(load_position(&db, &consumer).unwrap().is_none());
+    }
+
+    #[test]
+    fn group_members_are_stored_separately() {
+        let db = SelfDestructingDB::new_tmp();
+        let consumer = ConsumerId::new("projector");
+        save_position(&db, &consumer.clone().member(0), 3).unwrap();
+        save_position(&db, &consumer.clone().member(1), 5).unwrap();
+        assert!(load_position(&db, &consumer).unwrap().is_none());
+        assert!(
+            load_position(&db, &consumer.clone().member(0)).unwrap() == Some(3)
+        );
+        assert!(load_position(&db, &consumer.member(1)).unwrap() == Some(5));
+    }
+
+    #[test]
+    fn a_failed_write_does_not_save_the_position() {
+        let db = SelfDestructingDB::new_tmp();
+        let consumer = ConsumerId::new("projector");
+        let mut ser = WriteSerializer::new();
+        let msg = WriteMessage {
+            id: Id::new(),
+            stream_name: "stream1".into(),
+            message_type: "someMsgType".into(),
+            data: Cow::Borrowed(b"{}"),
+            metadata: Cow::Borrowed(b""),
+            expected_version: ExpectedVersion::NoStream,
+        };
+        write_mess_with_position(&db, msg.clone(), &mut ser, &consumer, 1)
+            .unwrap();
+        assert!(load_position(&db, &consumer).unwrap() == Some(1));
+
+        let mut stale = msg;
+        stale.id = Id::new();
+        stale.expected_version =
+            ExpectedVersion::Exact(StreamPos::Sequential(5));
+        let res = write_mess_with_position(&db, stale, &mut ser, &consumer, 2);
+        assert!(res.is_err());
+        assert!(load_position(&db, &consumer).unwrap() == Some(1));
+    }
+
+    #[test]
+    fn a_relaxed_write_is_rejected_without_saving() {
+        let db = SelfDestructingDB::new_tmp();
+        let consumer = ConsumerId::new("projector");
+        let msg = WriteMessage {
+            id: Id::new(),
+            stream_name: "stream1".into(),
+            message_type: "someMsgType".into(),
+            data: Cow::Borrowed(b"{}"),
+            metadata: Cow::Borrowed(b""),
+            expected_version: ExpectedVersion::Exact(StreamPos::Relaxed(0)),
+        };
+        let mut ser = WriteSerializer::new();
+        let res = write_mess_with_position(&db, msg, &mut ser, &consumer, 1);
+        assert!(let Err(Error::UnsupportedRelaxed { .. }) = res);
+        assert!(load_position(&db, &consumer).unwrap().is_none());
+    }
+}
@@ -1,20 +1,20 @@
-use std::marker::PhantomData;
+use std::ops::Range;
+
+use rocksdb::{Direction, IteratorMode};
 
 use super::keys::{GlobalKey, StreamKey, SEPARATOR_CHAR};
 use crate::{
+    clock::Tick,
     error::{Error, Result},
-    read::{GetMessages, OptGlobalPos, OptStream, Unset},
-    Message,
+    read::{self, Cursor, CursorRead, IntoCursor, OptTimeRange, ReadFilter},
+    Message, StreamInfo, StreamPos,
 };
 
 use super::{
     db::DB,
-    record::{GlobalRecord, StreamRecord},
+    record::{GlobalRecord, GlobalRecordHead, StreamRecord, StreamRecordHead},
 };
 
-pub const LIMIT_MAX: usize = 10_000;
-pub const LIMIT_DEFAULT: usize = 1_000;
-
 pub struct MessageIter<'msg, Iter: Iterator<Item = Result<Message<'msg>>>>(
     Iter,
 );
@@ -25,14 +25,158 @@ pub fn fetch_global<'iter, 'msg, 'db: 'iter>(
     limit: usize,
     // ) -> Result<impl 'iter + Iterator<Item = Result<Message<'msg>>>> {
 ) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
-    let glob_key = pos.to_be_bytes();
+    fetch_global_where(
+        db,
+        pos..u64::MAX,
+        limit,
+        |_| true,
+        ReadFilter::default(),
+        read::Direction::Forward,
+    )
+}
+
+/// Returns the global position of the first message recorded at or after
+/// `tick`, or `None` if every message is older.
+pub fn position_at_time(db: &DB, tick: Tick) -> Result<Option<u64>> {
+    let start = tick.to_u64().to_be_bytes();
+    db.iterator_cf(db.time(), IteratorMode::From(&start, Direction::Forward))
+        .next()
+        .transpose()?
+        .map(|(_, global)| GlobalKey::from_bytes(global).map(|key| key.0))
+        .transpose()
+}
+
+/// Returns the stream's first or last record, depending on where `mode`
+/// starts.
+fn stream_edge(
+    db: &DB,
+    stream: &str,
+    mode: IteratorMode,
+) -> Result<Option<(StreamPos, StreamRecord<'static>)>> {
+    let Some((k, v)) = db.iterator_cf(db.stream(), mode).next().transpose()?
+    else {
+        return Ok(None);
+    };
+    let key = StreamKey::from_bytes(&k)?;
+    if key.stream != stream {
+        return Ok(None);
+    }
+    Ok(Some((key.position, StreamRecord::from_bytes(&v)?)))
+}
+
+/// Summarizes a stream from its first and last records, or returns `None` if
+/// it has no messages.
+pub fn stream_info(db: &DB, stream: &str) -> Result<Option<StreamInfo>> {
+    let first_key =
+        StreamKey::new(stream.into(), StreamPos::Sequential(0)).to_bytes();
+    let last_key = StreamKey::max(stream.into()).to_bytes();
+    let first = stream_edge(
+        db,
+        stream,
+        IteratorMode::From(&first_key, Direction::Forward),
+    )?;
+    let last = stream_edge(
+        db,
+        stream,
+        IteratorMode::From(&last_key, Direction::Reverse),
+    )?;
+    let (Some((first_pos, first)), Some((position, last))) = (first, last)
+    else {
+        return Ok(None);
+    };
+    Ok(Some(StreamInfo {
+        position,
+        // Stream positions are contiguous, so no need to count the keys.
+        count: position.position() - first_pos.position() + 1,
+        first_global: first.global_position,
+        last_global: last.global_position,
+        first_time: Tick::from_u64(first.ord),
+        last_time: Tick::from_u64(last.ord),
+    }))
+}
+
+/// Lists up to `limit` stream names starting with `prefix`, after `after`,
+/// in key order. Each stream's messages are skipped with a single seek.
+pub fn list_streams(
+    db: &DB,
+    prefix: &str,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<Vec<String>> {
+    // The first key past every message of the stream.
+    let past = |stream: &str| {
+        let mut key = StreamKey::max(stream.into()).to_bytes();
+        key.push(0);
+        key
+    };
+    let mut from = match after {
+        Some(after) => past(after).max(prefix.as_bytes().to_vec()),
+        None => prefix.as_bytes().to_vec(),
+    };
+    let mut names = Vec::new();
+    while names.len() < limit {
+        let mode = IteratorMode::From(&from, Direction::Forward);
+        let Some((k, _)) =
+            db.iterator_cf(db.stream(), mode).next().transpose()?
+        else {
+            break;
+        };
+        if !k.starts_with(prefix.as_bytes()) {
+            break;
+        }
+        let stream = StreamKey::from_bytes(&k)?.stream.into_owned();
+        from = past(&stream);
+        names.push(stream);
+    }
+    Ok(names)
+}
+
+/// Fetch global messages in `range` whose record head passes `keep` and
+/// `filter`, in the given direction. Records rejected by their head are
+/// skipped without decoding their data, and no rejected record counts toward
+/// `limit`.
+fn fetch_global_where<'iter, 'msg, 'db: 'iter>(
+    db: &'db DB,
+    range: Range<u64>,
+    limit: usize,
+    keep: impl 'iter + Fn(&GlobalRecordHead) -> bool,
+    filter: ReadFilter,
+    direction: read::Direction,
+) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
+    let from = range.start.to_be_bytes();
+    let until = range.end.to_be_bytes();
     let cf = db.global();
-    let iter = db.prefix_iterator_cf(cf, glob_key);
-    iter.map(|res| {
-        let (k, v) = res.as_ref().map_err(|e| Error::Other(e.to_string()))?;
-        let key = GlobalKey::from_bytes(k)?;
-        let rec = GlobalRecord::from_bytes(v)?;
-        Ok(rec.into_message(key.0))
+    let iter = match direction {
+        read::Direction::Forward => {
+            db.iterator_cf(cf, IteratorMode::From(&from, Direction::Forward))
+        }
+        read::Direction::Backward => {
+            let last = range.end.saturating_sub(1).to_be_bytes();
+            db.iterator_cf(cf, IteratorMode::From(&last, Direction::Reverse))
+        }
+    };
+    iter.take_while(move |res| match res {
+        Ok((k, _)) => {
+            from.as_slice() <= k.as_ref() && k.as_ref() < until.as_slice()
+        }
+        Err(_) => true,
+    })
+    .filter_map(move |res| {
+        let read = || -> Result<_> {
+            let (k, v) =
+                res.as_ref().map_err(|e| Error::Other(e.to_string()))?;
+            let head = GlobalRecordHead::from_bytes(v)?;
+            if !keep(&head)
+                || !filter.wants_type(head.message_type)
+                || !filter.wants_stream(head.stream_name)
+            {
+                return Ok(None);
+            }
+            let key = GlobalKey::from_bytes(k)?;
+            let msg = GlobalRecord::from_bytes(v)?.into_message(key.0);
+            Ok(filter.wants_metadata(msg.metadata.as_deref()).then_some(msg))
+        };
+        read().transpose()
     })
     .take(limit)
 }
@@ -42,61 +186,211 @@ pub fn fetch_stream<'iter, 'msg, 'db: 'iter>(
     stream_name: impl AsRef<str> + 'iter,
     limit: usize,
 ) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
-    let mut search_key = stream_name.as_ref().to_owned();
-    search_key.push(SEPARATOR_CHAR);
-    let cf = db.stream();
-    let iter = db.prefix_iterator_cf(cf, search_key);
-    iter.map(|res| {
+    fetch_stream_where(
+        db,
+        stream_name,
+        None,
+        limit,
+        ReadFilter::default(),
+        read::Direction::Forward,
+    )
+}
+
+/// Fetch messages from a stream, starting at the given stream position.
+pub fn fetch_stream_from<'iter, 'msg, 'db: 'iter>(
+    db: &'db DB,
+    stream_name: impl AsRef<str> + 'iter,
+    position: StreamPos,
+    limit: usize,
+) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
+    fetch_stream_where(
+        db,
+        stream_name,
+        Some(position),
+        limit,
+        ReadFilter::default(),
+        read::Direction::Forward,
+    )
+}
+
+/// Fetch the messages of a stream which pass `filter`, in the given
+/// direction. Forward reads start at `stream_pos`, backward reads end before
+/// it, and without one both read the whole stream.
+fn fetch_stream_where<'iter, 'msg, 'db: 'iter>(
+    db: &'db DB,
+    stream_name: impl AsRef<str> + 'iter,
+    stream_pos: Option<StreamPos>,
+    limit: usize,
+    filter: ReadFilter,
+    direction: read::Direction,
+) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
+    let stream = stream_name.as_ref();
+    let (key, before) = match (direction, stream_pos) {
+        (read::Direction::Forward, None) => {
+            let mut key = stream.to_owned();
+            key.push(SEPARATOR_CHAR);
+            (key.into_bytes(), None)
+        }
+        (read::Direction::Forward, Some(pos)) => {
+            (StreamKey::new(stream.into(), pos).to_bytes(), None)
+        }
+        (read::Direction::Backward, None) => {
+            (StreamKey::max(stream.into()).to_bytes(), None)
+        }
+        (read::Direction::Backward, Some(pos)) => {
+            (StreamKey::new(stream.into(), pos).to_bytes(), Some(pos))
+        }
+    };
+    let mode = match direction {
+        read::Direction::Forward => {
+            IteratorMode::From(&key, Direction::Forward)
+        }
+        read::Direction::Backward => {
+            IteratorMode::From(&key, Direction::Reverse)
+        }
+    };
+    let iter = db.iterator_cf(db.stream(), mode);
+    iter.map(|res| -> Result<_> {
         let (k, v) = res?;
         let key = StreamKey::from_bytes(k)?;
-        let rec = StreamRecord::from_bytes(v)?;
-        Ok(rec.into_message(key.stream, key.position))
+        Ok((key, v))
     })
     .take_while(move |res| match res {
-        Ok(msg) => msg.stream_name == stream_name.as_ref(),
+        Ok((key, _)) => key.stream == stream_name.as_ref(),
         Err(_) => true,
     })
+    // A backward read lands on its end position when it exists.
+    .skip_while(move |res| match (res, before) {
+        (Ok((key, _)), Some(before)) => {
+            key.position.encode() >= before.encode()
+        }
+        _ => false,
+    })
+    .filter_map(move |res| {
+        let read = || -> Result<_> {
+            let (key, v) = res?;
+            let head = StreamRecordHead::from_bytes(&v)?;
+            if !filter.wants_type(head.message_type)
+                || !filter.wants_stream(&key.stream)
+            {
+                return Ok(None);
+            }
+            let msg = StreamRecord::from_bytes(&v)?
+                .into_message(key.stream, key.position);
+            Ok(filter.wants_metadata(msg.metadata.as_deref()).then_some(msg))
+        };
+        read().transpose()
+    })
     .take(limit)
 }
 
-// pub struct Fetch;
-pub struct Fetch<Param> {
-    _mark: PhantomData<Param>,
+/// Returns the global positions a read from `global_pos` covers: onward
+/// from it when reading forward, and before it when reading backward.
+const fn global_range(
+    global_pos: u64,
+    direction: read::Direction,
+) -> Range<u64> {
+    match direction {
+        read::Direction::Forward => global_pos..u64::MAX,
+        read::Direction::Backward => 0..global_pos,
+    }
 }
 
-impl Fetch<OptGlobalPos> {
-    pub fn fetch<'iter, 'msg, 'db: 'iter>(
-        db: &'db DB,
-        opts: GetMessages<Unset, OptGlobalPos, Unset>,
-    ) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
-        fetch_global(db, opts.start_global_position.0, opts.limit)
+/// Resolves a time range to the global positions it covers, narrowed by
+/// where the read continues from. A failed lookup yields an empty range and
+/// the error, to be read in its place.
+fn time_range_positions(
+    db: &DB,
+    range: OptTimeRange,
+    direction: read::Direction,
+) -> (Range<u64>, Option<Error>) {
+    let bounds =
+        position_at_time(db, Tick::from_u64(range.start)).and_then(|start| {
+            Ok((start, position_at_time(db, Tick::from_u64(range.end))?))
+        });
+    match bounds {
+        // Nothing was recorded after `start`.
+        Ok((None, _)) => (0..0, None),
+        Ok((Some(start), end)) => {
+            let end = end.unwrap_or(u64::MAX);
+            let range = match direction {
+                read::Direction::Forward => start.max(range.global_pos)..end,
+                read::Direction::Backward => start..end.min(range.global_pos),
+            };
+            (range, None)
+        }
+        Err(e) => (0..0, Some(e)),
     }
 }
 
-impl<'iter, 's: 'iter> Fetch<(OptStream<'s>, OptGlobalPos)> {
-    pub fn fetch<'msg, 'db: 'iter>(
-        db: &'db DB,
-        opts: GetMessages<OptStream<'s>, OptGlobalPos, Unset>,
-    ) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
-        let stream = opts.stream.to_owned();
-        fetch_global(db, opts.start_global_position.0, opts.limit).filter(
-            move |res| {
-                match res {
-                    Ok(rec) => rec.stream_name == stream.0.as_ref(),
-                    // pass along all errors regardless of prefix
-                    Err(_) => true,
-                }
-            },
-        )
+/// The messages of a read whose kind is only known at runtime.
+pub type BoxedMessages<'iter, 'msg> =
+    Box<dyn 'iter + Iterator<Item = Result<Message<'msg>>>>;
+
+/// Fetch the messages a cursor reads, in its direction.
+// Mapping with `Err` itself would tie the iterator to `'msg`.
+#[allow(clippy::redundant_closure)]
+pub fn fetch_cursor<'iter, 'msg, 'db: 'iter>(
+    db: &'db DB,
+    cursor: Cursor,
+) -> BoxedMessages<'iter, 'msg> {
+    let Cursor { read, limit, filter, direction } = cursor;
+    match read {
+        CursorRead::Global { stream, global_pos } => {
+            Box::new(fetch_global_where(
+                db,
+                global_range(global_pos, direction),
+                limit,
+                move |head| {
+                    stream.as_ref().is_none_or(|s| head.stream_name == s)
+                },
+                filter,
+                direction,
+            ))
+        }
+        CursorRead::Stream { stream, stream_pos } => {
+            Box::new(fetch_stream_where(
+                db, stream, stream_pos, limit, filter, direction,
+            ))
+        }
+        CursorRead::Category { category, global_pos } => {
+            Box::new(fetch_global_where(
+                db,
+                global_range(global_pos, direction),
+                limit,
+                move |head| read::category(head.stream_name) == category,
+                filter,
+                direction,
+            ))
+        }
+        CursorRead::Between { category, range } => {
+            let (range, err) = time_range_positions(db, range, direction);
+            Box::new(err.into_iter().map(|e| Err(e)).chain(fetch_global_where(
+                db,
+                range,
+                limit,
+                move |head| {
+                    category
+                        .as_ref()
+                        .is_none_or(|c| read::category(head.stream_name) == c)
+                },
+                filter,
+                direction,
+            )))
+        }
     }
 }
 
-impl<'iter, 's: 'iter> Fetch<OptStream<'s>> {
-    pub fn fetch<'msg, 'db: 'iter>(
-        db: &'db DB,
-        opts: GetMessages<OptStream<'s>, Unset, Unset>,
-    ) -> impl 'iter + Iterator<Item = Result<Message<'msg>>> {
-        fetch_stream(db, opts.stream.0, opts.limit)
+/// Fetch the messages of any read the [`read::GetMessages`] builder allows,
+/// or an [`Error::InvalidRead`] for a combination no read can honour.
+#[allow(clippy::redundant_closure)]
+pub fn fetch<'iter, 'msg, 'db: 'iter>(
+    db: &'db DB,
+    read: impl IntoCursor,
+) -> BoxedMessages<'iter, 'msg> {
+    match read.into_cursor() {
+        Ok(cursor) => fetch_cursor(db, cursor),
+        Err(err) => Box::new(std::iter::once(err).map(|e| Err(e))),
     }
 }
 
@@ -111,11 +405,12 @@ mod test {
     use rstest::*;
 
     use crate::{
+        read::GetMessages,
         rocks::{
             db::test::SelfDestructingDB,
             write::{write_mess, WriteSerializer},
         },
-        write::WriteMessage,
+        write::{ExpectedVersion, WriteMessage},
         StreamPos,
     };
 
@@ -132,7 +427,7 @@ mod test {
         let meta = [99u8; 100];
 
         let rows = std::iter::once(None).chain((0u64..).map(Some)).map(|x| {
-            let expected_stream_position = x.map(StreamPos::Sequential);
+            let expected_version = x.map(StreamPos::Sequential).into();
             let i = match x {
                 Some(x) => x + 1,
                 None => 0,
@@ -147,7 +442,7 @@ mod test {
                     message_type: "MessageType".into(),
                     data: data[..].into(),
                     metadata: meta[..].into(),
-                    expected_stream_position,
+                    expected_version,
                 },
                 WriteMessage {
                     id: Id::from_str(
@@ -158,7 +453,7 @@ mod test {
                     message_type: "MessageType".into(),
                     data: data[..].into(),
                     metadata: [][..].into(),
-                    expected_stream_position,
+                    expected_version,
                 },
             ]
         });
@@ -179,7 +474,7 @@ mod test {
         fn it_gets_messages_up_to_limit() {
             let db = test_db(5);
             let opts = GetMessages::default().from_global(0).with_limit(6);
-            let messages = Fetch::<OptGlobalPos>::fetch(&db, opts);
+            let messages = fetch(&db, opts);
             let messages: Result<Vec<_>> = messages.collect();
             let messages = messages.unwrap();
             for msg in messages.iter() {
@@ -203,9 +498,8 @@ mod test {
         fn it_gets_messages_starting_from_given_pos() {
             let db = test_db(5);
             let opts = GetMessages::default().from_global(5);
-            let messages = Fetch::<OptGlobalPos>::fetch(&db, opts)
-                .collect::<Result<Vec<_>>>()
-                .unwrap();
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
             // assert!(messages.len() == 2);
             let m = &messages[0];
             assert!(m.global_position == 5);
@@ -223,7 +517,7 @@ mod test {
         fn it_returns_empty_iter_if_pos_too_high() {
             let db = test_db(5);
             let opts = GetMessages::default().from_global(500);
-            let iter = Fetch::<OptGlobalPos>::fetch(&db, opts);
+            let iter = fetch(&db, opts);
             assert!(iter.count() == 0);
         }
 
@@ -231,13 +525,333 @@ mod test {
         fn it_only_returns_messages_from_given_stream() {
             let db = test_db(5);
             let opts = GetMessages::default().in_stream("stream1");
-            let messages = Fetch::<OptStream<'_>>::fetch(&db, opts)
-                .collect::<Result<Vec<_>>>()
-                .unwrap();
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            for message in messages {
+                assert!(message.stream_name == "stream1");
+            }
+        }
+
+        #[rstest]
+        fn it_returns_stream_messages_starting_from_given_stream_pos() {
+            let db = test_db(5);
+            let opts = GetMessages::default()
+                .in_stream("stream1")
+                .from_stream_pos(StreamPos::Sequential(3));
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(messages.len() == 2);
+            assert!(messages[0].stream_position == StreamPos::Sequential(3));
+            assert!(messages[1].stream_position == StreamPos::Sequential(4));
             for message in messages {
                 assert!(message.stream_name == "stream1");
             }
         }
+
+        fn globals(messages: BoxedMessages) -> Vec<u64> {
+            messages.map(|m| m.unwrap().global_position).collect()
+        }
+
+        #[rstest]
+        fn it_reads_the_global_log_backward() {
+            let db = test_db(5);
+            let opts = GetMessages::default().backwards().with_limit(3);
+            assert!(globals(fetch(&db, opts)) == [10, 9, 8]);
+            let opts = GetMessages::default().from_global(4).backwards();
+            assert!(globals(fetch(&db, opts)) == [4, 3, 2, 1]);
+        }
+
+        #[rstest]
+        fn it_reads_a_stream_backward_before_a_position() {
+            let db = test_db(5);
+            let opts = GetMessages::default().in_stream("stream2").backwards();
+            assert!(globals(fetch(&db, opts)) == [10, 8, 6, 4, 2]);
+            let opts = GetMessages::default()
+                .in_stream("stream2")
+                .from_stream_pos(StreamPos::Sequential(2))
+                .backwards();
+            assert!(globals(fetch(&db, opts)) == [6, 4, 2]);
+        }
+
+        #[rstest]
+        fn it_reads_a_category_backward() {
+            let db = test_db(5);
+            let opts = GetMessages::default()
+                .in_category("stream1")
+                .from_global(6)
+                .backwards();
+            assert!(globals(fetch(&db, opts)) == [5, 3, 1]);
+        }
+
+        #[rstest]
+        fn it_honours_the_stream_of_a_global_read() {
+            let db = test_db(5);
+            let opts =
+                GetMessages::default().in_stream("stream2").from_global(3);
+            assert!(globals(fetch(&db, opts)) == [4, 6, 8, 10]);
+        }
+
+        fn typed_db() -> SelfDestructingDB {
+            let db = SelfDestructingDB::new_tmp();
+            let mut ser = test_ser();
+            let types = ["A", "B", "C", "A", "B", "C"];
+            for message_type in types {
+                for stream_name in ["stream1", "stream2"] {
+                    let msg = WriteMessage {
+                        id: Id::new(),
+                        stream_name: stream_name.into(),
+                        message_type: message_type.into(),
+                        data: b"{}"[..].into(),
+                        metadata: [][..].into(),
+                        expected_version: ExpectedVersion::Any,
+                    };
+                    write_mess(&db, msg, &mut ser).unwrap();
+                }
+            }
+            db
+        }
+
+        fn types_of(messages: Vec<Message>) -> Vec<String> {
+            messages.into_iter().map(|m| m.message_type.into()).collect()
+        }
+
+        #[rstest]
+        fn it_only_returns_global_messages_of_given_types() {
+            let db = typed_db();
+            let opts =
+                GetMessages::default().from_global(0).of_types(&["A", "C"]);
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(types_of(messages) == ["A", "A", "C", "C"].repeat(2));
+        }
+
+        #[rstest]
+        fn the_limit_counts_only_matching_messages() {
+            let db = typed_db();
+            let opts = GetMessages::default()
+                .in_stream("stream2")
+                .from_global(0)
+                .of_types(&["B"])
+                .with_limit(2);
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(messages.len() == 2);
+            for message in messages {
+                assert!(message.stream_name == "stream2");
+                assert!(message.message_type == "B");
+            }
+        }
+
+        #[rstest]
+        fn it_only_returns_stream_messages_of_given_types() {
+            let db = typed_db();
+            let opts = GetMessages::default()
+                .in_stream("stream1")
+                .from_stream_pos(StreamPos::Sequential(1))
+                .of_types(&["A"]);
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(messages.len() == 1);
+            assert!(messages[0].stream_position == StreamPos::Sequential(3));
+        }
+
+        fn correlated_db() -> SelfDestructingDB {
+            let db = SelfDestructingDB::new_tmp();
+            let mut ser = test_ser();
+            let rows = [
+                ("account-1", Some("accountComponent-1")),
+                ("account-2", Some("billing-7")),
+                ("accountTransaction-1", Some("accountComponent-2")),
+                ("account-1", None),
+                ("account", Some("accountComponent")),
+            ];
+            for (stream_name, correlation) in rows {
+                let metadata = correlation
+                    .map(|c| {
+                        serde_json::json!({ "correlationStreamName": c })
+                            .to_string()
+                            .into_bytes()
+                    })
+                    .unwrap_or_default();
+                let msg = WriteMessage {
+                    id: Id::new(),
+                    stream_name: stream_name.into(),
+                    message_type: "X".into(),
+                    data: b"{}"[..].into(),
+                    metadata: metadata.into(),
+                    expected_version: ExpectedVersion::Any,
+                };
+                write_mess(&db, msg, &mut ser).unwrap();
+            }
+            db
+        }
+
+        fn stream_names(messages: Vec<Message>) -> Vec<String> {
+            messages.into_iter().map(|m| m.stream_name.into()).collect()
+        }
+
+        #[rstest]
+        fn it_only_returns_messages_in_the_category() {
+            let db = correlated_db();
+            let opts = GetMessages::default().in_category("account");
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(
+                stream_names(messages)
+                    == ["account-1", "account-2", "account-1", "account"]
+            );
+        }
+
+        #[rstest]
+        fn it_only_returns_category_messages_correlated_to_the_category() {
+            let db = correlated_db();
+            let opts = GetMessages::default()
+                .in_category("account")
+                .from_global(0)
+                .correlation("accountComponent");
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(stream_names(messages) == ["account-1", "account"]);
+        }
+
+        #[rstest]
+        fn consumer_group_members_split_the_category() {
+            let db = SelfDestructingDB::new_tmp();
+            let mut ser = test_ser();
+            for i in 0..20 {
+                let msg = WriteMessage {
+                    id: Id::new(),
+                    stream_name: format!("account-{i}").into(),
+                    message_type: "X".into(),
+                    data: b"{}"[..].into(),
+                    metadata: [][..].into(),
+                    expected_version: ExpectedVersion::NoStream,
+                };
+                write_mess(&db, msg, &mut ser).unwrap();
+            }
+            let mut seen = vec![];
+            for member in 0..3 {
+                let opts = GetMessages::default()
+                    .in_category("account")
+                    .consumer_group(member, 3)
+                    .unwrap();
+                let messages =
+                    fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+                assert!(!messages.is_empty());
+                seen.extend(stream_names(messages));
+            }
+            seen.sort();
+            let mut expected: Vec<_> =
+                (0..20).map(|i| format!("account-{i}")).collect();
+            expected.sort();
+            assert!(seen == expected);
+        }
+
+        /// The (global position, ord) of every message, in order.
+        fn ords(db: &DB) -> Vec<(u64, Tick)> {
+            db.iterator_cf(db.global(), IteratorMode::Start)
+                .map(|res| {
+                    let (k, v) = res.unwrap();
+                    let record = GlobalRecord::from_bytes(&v).unwrap();
+                    let key = GlobalKey::from_bytes(k).unwrap();
+                    (key.0, Tick::from_u64(record.ord))
+                })
+                .collect()
+        }
+
+        #[rstest]
+        fn it_stamps_increasing_ords() {
+            let db = correlated_db();
+            let ords = ords(&db);
+            assert!(ords.len() == 5);
+            assert!(ords.windows(2).all(|w| w[0].1 < w[1].1));
+        }
+
+        #[rstest]
+        fn it_returns_messages_between_two_times() {
+            let db = correlated_db();
+            let ords = ords(&db);
+            let opts = GetMessages::default().between(ords[1].1, ords[3].1);
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            let positions: Vec<_> =
+                messages.iter().map(|m| m.global_position).collect();
+            assert!(positions == [ords[1].0, ords[2].0]);
+        }
+
+        #[rstest]
+        fn it_returns_category_messages_between_two_times() {
+            let db = correlated_db();
+            let ords = ords(&db);
+            let opts = GetMessages::default()
+                .in_category("account")
+                .between(ords[1].1, Tick::from_u64(u64::MAX));
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(
+                stream_names(messages) == ["account-2", "account-1", "account"]
+            );
+        }
+
+        #[rstest]
+        fn it_finds_the_position_at_a_time() {
+            let db = correlated_db();
+            let ords = ords(&db);
+            let at = |tick| position_at_time(&db, tick).unwrap();
+            assert!(at(Tick::from_u64(0)) == Some(ords[0].0));
+            assert!(at(ords[2].1) == Some(ords[2].0));
+            let after = Tick::from_u64(ords[4].1.to_u64() + 1);
+            assert!(at(after).is_none());
+        }
+
+        #[rstest]
+        fn it_summarizes_a_stream() {
+            let db = test_db(3);
+            let info = stream_info(&db, "stream2").unwrap().unwrap();
+            let ords = ords(&db);
+            assert!(info.position == StreamPos::Sequential(2));
+            assert!(info.count == 3);
+            assert!(info.first_global == ords[1].0);
+            assert!(info.last_global == ords[5].0);
+            assert!(info.first_time == ords[1].1);
+            assert!(info.last_time == ords[5].1);
+            assert!(stream_info(&db, "stream").unwrap().is_none());
+        }
+
+        #[rstest]
+        fn it_lists_streams() {
+            let db = correlated_db();
+            let names =
+                |prefix, after| list_streams(&db, prefix, after, 10).unwrap();
+            assert!(
+                names("account", None)
+                    == [
+                        "account-1",
+                        "account-2",
+                        "accountTransaction-1",
+                        "account"
+                    ]
+            );
+            assert!(names("account-", None) == ["account-1", "account-2"]);
+            assert!(names("account-", Some("account-1")) == ["account-2"]);
+            assert!(names("billing", None).is_empty());
+            let first = list_streams(&db, "", None, 1).unwrap();
+            assert!(first == ["account-1"]);
+        }
+
+        #[rstest]
+        fn global_reads_can_be_correlated() {
+            let db = correlated_db();
+            let opts = GetMessages::default()
+                .from_global(0)
+                .correlation("accountComponent")
+                .with_limit(2);
+            let messages =
+                fetch(&db, opts).collect::<Result<Vec<_>>>().unwrap();
+            assert!(
+                stream_names(messages) == ["account-1", "accountTransaction-1"]
+            );
+        }
         //
         //     #[rstest]
         //     fn the_lowest_limit_is_1() {
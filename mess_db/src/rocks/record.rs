@@ -20,16 +20,12 @@ pub struct GlobalRecord<'a> {
 impl<'a> GlobalRecord<'a> {
     pub(crate) fn from_write_serial_message(
         msg: &'a WriteSerialMessage,
+        stream_position: StreamPos,
     ) -> Result<Self> {
-        let stream_position = msg
-            .expected_position
-            .map(|x| x.next())
-            .unwrap_or(StreamPos::Sequential(0))
-            .encode();
         Ok(Self {
             id: msg.id.to_string().into(),
             stream_name: msg.stream_name.as_ref().into(),
-            stream_position,
+            stream_position: stream_position.encode(),
             message_type: msg.message_type.as_ref().into(),
             data: msg.data.as_ref().into(),
             metadata: msg.metadata.as_ref().into(),
@@ -58,6 +54,25 @@ impl<'a> GlobalRecord<'a> {
     }
 }
 
+/// The leading fields of a [`GlobalRecord`]. Decoding it borrows from the
+/// raw value and never touches the data or metadata, so reads can filter
+/// records before building a [`Message`].
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub(crate) struct GlobalRecordHead<'a> {
+    pub(crate) id: &'a str,
+    pub(crate) stream_name: &'a str,
+    pub(crate) stream_position: u64,
+    pub(crate) message_type: &'a str,
+}
+
+impl<'a> GlobalRecordHead<'a> {
+    pub(crate) fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        postcard::take_from_bytes(bytes)
+            .map(|(head, _rest)| head)
+            .map_err(|e| Error::DeserError(e.to_string()))
+    }
+}
+
 #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct StreamRecord<'a> {
     pub(crate) global_position: u64,
@@ -113,3 +128,19 @@ impl<'a> StreamRecord<'a> {
         }
     }
 }
+
+/// The leading fields of a [`StreamRecord`], see [`GlobalRecordHead`].
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub(crate) struct StreamRecordHead<'a> {
+    pub(crate) global_position: u64,
+    pub(crate) id: &'a str,
+    pub(crate) message_type: &'a str,
+}
+
+impl<'a> StreamRecordHead<'a> {
+    pub(crate) fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        postcard::take_from_bytes(bytes)
+            .map(|(head, _rest)| head)
+            .map_err(|e| Error::DeserError(e.to_string()))
+    }
+}
@@ -0,0 +1,171 @@
+use std::{path::Path, sync::Arc};
+
+use metrics::gauge;
+use rocksdb::properties;
+use tracing::{error, warn};
+
+use super::{
+    db::DB,
+    position,
+    read::{fetch_cursor, list_streams, position_at_time, stream_info},
+    write::{
+        get_last_global_position, write_mess, write_mess_with_position,
+        WriteSerializer,
+    },
+};
+use crate::{
+    clock::Tick,
+    error::Result,
+    metrics::{MEMTABLE_BYTES, PENDING_COMPACTION_BYTES, RUNNING_COMPACTIONS},
+    position::ConsumerId,
+    read::Cursor,
+    storage::{Storage, StorageReader},
+    write::WriteMessage,
+    OwnedMessage, Position, StreamInfo,
+};
+
+/// Runs the service on RocksDB. Readers share the database with the writer.
+pub struct RocksStorage {
+    db: Arc<DB>,
+    ser: WriteSerializer,
+}
+
+impl RocksStorage {
+    #[must_use]
+    pub fn new(db: DB) -> Self {
+        Self { db: Arc::new(db), ser: WriteSerializer::new() }
+    }
+}
+
+impl From<DB> for RocksStorage {
+    fn from(db: DB) -> Self {
+        Self::new(db)
+    }
+}
+
+impl StorageReader for Arc<DB> {
+    fn read(&self, cursor: &Cursor) -> Vec<Result<OwnedMessage>> {
+        fetch_cursor(self, cursor.clone())
+            .map(|res| res.map(OwnedMessage::from))
+            .collect()
+    }
+
+    fn position_at_time(&self, tick: Tick) -> Result<Option<u64>> {
+        position_at_time(self, tick)
+    }
+
+    fn stream_info(&self, stream: &str) -> Result<Option<StreamInfo>> {
+        stream_info(self, stream)
+    }
+
+    fn load_position(&self, consumer: &ConsumerId) -> Result<Option<u64>> {
+        position::load_position(self, consumer)
+    }
+
+    fn list_streams(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        list_streams(self, prefix, after, limit)
+    }
+}
+
+impl Storage for RocksStorage {
+    type Reader = Arc<DB>;
+
+    fn reader(&self) -> Result<Self::Reader> {
+        Ok(self.db.clone())
+    }
+
+    fn write(
+        &mut self,
+        message: WriteMessage<'_>,
+        checkpoint: Option<(&ConsumerId, u64)>,
+    ) -> Result<Position> {
+        match checkpoint {
+            None => write_mess(&self.db, message, &mut self.ser),
+            Some((consumer, global_pos)) => write_mess_with_position(
+                &self.db,
+                message,
+                &mut self.ser,
+                consumer,
+                global_pos,
+            ),
+        }
+    }
+
+    fn save_position(
+        &mut self,
+        consumer: &ConsumerId,
+        global_pos: u64,
+    ) -> Result<()> {
+        position::save_position(&self.db, consumer, global_pos)
+    }
+
+    fn reset_position(&mut self, consumer: &ConsumerId) -> Result<()> {
+        position::reset_position(&self.db, consumer)
+    }
+
+    fn last_global_position(&self) -> Result<u64> {
+        get_last_global_position(&self.db).map(|key| key.0)
+    }
+
+    fn backup(&self, path: &Path) -> Result<()> {
+        // A checkpoint hard-links the table files where it can.
+        rocksdb::checkpoint::Checkpoint::new(&self.db)?
+            .create_checkpoint(path)?;
+        Ok(())
+    }
+
+    fn record_metrics(&self) {
+        let cfs = [
+            ("global", self.db.global()),
+            ("stream", self.db.stream()),
+            ("position", self.db.position()),
+            ("time", self.db.time()),
+            ("id", self.db.id()),
+        ];
+        for (name, cf) in cfs {
+            let memtables = self.db.property_int_value_cf(
+                &cf,
+                properties::CUR_SIZE_ALL_MEM_TABLES,
+            );
+            if let Some(bytes) = known(memtables) {
+                gauge!(MEMTABLE_BYTES, "cf" => name).set(bytes as f64);
+            }
+            let pending = self.db.property_int_value_cf(
+                &cf,
+                properties::ESTIMATE_PENDING_COMPACTION_BYTES,
+            );
+            if let Some(bytes) = known(pending) {
+                gauge!(PENDING_COMPACTION_BYTES, "cf" => name)
+                    .set(bytes as f64);
+            }
+        }
+        let running =
+            self.db.property_int_value(properties::NUM_RUNNING_COMPACTIONS);
+        if let Some(count) = known(running) {
+            gauge!(RUNNING_COMPACTIONS).set(count as f64);
+        }
+    }
+
+    fn close(self) -> Result<()> {
+        let Some(db) = Arc::into_inner(self.db) else {
+            error!("database still in use, not closing it");
+            return Ok(());
+        };
+        db.close()
+    }
+}
+
+/// Returns a property's value, logging why there isn't one.
+fn known(
+    value: std::result::Result<Option<u64>, rocksdb::Error>,
+) -> Option<u64> {
+    value.unwrap_or_else(|err| {
+        warn!(?err, "could not read a rocksdb property");
+        None
+    })
+}
@@ -1,7 +1,4 @@
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
-};
+use std::sync::{atomic::Ordering, Arc};
 
 use super::{
     db::DB,
@@ -10,15 +7,14 @@ use super::{
 };
 use crate::{
     error::{Error, Result},
-    write::{WriteMessage, WriteSerialMessage},
+    position::ConsumerId,
+    write::{ExpectedVersion, WriteMessage, WriteSerialMessage},
     Position, StreamPos,
 };
 use rocksdb::{IteratorMode, ReadOptions};
 
-static mut CACHED_GLOBAL: AtomicU64 = AtomicU64::new(0);
-
 pub fn get_last_global_position(db: &DB) -> Result<GlobalKey> {
-    let cached = unsafe { CACHED_GLOBAL.load(Ordering::SeqCst) };
+    let cached = db.last_global.load(Ordering::SeqCst);
     if cached != 0 {
         return Ok(GlobalKey(cached));
     }
@@ -35,9 +31,7 @@ pub fn get_last_global_position(db: &DB) -> Result<GlobalKey> {
         .map_err(|e| Error::ReadError(e.to_string()))?
         .map_err(|e| Error::ReadError(e.to_string()));
     if let Ok(key) = result.as_ref() {
-        unsafe {
-            CACHED_GLOBAL.store(key.0, Ordering::Release);
-        }
+        db.last_global.store(key.0, Ordering::Release);
     }
     result
 }
@@ -73,21 +67,13 @@ pub fn get_last_stream_position<'a>(
 }
 
 fn next_stream_pos<'a>(
-    expected_position: Option<StreamPos>,
+    expected_version: ExpectedVersion,
     stream_name: &'a str,
     last_stream: Option<StreamKey<'a>>,
 ) -> Result<StreamKey<'a>> {
-    match (expected_position, last_stream) {
-        (None, None) => {
-            Ok(StreamKey::new(stream_name.into(), StreamPos::Sequential(0)))
-        }
-        (Some(a), Some(key)) if a == key.position => Ok(key.next()),
-        (expected, key) => Err(Error::WrongStreamPosition {
-            stream: stream_name.to_string(),
-            expected: expected.map(|x| x.position()),
-            got: key.map(|k| k.position.position()),
-        }),
-    }
+    let current = last_stream.map(|key| key.position);
+    let next = expected_version.next_position(stream_name, current)?;
+    Ok(StreamKey::new(stream_name.into(), next))
 }
 
 pub struct WriteSerializer<const S: usize = 1024> {
@@ -126,11 +112,16 @@ fn write_records(
     next_global: GlobalKey,
     next_stream: StreamKey,
     ser: &mut WriteSerializer,
+    checkpoint: Option<(&ConsumerId, u64)>,
 ) -> Result<Position> {
-    let global_record = GlobalRecord::from_write_serial_message(&msg)?;
-    let stream_record =
+    let ord = db.clock.next();
+    let mut global_record =
+        GlobalRecord::from_write_serial_message(&msg, next_stream.position)?;
+    global_record.ord = ord.to_u64();
+    let mut stream_record =
         StreamRecord::from_write_serial_message(&msg, next_global.0)?
             .set_global_position(next_global.0);
+    stream_record.ord = ord.to_u64();
 
     // let mut buf = [0u8; 1024];
     // let mut buf2 = [0u8; 1024];
@@ -144,7 +135,17 @@ fn write_records(
     let mut batch = rocksdb::WriteBatch::default();
     batch.put_cf(db.global(), next_global.as_bytes(), &global_bytes);
     batch.put_cf(db.stream(), next_stream.as_bytes(), &stream_bytes);
+    batch.put_cf(db.time(), ord.to_u64().to_be_bytes(), next_global.as_bytes());
+    batch.put_cf(db.id(), msg.id.to_string(), next_global.as_bytes());
+    if let Some((consumer, global_pos)) = checkpoint {
+        batch.put_cf(
+            db.position(),
+            consumer.as_bytes(),
+            global_pos.to_be_bytes(),
+        );
+    }
     db.write(batch)?;
+    db.clock.observe(ord);
 
     Ok(Position { global: next_global.0, stream: next_stream.position })
 }
@@ -153,12 +154,40 @@ pub fn write_mess(
     msg: WriteMessage,
     ser: &mut WriteSerializer,
 ) -> Result<Position> {
-    match msg.expected_stream_position {
-        None | Some(StreamPos::Sequential(_)) => {
-            write_serial_mess(db, msg.into(), ser)
+    match msg.expected_version {
+        ExpectedVersion::Exact(StreamPos::Relaxed(_)) => {
+            Err(unsupported_relaxed(&msg))
         }
-        Some(StreamPos::Relaxed(_)) => todo!(),
+        _ => write_serial_mess(db, msg.into(), ser),
+    }
+}
+
+/// Returns the position of the message with the same id, if one was written
+/// before, so a retried write returns it instead of writing again. Fails if
+/// it went to another stream.
+fn written_before(
+    db: &DB,
+    msg: &WriteSerialMessage,
+) -> Result<Option<Position>> {
+    let id = msg.id.to_string();
+    let Some(global) = db.get_cf(db.id(), &id)? else {
+        return Ok(None);
+    };
+    let global = GlobalKey::from_bytes(&global)?;
+    let record =
+        db.get_cf(db.global(), global.as_bytes())?.ok_or_else(|| {
+            Error::ReadError(format!("no message at {}", global.0))
+        })?;
+    let record = GlobalRecord::from_bytes(&record)?;
+    if record.stream_name != msg.stream_name {
+        return Err(Error::IdInUse { id, stream: record.stream_name.into() });
     }
+    let stream = StreamPos::decode(record.stream_position);
+    Ok(Some(Position { global: global.0, stream }))
+}
+
+fn unsupported_relaxed(msg: &WriteMessage) -> Error {
+    Error::UnsupportedRelaxed { stream: msg.stream_name.to_string() }
 }
 
 pub fn write_serial_mess(
@@ -166,16 +195,48 @@ pub fn write_serial_mess(
     msg: WriteSerialMessage,
     ser: &mut WriteSerializer,
 ) -> Result<Position> {
+    write_serial_mess_with_position(db, msg, ser, None)
+}
+
+/// Writes the message and saves the consumer's position in the same batch,
+/// so either both are stored or neither is.
+pub fn write_mess_with_position(
+    db: &DB,
+    msg: WriteMessage,
+    ser: &mut WriteSerializer,
+    consumer: &ConsumerId,
+    global_pos: u64,
+) -> Result<Position> {
+    match msg.expected_version {
+        ExpectedVersion::Exact(StreamPos::Relaxed(_)) => {
+            Err(unsupported_relaxed(&msg))
+        }
+        _ => write_serial_mess_with_position(
+            db,
+            msg.into(),
+            ser,
+            Some((consumer, global_pos)),
+        ),
+    }
+}
+
+fn write_serial_mess_with_position(
+    db: &DB,
+    msg: WriteSerialMessage,
+    ser: &mut WriteSerializer,
+    checkpoint: Option<(&ConsumerId, u64)>,
+) -> Result<Position> {
+    if let Some(position) = written_before(db, &msg)? {
+        return Ok(position);
+    }
     let next_global = get_last_global_position(db)?.next();
     let last_stream = get_last_stream_position(db, &msg.stream_name)?;
     let stream_name = msg.stream_name.clone();
     let next_stream =
-        next_stream_pos(msg.expected_position, &stream_name, last_stream)?;
-    let res = write_records(db, msg, next_global, next_stream, ser);
+        next_stream_pos(msg.expected_version, &stream_name, last_stream)?;
+    let res = write_records(db, msg, next_global, next_stream, ser, checkpoint);
     if let Ok(position) = res.as_ref() {
-        unsafe {
-            CACHED_GLOBAL.store(position.global, Ordering::SeqCst);
-        }
+        db.last_global.store(position.global, Ordering::SeqCst);
     }
     res
 }
@@ -184,11 +245,11 @@ pub async fn write_mess_async<'a>(
     msg: WriteMessage<'a>,
     ser: &mut WriteSerializer,
 ) -> Result<Position> {
-    match msg.expected_stream_position {
-        None | Some(StreamPos::Sequential(_)) => {
-            write_serial_mess_async(db, msg.into(), ser).await
+    match msg.expected_version {
+        ExpectedVersion::Exact(StreamPos::Relaxed(_)) => {
+            Err(unsupported_relaxed(&msg))
         }
-        Some(StreamPos::Relaxed(_)) => todo!(),
+        _ => write_serial_mess_async(db, msg.into(), ser).await,
     }
 }
 
@@ -197,6 +258,9 @@ pub async fn write_serial_mess_async<'a>(
     msg: WriteSerialMessage<'a>,
     ser: &mut WriteSerializer,
 ) -> Result<Position> {
+    if let Some(position) = written_before(&db, &msg)? {
+        return Ok(position);
+    }
     let (last_global, last_stream) = {
         let adb = Arc::clone(&db);
         let g = tokio::spawn(async move { get_last_global_position(&adb) });
@@ -210,8 +274,8 @@ pub async fn write_serial_mess_async<'a>(
     let next_global = last_global??.next();
     let stream_name = msg.stream_name.clone();
     let next_stream =
-        next_stream_pos(msg.expected_position, &stream_name, last_stream??)?;
-    write_records(&db, msg, next_global, next_stream, ser)
+        next_stream_pos(msg.expected_version, &stream_name, last_stream??)?;
+    write_records(&db, msg, next_global, next_stream, ser, None)
 }
 
 #[cfg(test)]
@@ -306,7 +370,7 @@ mod test_write_mess {
             message_type: "someMsgType".into(),
             data: Cow::Borrowed(b"{\"a\": 1})"),
             metadata: Cow::Borrowed(b"{\"b\": 2}"),
-            expected_stream_position: None,
+            expected_version: ExpectedVersion::NoStream,
         };
         write_mess(&db, msg, &mut ser).unwrap();
         let msg = WriteMessage {
@@ -315,7 +379,7 @@ mod test_write_mess {
             message_type: "someMsgType".into(),
             data: Cow::Borrowed(b"{\"a\": 1})"),
             metadata: Cow::Borrowed(b"{\"b\": 2}"),
-            expected_stream_position: None,
+            expected_version: ExpectedVersion::NoStream,
         };
         write_mess(&db, msg, &mut ser).unwrap();
         let msg = WriteMessage {
@@ -324,7 +388,7 @@ mod test_write_mess {
             message_type: "someMsgType".into(),
             data: Cow::Borrowed(b"{\"a\": 1})"),
             metadata: Cow::Borrowed(b"{\"b\": 2}"),
-            expected_stream_position: Some(StreamPos::Sequential(0)),
+            expected_version: ExpectedVersion::Exact(StreamPos::Sequential(0)),
         };
         write_mess(&db, msg, &mut ser).unwrap();
         let msg = WriteMessage {
@@ -333,7 +397,7 @@ mod test_write_mess {
             message_type: "someMsgType".into(),
             data: Cow::Borrowed(b"{\"a\": 1})"),
             metadata: Cow::Borrowed(b"{\"b\": 2}"),
-            expected_stream_position: Some(StreamPos::Sequential(0)),
+            expected_version: ExpectedVersion::Exact(StreamPos::Sequential(0)),
         };
         write_mess(&db, msg, &mut ser).unwrap();
         db
@@ -381,12 +445,16 @@ mod test_write_mess {
             message_type: "someMsgType".into(),
             data: Cow::Borrowed(b"{\"a\": 1})"),
             metadata: Cow::Borrowed(b"{\"b\": 2}"),
-            expected_stream_position: None,
+            expected_version: ExpectedVersion::NoStream,
         };
         let mut msg2 = msg1.clone();
-        msg2.expected_stream_position = Some(StreamPos::Sequential(0));
+        msg2.id = Id::new();
+        msg2.expected_version =
+            ExpectedVersion::Exact(StreamPos::Sequential(0));
         let mut msg3 = msg1.clone();
-        msg3.expected_stream_position = Some(StreamPos::Sequential(2));
+        msg3.id = Id::new();
+        msg3.expected_version =
+            ExpectedVersion::Exact(StreamPos::Sequential(2));
 
         let mut ser = ser();
         write_mess(&db, msg1, &mut ser).unwrap();
@@ -394,8 +462,8 @@ mod test_write_mess {
         let result = write_mess(&db, msg3, &mut ser).unwrap_err();
         assert!(let Error::WrongStreamPosition {
             stream: _,
-            expected: Some(2),
-            got: Some(1)
+            expected: ExpectedVersion::Exact(StreamPos::Sequential(2)),
+            got: Some(StreamPos::Sequential(1))
         } = result);
     }
 }
@@ -0,0 +1,38 @@
+use rusqlite::{functions::FunctionFlags, Connection};
+
+use super::message_db;
+use crate::{error::Result, read};
+
+/// Registers the SQL functions which reads rely on, and message-db's, see
+/// [`message_db`]. SQLite functions live on the connection, so this has to
+/// run for every new connection.
+pub fn register_functions(conn: &Connection) -> Result<()> {
+    conn.create_scalar_function(
+        "hash_64",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let value = ctx.get_raw(0).as_str()?;
+            Ok(read::hash_64(value) as i64)
+        },
+    )?;
+    message_db::register(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+    use rstest::*;
+
+    #[rstest]
+    fn hash_64_matches_the_rust_hash() {
+        let conn = Connection::open_in_memory().unwrap();
+        register_functions(&conn).unwrap();
+        let hash: i64 = conn
+            .query_row("SELECT hash_64('account-123')", [], |row| row.get(0))
+            .unwrap();
+        assert!(hash as u64 == read::hash_64("account-123"));
+    }
+}
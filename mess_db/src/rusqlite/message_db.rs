@@ -0,0 +1,643 @@
+//! [message-db](http://docs.eventide-project.org/user-guide/message-db/)'s
+//! server functions, so that scripts and tools written for message-db work
+//! on a connection to a mess SQLite file:
+//!
+//! ```sql
+//! SELECT write_message('<id>', 'account-123', 'Deposited', '{"amount":5}');
+//! SELECT * FROM get_stream_messages('account-123', 0, 10);
+//! SELECT * FROM get_category_messages('account', 1, 10, NULL, 0, 2);
+//! SELECT * FROM get_last_stream_message('account-123');
+//! SELECT stream_version('account-123');
+//! ```
+//!
+//! Arguments and defaults are message-db's, and `NULL` stands for a left out
+//! argument. Positions are the stream positions mess writes, without their
+//! Sequential/Relaxed bit. The `condition` argument, which message-db only
+//! takes when it is configured to, is refused.
+//!
+//! The reads are table-valued functions, which SQLite builds on virtual
+//! tables: the `unsafe` below is what rusqlite asks of those, and of
+//! functions which query the connection they run on.
+
+use std::{marker::PhantomData, os::raw::c_int};
+
+use rusqlite::{
+    ffi,
+    functions::{Context, FunctionFlags},
+    types::{ToSql, Value},
+    vtab::{
+        self, eponymous_only_module, IndexConstraintOp, IndexInfo, VTab,
+        VTabConnection, VTabCursor, Values,
+    },
+    Connection,
+};
+
+use super::{
+    read::{filter_clause, get_latest_stream_position},
+    write::insert_message,
+};
+use crate::{
+    error::{Error, Result},
+    read::{ConsumerGroup, ReadFilter},
+    write::ExpectedVersion,
+    StreamPos,
+};
+
+/// The columns of message-db's `message` type, as the reads return them.
+const COLUMNS: &str = r#"
+    id,
+    stream_name,
+    message_type,
+    position >> 1,
+    global_position,
+    data,
+    metadata,
+    strftime('%Y-%m-%d %H:%M:%f', time_ms / 1000.0, 'unixepoch')"#;
+
+/// How many of [`COLUMNS`] there are. The function arguments follow them.
+const ROW_COLUMNS: usize = 8;
+
+/// Registers `write_message`, `stream_version`, `get_stream_messages`,
+/// `get_category_messages` and `get_last_stream_message` on the connection.
+pub fn register(conn: &Connection) -> Result<()> {
+    conn.create_scalar_function(
+        "write_message",
+        -1,
+        // Writes only where they are asked for, never from a trigger or view.
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DIRECTONLY,
+        |ctx| {
+            // SAFETY: the connection is only used for the duration of the
+            // call, on the thread running it.
+            let conn = unsafe { ctx.get_connection()? };
+            write_message(&conn, ctx).map_err(user_error)
+        },
+    )?;
+    conn.create_scalar_function(
+        "stream_version",
+        1,
+        FunctionFlags::SQLITE_UTF8,
+        |ctx| {
+            // SAFETY: as for write_message.
+            let conn = unsafe { ctx.get_connection()? };
+            let stream_name: String = ctx.get(0)?;
+            let version = get_latest_stream_position(&conn, &stream_name)
+                .map_err(user_error)?;
+            Ok(version.map(|pos| pos.position() as i64))
+        },
+    )?;
+    for read in [Read::Stream, Read::Category, Read::LastStream] {
+        conn.create_module(
+            read.name(),
+            eponymous_only_module::<ReadTab>(),
+            Some(read),
+        )?;
+    }
+    Ok(())
+}
+
+fn user_error(err: Error) -> rusqlite::Error {
+    rusqlite::Error::UserFunctionError(Box::new(err))
+}
+
+/// `write_message(id, stream_name, type, data, metadata, expected_version)`,
+/// returning the position written at.
+fn write_message(conn: &Connection, ctx: &Context<'_>) -> Result<i64> {
+    if !(4..=6).contains(&ctx.len()) {
+        return Err(Error::Other(
+            "write_message takes 4 to 6 arguments".into(),
+        ));
+    }
+    let optional = |i| match ctx.len() > i {
+        true => ctx.get::<Value>(i),
+        false => Ok(Value::Null),
+    };
+    let id: String = ctx.get(0)?;
+    let stream_name: String = ctx.get(1)?;
+    let message_type: String = ctx.get(2)?;
+    let data = match ctx.get::<Value>(3)? {
+        Value::Null => {
+            return Err(Error::Other("write_message needs data".into()))
+        }
+        data => data,
+    };
+    let metadata = match optional(4)? {
+        Value::Null => None,
+        metadata => Some(metadata),
+    };
+    let expected_version = match optional(5)? {
+        Value::Null => ExpectedVersion::Any,
+        Value::Integer(-1) => ExpectedVersion::NoStream,
+        Value::Integer(pos) if pos >= 0 => {
+            ExpectedVersion::Exact(StreamPos::Sequential(pos as u64))
+        }
+        _ => return Err(Error::Other("invalid expected_version".into())),
+    };
+    let written = insert_message(
+        conn,
+        &id,
+        &stream_name,
+        &message_type,
+        data,
+        metadata,
+        expected_version,
+    );
+    match written {
+        Ok(pos) => Ok(pos.stream.position() as i64),
+        Err(Error::WrongStreamPosition { stream, expected, got }) => {
+            let version = |pos: Option<StreamPos>| {
+                pos.map_or(-1, |pos| pos.position() as i64)
+            };
+            let expected = match expected {
+                ExpectedVersion::Exact(pos) => version(Some(pos)),
+                _ => -1,
+            };
+            // Clients of message-db look for this message.
+            Err(Error::Other(format!(
+                "Wrong expected version: {expected} (Stream: {stream}, \
+                 Stream Version: {})",
+                version(got)
+            )))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The table-valued functions, each with message-db's arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Read {
+    /// `get_stream_messages(stream_name, position, batch_size, condition)`
+    Stream,
+    /// `get_category_messages(category, position, batch_size, correlation,
+    /// consumer_group_member, consumer_group_size, condition)`
+    Category,
+    /// `get_last_stream_message(stream_name, type)`
+    LastStream,
+}
+
+impl Read {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Stream => "get_stream_messages",
+            Self::Category => "get_category_messages",
+            Self::LastStream => "get_last_stream_message",
+        }
+    }
+
+    const fn args(self) -> &'static [&'static str] {
+        match self {
+            Self::Stream => {
+                &["stream_name", "position", "batch_size", "condition"]
+            }
+            Self::Category => &[
+                "category",
+                "position",
+                "batch_size",
+                "correlation",
+                "consumer_group_member",
+                "consumer_group_size",
+                "condition",
+            ],
+            Self::LastStream => &["stream_name", "type"],
+        }
+    }
+
+    /// Reads the rows for the arguments, `NULL` standing for those left
+    /// out.
+    fn rows(self, conn: &Connection, args: &[Value]) -> Result<Vec<Row>> {
+        let arg = |i: usize| args.get(i).unwrap_or(&Value::Null);
+        let text = |i| match arg(i) {
+            Value::Null => Ok(None),
+            Value::Text(text) => Ok(Some(text.as_str())),
+            _ => Err(invalid(self, i)),
+        };
+        let int = |i| match arg(i) {
+            Value::Null => Ok(None),
+            Value::Integer(n) => Ok(Some(*n)),
+            _ => Err(invalid(self, i)),
+        };
+        let name = text(0)?.ok_or_else(|| invalid(self, 0))?;
+        match self {
+            Self::Stream => {
+                if text(3)?.is_some() {
+                    return Err(no_condition());
+                }
+                if !name.contains('-') {
+                    return Err(Error::Other(format!(
+                        "must be a stream name: {name}"
+                    )));
+                }
+                let position = int(1)?.unwrap_or(0);
+                let batch_size = int(2)?.unwrap_or(1000);
+                query(
+                    conn,
+                    &format!(
+                        "SELECT {COLUMNS}
+                        FROM messages
+                        WHERE stream_name = ?1 AND position >> 1 >= ?2
+                        ORDER BY global_position ASC
+                        LIMIT ?3"
+                    ),
+                    &[&name, &position, &batch_size],
+                )
+            }
+            Self::Category => {
+                if text(6)?.is_some() {
+                    return Err(no_condition());
+                }
+                if name.contains('-') {
+                    return Err(Error::Other(format!(
+                        "must be a category: {name}"
+                    )));
+                }
+                let position = int(1)?.unwrap_or(1);
+                let batch_size = int(2)?.unwrap_or(1000);
+                let consumer_group =
+                    match (int(4)?, int(5)?) {
+                        (None, None) => None,
+                        (Some(member), Some(size)) => {
+                            let group = u32::try_from(member)
+                                .ok()
+                                .zip(u32::try_from(size).ok())
+                                .ok_or_else(|| invalid(self, 4))?;
+                            Some(ConsumerGroup::new(group.0, group.1)?)
+                        }
+                        _ => return Err(Error::Other(
+                            "consumer_group_member and consumer_group_size \
+                             go together"
+                                .into(),
+                        )),
+                    };
+                let filter = ReadFilter {
+                    correlation: text(3)?.map(str::to_owned),
+                    consumer_group,
+                    ..ReadFilter::none()
+                };
+                let (filter_clause, filter_params) = filter_clause(&filter, 4);
+                let mut params: Vec<&dyn ToSql> =
+                    vec![&name, &position, &batch_size];
+                params.extend(filter_params);
+                query(
+                    conn,
+                    &format!(
+                        "SELECT {COLUMNS}
+                        FROM messages
+                        WHERE (
+                            category = ?1
+                            OR (stream_name = ?1 AND instr(stream_name, '-') = 0)
+                        )
+                        AND global_position >= ?2
+                        {filter_clause}
+                        ORDER BY global_position ASC
+                        LIMIT ?3"
+                    ),
+                    &params,
+                )
+            }
+            Self::LastStream => query(
+                conn,
+                &format!(
+                    "SELECT {COLUMNS}
+                    FROM messages
+                    WHERE stream_name = ?1
+                    AND (?2 IS NULL OR message_type = ?2)
+                    ORDER BY global_position DESC
+                    LIMIT 1"
+                ),
+                &[&name, &text(1)?],
+            ),
+        }
+    }
+}
+
+fn invalid(read: Read, arg: usize) -> Error {
+    Error::Other(format!("{}: invalid {}", read.name(), read.args()[arg]))
+}
+
+fn no_condition() -> Error {
+    Error::Other("the condition argument is not supported".into())
+}
+
+/// A row of message-db's `message` type, with a value for each of
+/// [`COLUMNS`].
+type Row = Vec<Value>;
+
+fn query(
+    conn: &Connection,
+    sql: &str,
+    params: &[&dyn ToSql],
+) -> Result<Vec<Row>> {
+    let mut stmt = conn.prepare_cached(sql)?;
+    let rows = stmt
+        .query_map(params, |row| {
+            (0..ROW_COLUMNS).map(|i| row.get(i)).collect()
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(rows)
+}
+
+#[repr(C)]
+struct ReadTab {
+    /// What SQLite knows of the table. It has to come first.
+    base: ffi::sqlite3_vtab,
+    read: Read,
+    db: *mut ffi::sqlite3,
+}
+
+// SAFETY: the table only hands out cursors borrowing it, and it reads
+// through the connection it was made for.
+unsafe impl<'vtab> VTab<'vtab> for ReadTab {
+    type Aux = Read;
+    type Cursor = ReadCursor<'vtab>;
+
+    fn connect(
+        db: &mut VTabConnection,
+        aux: Option<&Read>,
+        _args: &[&[u8]],
+    ) -> rusqlite::Result<(String, Self)> {
+        let read = *aux.expect("modules are registered with their read");
+        let args: Vec<_> =
+            read.args().iter().map(|arg| format!("arg_{arg} HIDDEN")).collect();
+        let schema = format!(
+            "CREATE TABLE x(id, stream_name, type, position, global_position, \
+             data, metadata, time, {})",
+            args.join(", ")
+        );
+        // SAFETY: SQLite drops the table before it closes the connection.
+        let db = unsafe { db.handle() };
+        Ok((schema, Self { base: ffi::sqlite3_vtab::default(), read, db }))
+    }
+
+    /// Takes each argument given, in order. Their bits in `idx_num` tell
+    /// `filter` which ones it was passed.
+    fn best_index(&self, info: &mut IndexInfo) -> rusqlite::Result<()> {
+        let mut given = vec![None; self.read.args().len()];
+        for (i, constraint) in info.constraints().enumerate() {
+            let Some(arg) =
+                (constraint.column() as usize).checked_sub(ROW_COLUMNS)
+            else {
+                continue;
+            };
+            if !constraint.is_usable() {
+                // Another plan passes the argument.
+                return Err(rusqlite::Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_CONSTRAINT),
+                    None,
+                ));
+            }
+            if constraint.operator()
+                == IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ
+            {
+                given[arg] = Some(i);
+            }
+        }
+        let mut idx_num = 0;
+        let mut argv_index = 0;
+        for (arg, constraint) in given.into_iter().enumerate() {
+            if let Some(constraint) = constraint {
+                argv_index += 1;
+                idx_num |= 1 << arg;
+                let mut usage = info.constraint_usage(constraint);
+                usage.set_argv_index(argv_index);
+                usage.set_omit(true);
+            }
+        }
+        info.set_idx_num(idx_num);
+        info.set_estimated_cost(1.0);
+        Ok(())
+    }
+
+    fn open(&'vtab mut self) -> rusqlite::Result<ReadCursor<'vtab>> {
+        Ok(ReadCursor {
+            base: ffi::sqlite3_vtab_cursor::default(),
+            read: self.read,
+            db: self.db,
+            rows: vec![],
+            row: 0,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[repr(C)]
+struct ReadCursor<'vtab> {
+    /// What SQLite knows of the cursor. It has to come first.
+    base: ffi::sqlite3_vtab_cursor,
+    read: Read,
+    db: *mut ffi::sqlite3,
+    rows: Vec<Row>,
+    row: usize,
+    phantom: PhantomData<&'vtab ReadTab>,
+}
+
+// SAFETY: the cursor lives no longer than its table, and so its connection.
+unsafe impl VTabCursor for ReadCursor<'_> {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &Values<'_>,
+    ) -> rusqlite::Result<()> {
+        let mut given = values.iter();
+        let args = (0..self.read.args().len())
+            .map(|arg| match idx_num & (1 << arg) {
+                0 => Value::Null,
+                _ => given.next().map_or(Value::Null, |value| value.into()),
+            })
+            .collect::<Vec<_>>();
+        // SAFETY: the handle is the connection running this statement, and
+        // isn't closed by dropping this.
+        let conn = unsafe { Connection::from_handle(self.db)? };
+        self.rows = self
+            .read
+            .rows(&conn, &args)
+            .map_err(|err| rusqlite::Error::ModuleError(err.to_string()))?;
+        self.row = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> rusqlite::Result<()> {
+        self.row += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row >= self.rows.len()
+    }
+
+    fn column(
+        &self,
+        ctx: &mut vtab::Context,
+        i: c_int,
+    ) -> rusqlite::Result<()> {
+        match self.rows[self.row].get(i as usize) {
+            Some(value) => ctx.set_result(value),
+            // An argument, which is only given back as it was passed.
+            None => ctx.set_result(&Value::Null),
+        }
+    }
+
+    fn rowid(&self) -> rusqlite::Result<i64> {
+        Ok(self.row as i64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rusqlite::test::new_memory_conn_with_migrations;
+    use assert2::assert;
+    use rstest::*;
+
+    #[fixture]
+    fn conn() -> Connection {
+        new_memory_conn_with_migrations()
+    }
+
+    fn write(conn: &Connection, id: &str, stream: &str, meta: &str) -> i64 {
+        conn.query_row(
+            "SELECT write_message(?1, ?2, 'Deposited', '{\"n\":1}', ?3)",
+            [id, stream, meta],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    fn ids(conn: &Connection, sql: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(sql).unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[rstest]
+    fn write_message_returns_the_stream_position(conn: Connection) {
+        assert!(write(&conn, "a", "account-1", "{}") == 0);
+        assert!(write(&conn, "b", "account-1", "{}") == 1);
+        let version: Option<i64> = conn
+            .query_row("SELECT stream_version('account-1')", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(version == Some(1));
+        let version: Option<i64> = conn
+            .query_row("SELECT stream_version('account-2')", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(version.is_none());
+    }
+
+    #[rstest]
+    fn write_message_checks_the_expected_version(conn: Connection) {
+        let sql = "SELECT write_message('a', 'account-1', 'Opened', '{}', \
+                   NULL, -1)";
+        conn.query_row(sql, [], |_| Ok(())).unwrap();
+        let sql = sql.replace("'a'", "'b'");
+        let err = conn.query_row(&sql, [], |_| Ok(())).unwrap_err();
+        assert!(
+            err.to_string()
+                == "Wrong expected version: -1 (Stream: account-1, Stream \
+                    Version: 0)"
+        );
+        let sql = "SELECT write_message('b', 'account-1', 'Opened', '{}', \
+                   NULL, 0)";
+        let pos: i64 = conn.query_row(sql, [], |row| row.get(0)).unwrap();
+        assert!(pos == 1);
+    }
+
+    #[rstest]
+    fn get_stream_messages_reads_from_a_position(conn: Connection) {
+        for id in ["a", "b", "c"] {
+            write(&conn, id, "account-1", "{}");
+        }
+        write(&conn, "d", "account-2", "{}");
+        let read = "SELECT id FROM get_stream_messages('account-1', 1)";
+        assert!(ids(&conn, read) == ["b", "c"]);
+        let read = "SELECT id FROM get_stream_messages('account-1', 0, 1)";
+        assert!(ids(&conn, read) == ["a"]);
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT stream_name, type, position, global_position, data, \
+                 metadata, time FROM get_stream_messages('account-2')",
+            )
+            .unwrap();
+        let row = stmt
+            .query_row([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            })
+            .unwrap();
+        assert!(row.0 == "account-2");
+        assert!(row.1 == "Deposited");
+        assert!((row.2, row.3) == (0, 4));
+        assert!(row.4 == r#"{"n":1}"#);
+        assert!(row.5 == "{}");
+        assert!(row.6.len() == "2023-01-01 00:00:00.000".len());
+    }
+
+    #[rstest]
+    fn get_category_messages_honours_groups_and_correlation(conn: Connection) {
+        let replies = r#"{"correlationStreamName":"replies-1"}"#;
+        write(&conn, "a", "account-1", replies);
+        write(&conn, "b", "account-2", "{}");
+        write(&conn, "c", "account-3", replies);
+        write(&conn, "d", "other-1", replies);
+
+        let read = "SELECT id FROM get_category_messages('account', 2)";
+        assert!(ids(&conn, read) == ["b", "c"]);
+        let read = "SELECT id FROM get_category_messages('account', 1, \
+                    NULL, 'replies')";
+        assert!(ids(&conn, read) == ["a", "c"]);
+
+        let mut grouped = vec![];
+        for member in 0..2 {
+            let read = format!(
+                "SELECT id FROM get_category_messages('account', 1, NULL, \
+                 NULL, {member}, 2)"
+            );
+            grouped.extend(ids(&conn, &read));
+        }
+        grouped.sort();
+        assert!(grouped == ["a", "b", "c"]);
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM get_category_messages('account-1')")]
+    #[case("SELECT * FROM get_stream_messages('account')")]
+    #[case("SELECT * FROM get_category_messages('account', 1, 10, NULL, 0)")]
+    #[case("SELECT * FROM get_category_messages('account', 1, 10, NULL, 2, 2)")]
+    #[case("SELECT * FROM get_stream_messages('account-1', 0, 10, 'x = 1')")]
+    #[case("SELECT * FROM get_stream_messages()")]
+    fn invalid_reads_fail(conn: Connection, #[case] sql: &str) {
+        let mut stmt = conn.prepare(sql).unwrap();
+        assert!(stmt
+            .query([])
+            .and_then(|mut rows| rows.next().map(drop))
+            .is_err());
+    }
+
+    #[rstest]
+    fn get_last_stream_message_can_filter_by_type(conn: Connection) {
+        write(&conn, "a", "account-1", "{}");
+        conn.query_row(
+            "SELECT write_message('b', 'account-1', 'Withdrawn', '{}')",
+            [],
+            |_| Ok(()),
+        )
+        .unwrap();
+        let read = "SELECT id FROM get_last_stream_message('account-1')";
+        assert!(ids(&conn, read) == ["b"]);
+        let read =
+            "SELECT id FROM get_last_stream_message('account-1', 'Deposited')";
+        assert!(ids(&conn, read) == ["a"]);
+        let read = "SELECT id FROM get_last_stream_message('account-2')";
+        assert!(ids(&conn, read).is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use super::functions::register_functions;
 use crate::error::{Error, Result};
 use once_cell::sync::Lazy;
 use rusqlite::{Connection, Transaction};
@@ -13,7 +14,7 @@ const TIME_FACTOR: u64 = 1000 / CLOCK_RESOLUTION_MS;
 type MigrationFn =
     Box<dyn Send + Sync + Fn(&Transaction) -> rusqlite::Result<()>>;
 
-static MIGRATIONS: Lazy<[MigrationFn; 1]> = Lazy::new(|| {
+static MIGRATIONS: Lazy<[MigrationFn; 3]> = Lazy::new(|| {
     [
         // Migration 1 creates the messages table.
         Box::new(|tx: &Transaction| {
@@ -118,7 +119,51 @@ END;
             tx.execute("CREATE UNIQUE INDEX messages_id ON messages (id)", [])?;
             Ok(())
         }),
-        // Migration 2...
+        // Migration 2 creates the consumer positions table. Consumers outside
+        // of a group use member -1.
+        Box::new(|tx: &Transaction| {
+            tx.execute(
+                r#"
+            CREATE TABLE consumer_positions (
+                consumer TEXT NOT NULL,
+                member INTEGER NOT NULL DEFAULT -1,
+                global_position INTEGER NOT NULL,
+                PRIMARY KEY (consumer, member)
+            )
+            STRICT;
+        "#,
+                [],
+            )?;
+            Ok(())
+        }),
+        // Migration 3 makes check_stream_position compare encoded positions,
+        // as writes store them: each message in a stream is 2 past the last
+        // one, keeping its Sequential/Relaxed bit. Whether the write's
+        // expected version holds is checked before inserting.
+        Box::new(|tx: &Transaction| {
+            tx.execute("DROP TRIGGER IF EXISTS check_stream_position", [])?;
+            tx.execute(
+                r#"
+CREATE TRIGGER check_stream_position
+BEFORE INSERT ON messages
+FOR EACH ROW
+BEGIN
+    SELECT CASE WHEN
+        IFNULL((
+            SELECT position + 2
+            FROM messages
+            WHERE stream_name = NEW.stream_name
+            ORDER BY global_position DESC
+            LIMIT 1
+        ), NEW.position & 1) != NEW.position
+    THEN RAISE(ROLLBACK, 'stream position mismatch') END;
+END;
+        "#,
+                [],
+            )?;
+            Ok(())
+        }),
+        // Migration 4...
         // Box::new(|tx: &Transaction| {
         //     tx.execute("", [])?;
         //     Ok(())
@@ -139,8 +184,11 @@ fn set_user_version(conn: &Connection, version: i32) -> Result<()> {
 }
 
 /// Runs thoughs migrations which have not been run and runs them, updating
-/// the tracked migration version in the db.
+/// the tracked migration version in the db. It also registers the SQL
+/// functions which reads rely on, see [`register_functions`].
 pub fn migrate(conn: &mut Connection) -> Result<()> {
+    register_functions(conn)?;
+
     let starting_version = get_user_version(conn)?;
 
     for (version, migration) in
@@ -1,7 +1,11 @@
 #![cfg(feature = "rusqlite")]
 pub mod connection;
+pub mod functions;
+pub mod message_db;
 pub mod migration;
+pub mod position;
 pub mod read;
+pub mod storage;
 pub mod write;
 
 #[cfg(test)]
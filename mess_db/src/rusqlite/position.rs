@@ -0,0 +1,160 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use super::write::{write_mess, write_raw_mess};
+use crate::{
+    error::Result,
+    position::ConsumerId,
+    write::{WriteMessage, WriteMessageOld},
+    Position,
+};
+
+// Consumers outside of a group are stored as member -1.
+fn member(consumer: &ConsumerId) -> i64 {
+    consumer.group_member().map_or(-1, i64::from)
+}
+
+/// Returns the consumer's last saved global position, if it has one.
+pub fn load_position(
+    conn: &Connection,
+    consumer: &ConsumerId,
+) -> Result<Option<u64>> {
+    let mut stmt = conn.prepare_cached(
+        r#"
+        SELECT global_position FROM consumer_positions
+        WHERE consumer = ?1 AND member = ?2"#,
+    )?;
+    let pos: Option<i64> = stmt
+        .query_row(params![consumer.name(), member(consumer)], |row| row.get(0))
+        .optional()?;
+    Ok(pos.map(|pos| pos as u64))
+}
+
+/// Saves the consumer's position. Call it inside the same transaction as a
+/// write to store both or neither, as [`write_mess_with_position`] does.
+pub fn save_position(
+    conn: &Connection,
+    consumer: &ConsumerId,
+    global_pos: u64,
+) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        r#"
+        INSERT INTO consumer_positions (consumer, member, global_position)
+        VALUES (?1, ?2, ?3)
+        ON CONFLICT (consumer, member)
+        DO UPDATE SET global_position = excluded.global_position"#,
+    )?;
+    stmt.execute(params![
+        consumer.name(),
+        member(consumer),
+        global_pos as i64
+    ])?;
+    Ok(())
+}
+
+/// Forgets the consumer's position so it starts again from the beginning.
+pub fn reset_position(conn: &Connection, consumer: &ConsumerId) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "DELETE FROM consumer_positions WHERE consumer = ?1 AND member = ?2",
+    )?;
+    stmt.execute(params![consumer.name(), member(consumer)])?;
+    Ok(())
+}
+
+/// Writes the message and saves the consumer's position in one transaction.
+pub fn write_mess_with_position<D: Serialize, M: Serialize>(
+    conn: &mut Connection,
+    msg: WriteMessageOld<D, M>,
+    consumer: &ConsumerId,
+    global_pos: u64,
+) -> Result<Position> {
+    let tx = conn.transaction()?;
+    let pos = write_mess(&tx, msg)?;
+    save_position(&tx, consumer, global_pos)?;
+    tx.commit()?;
+    Ok(pos)
+}
+
+/// Like [`write_mess_with_position`], for a message whose data and metadata
+/// are already encoded.
+pub fn write_raw_mess_with_position(
+    conn: &mut Connection,
+    msg: WriteMessage<'_>,
+    consumer: &ConsumerId,
+    global_pos: u64,
+) -> Result<Position> {
+    let tx = conn.transaction()?;
+    let pos = write_raw_mess(&tx, msg)?;
+    save_position(&tx, consumer, global_pos)?;
+    tx.commit()?;
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod test {
+    use assert2::assert;
+    use ident::Id;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        rusqlite::test::new_memory_conn_with_migrations,
+        write::ExpectedVersion, StreamPos,
+    };
+
+    fn message(
+        expected_version: ExpectedVersion,
+    ) -> WriteMessageOld<'static, serde_json::Value, ()> {
+        WriteMessageOld {
+            id: Id::new(),
+            stream_name: "stream1".into(),
+            message_type: "someMsgType".into(),
+            data: json!({}),
+            metadata: None,
+            expected_version,
+        }
+    }
+
+    #[test]
+    fn it_loads_what_was_saved() {
+        let conn = new_memory_conn_with_migrations();
+        let consumer = ConsumerId::new("projector");
+        assert!(load_position(&conn, &consumer).unwrap().is_none());
+        save_position(&conn, &consumer, 7).unwrap();
+        save_position(&conn, &consumer, 12).unwrap();
+        assert!(load_position(&conn, &consumer).unwrap() == Some(12));
+        reset_position(&conn, &consumer).unwrap();
+        assert!(load_position(&conn, &consumer).unwrap().is_none());
+    }
+
+    #[test]
+    fn group_members_are_stored_separately() {
+        let conn = new_memory_conn_with_migrations();
+        let consumer = ConsumerId::new("projector");
+        save_position(&conn, &consumer.clone().member(0), 3).unwrap();
+        save_position(&conn, &consumer.clone().member(1), 5).unwrap();
+        assert!(load_position(&conn, &consumer).unwrap().is_none());
+        let member0 = consumer.clone().member(0);
+        assert!(load_position(&conn, &member0).unwrap() == Some(3));
+        assert!(load_position(&conn, &consumer.member(1)).unwrap() == Some(5));
+    }
+
+    #[test]
+    fn a_failed_write_does_not_save_the_position() {
+        let mut conn = new_memory_conn_with_migrations();
+        let consumer = ConsumerId::new("projector");
+        write_mess_with_position(
+            &mut conn,
+            message(ExpectedVersion::NoStream),
+            &consumer,
+            1,
+        )
+        .unwrap();
+        assert!(load_position(&conn, &consumer).unwrap() == Some(1));
+
+        let stale = message(ExpectedVersion::Exact(StreamPos::Sequential(5)));
+        let res = write_mess_with_position(&mut conn, stale, &consumer, 2);
+        assert!(res.is_err());
+        assert!(load_position(&conn, &consumer).unwrap() == Some(1));
+    }
+}
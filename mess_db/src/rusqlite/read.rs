@@ -1,42 +1,80 @@
-use std::borrow::Cow;
-
-use rusqlite::{params, Connection};
+use rusqlite::{
+    params,
+    types::{ToSql, Value},
+    Connection, OptionalExtension,
+};
 
 use crate::{
+    clock::Tick,
     error::Error,
-    read::OptStream,
-    read::{GetMessages, OptGlobalPos, OptStreamPos, Unset},
-    Message, StreamPos,
+    read::{
+        Cursor, CursorRead, Direction, IntoCursor, ReadFilter, LIMIT_DEFAULT,
+        LIMIT_MAX,
+    },
+    Message, StreamInfo, StreamPos,
 };
 
+/// Returns the `AND ..` clauses for a read filter, numbering its parameters
+/// from `first_param`, along with the parameter values.
+pub(crate) fn filter_clause(
+    filter: &ReadFilter,
+    first_param: usize,
+) -> (String, Vec<&dyn ToSql>) {
+    let mut clause = String::new();
+    let mut params: Vec<&dyn ToSql> = vec![];
+    if let Some(types) = &filter.types {
+        let placeholders: Vec<_> = (0..types.len())
+            .map(|i| format!("?{}", first_param + params.len() + i))
+            .collect();
+        clause.push_str(&format!(
+            "AND message_type IN ({})\n",
+            placeholders.join(", ")
+        ));
+        params.extend(types.iter().map(|t| t as &dyn ToSql));
+    }
+    if let Some(correlation) = &filter.correlation {
+        let n = first_param + params.len();
+        clause.push_str(&format!(
+            r#"AND (
+                metadata->>'correlationStreamName' = ?{n}
+                OR substr(
+                    metadata->>'correlationStreamName', 1, length(?{n}) + 1
+                ) = ?{n} || '-'
+            )
+            "#
+        ));
+        params.push(correlation);
+    }
+    if let Some(group) = &filter.consumer_group {
+        let n = first_param + params.len();
+        clause.push_str(&format!(
+            r#"AND hash_64(
+                CASE WHEN cardinal_id = '' THEN stream_id ELSE cardinal_id END
+            ) % ?{n} = ?{}
+            "#,
+            n + 1
+        ));
+        params.push(&group.size);
+        params.push(&group.member);
+    }
+    (clause, params)
+}
+
+/// Returns the limit of a read asking for `limit` messages.
+fn clamp_limit(limit: Option<i32>) -> usize {
+    limit.map_or(LIMIT_DEFAULT, |limit| {
+        limit.clamp(1, LIMIT_MAX as i32) as usize
+    })
+}
+
 pub fn get_messages(
     conn: &Connection,
     global_position: i32,
     limit: Option<i32>,
 ) -> Result<Vec<Message>, Error> {
-    let limit = limit.unwrap_or(1_000).clamp(1, 10_000);
-    let mut stmt = conn.prepare_cached(
-        r#"
-        SELECT
-            global_position,
-            position,
-            time_ms,
-            stream_name,
-            message_type,
-            data,
-            metadata,
-            id
-        FROM messages
-        WHERE global_position >= $1
-        ORDER BY global_position ASC
-        LIMIT $2"#,
-    )?;
-    let messages = stmt
-        .query_and_then(params![global_position, limit], |row| {
-            Message::try_from(row)
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-    Ok(messages)
+    let global_pos = u64::try_from(global_position).unwrap_or(0);
+    let cursor = Cursor::global(None, global_pos, clamp_limit(limit));
+    get_cursor_messages(conn, &cursor)
 }
 
 pub fn get_stream_messages<'a>(
@@ -44,109 +82,147 @@ pub fn get_stream_messages<'a>(
     stream_name: &str,
     limit: Option<i32>,
 ) -> Result<Vec<Message<'a>>, Error> {
-    let limit = limit.unwrap_or(1_000).clamp(1, 10_000);
+    let cursor =
+        Cursor::stream(stream_name.to_owned(), None, clamp_limit(limit));
+    get_cursor_messages(conn, &cursor)
+}
+
+/// Returns the global position of the first message recorded at or after
+/// `tick`, or `None` if every message is older.
+pub fn position_at_time(
+    conn: &Connection,
+    tick: Tick,
+) -> Result<Option<u64>, Error> {
     let mut stmt = conn.prepare_cached(
         r#"
-        SELECT
-            global_position,
-            position,
-            time_ms,
-            stream_name,
-            message_type,
-            data,
-            metadata,
-            id
-        FROM messages
-        WHERE stream_name = $1
-        ORDER BY global_position ASC
-        LIMIT $2"#,
+        SELECT global_position FROM messages
+        WHERE ord >= ?1
+        ORDER BY ord ASC
+        LIMIT 1"#,
     )?;
-    let messages = stmt
-        .query_and_then(params![stream_name, limit], |row| {
-            Message::try_from(row)
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-    Ok(messages)
+    let pos: Option<i64> = stmt
+        .query_row(params![tick.to_u64() as i64], |row| row.get(0))
+        .optional()?;
+    Ok(pos.map(|pos| pos as u64))
 }
 
-pub enum DbGetMessages<'a> {
-    GetGlobalMessages {
-        stream: Option<Cow<'a, str>>,
-        global_pos: u64,
-        limit: usize,
-    },
-    GetStreamMessages {
-        stream: Cow<'a, str>,
-        stream_pos: Option<StreamPos>,
-        limit: usize,
-    },
+/// `AND`ed conditions of a `WHERE` clause, with their parameter values.
+#[derive(Default)]
+struct Conditions {
+    clauses: Vec<String>,
+    params: Vec<Value>,
 }
 
-impl<'a> From<GetMessages<Unset, OptGlobalPos, Unset>> for DbGetMessages<'a> {
-    fn from(val: GetMessages<Unset, OptGlobalPos, Unset>) -> Self {
-        DbGetMessages::GetGlobalMessages {
-            stream: None,
-            global_pos: val.start_global_position.0,
-            limit: val.limit,
-        }
+impl Conditions {
+    /// Adds a clause whose `?` placeholders all bind `value`.
+    fn push(&mut self, clause: &str, value: impl Into<Value>) {
+        self.params.push(value.into());
+        let n = self.params.len();
+        self.clauses.push(clause.replace('?', &format!("?{n}")));
     }
-}
 
-impl<'a> From<GetMessages<OptStream<'a>, OptGlobalPos, Unset>>
-    for DbGetMessages<'a>
-{
-    fn from(val: GetMessages<OptStream<'a>, OptGlobalPos, Unset>) -> Self {
-        DbGetMessages::GetGlobalMessages {
-            stream: Some(val.stream.0),
-            global_pos: val.start_global_position.0,
-            limit: val.limit,
+    /// Bounds a position column at the start of a read: from `pos` on when
+    /// reading forward, or before it when reading backward.
+    fn start(&mut self, column: &str, direction: Direction, pos: u64) {
+        // Positions are stored as i64, so larger ones lie past every row.
+        let pos = pos.min(i64::MAX as u64) as i64;
+        match direction {
+            Direction::Forward => self.push(&format!("{column} >= ?"), pos),
+            Direction::Backward => self.push(&format!("{column} < ?"), pos),
         }
     }
 }
 
-impl<'a> From<GetMessages<OptStream<'a>, Unset, Unset>> for DbGetMessages<'a> {
-    fn from(val: GetMessages<OptStream<'a>, Unset, Unset>) -> Self {
-        DbGetMessages::GetStreamMessages {
-            stream: val.stream.0,
-            stream_pos: None,
-            limit: val.limit,
+/// Returns a page of the read the cursor describes, in its direction.
+pub fn get_cursor_messages<'a>(
+    conn: &Connection,
+    cursor: &Cursor,
+) -> Result<Vec<Message<'a>>, Error> {
+    cursor.validate()?;
+    let direction = cursor.direction;
+    let mut conditions = Conditions::default();
+    // The `category` column is empty for a stream without an id, which is a
+    // category of its own, as `read::category` has it.
+    let category = |conditions: &mut Conditions, category: &str| {
+        let clause = "(category = ? \
+            OR (stream_name = ? AND instr(stream_name, '-') = 0))";
+        conditions.push(clause, category.to_owned());
+    };
+    match &cursor.read {
+        CursorRead::Global { stream, global_pos } => {
+            if let Some(stream) = stream {
+                conditions.push("stream_name = ?", stream.clone());
+            }
+            conditions.start("global_position", direction, *global_pos);
         }
-    }
-}
-
-impl<'a> From<GetMessages<OptStream<'a>, Unset, OptStreamPos>>
-    for DbGetMessages<'a>
-{
-    fn from(val: GetMessages<OptStream<'a>, Unset, OptStreamPos>) -> Self {
-        DbGetMessages::GetStreamMessages {
-            stream: val.stream.0,
-            stream_pos: Some(val.start_stream_position.0),
-            limit: val.limit,
+        CursorRead::Stream { stream, stream_pos } => {
+            conditions.push("stream_name = ?", stream.clone());
+            if let Some(pos) = stream_pos {
+                conditions.start("position", direction, pos.encode());
+            }
+        }
+        CursorRead::Category { category: name, global_pos } => {
+            category(&mut conditions, name);
+            conditions.start("global_position", direction, *global_pos);
+        }
+        CursorRead::Between { category: name, range } => {
+            conditions.push("ord >= ?", range.start as i64);
+            conditions.push("ord < ?", range.end as i64);
+            if let Some(name) = name {
+                category(&mut conditions, name);
+            }
+            conditions.start("global_position", direction, range.global_pos);
         }
     }
+    let limit = cursor.limit as i64;
+    let (filter_clause, filter_params) =
+        filter_clause(&cursor.filter, conditions.params.len() + 2);
+    let order = match direction {
+        Direction::Forward => "ASC",
+        Direction::Backward => "DESC",
+    };
+    let mut stmt = conn.prepare_cached(&format!(
+        r#"
+        SELECT
+            global_position,
+            position,
+            time_ms,
+            stream_name,
+            message_type,
+            data,
+            metadata,
+            id
+        FROM messages
+        WHERE {}
+        {filter_clause}
+        ORDER BY global_position {order}
+        LIMIT ?{}"#,
+        conditions.clauses.join(" AND "),
+        conditions.params.len() + 1,
+    ))?;
+    let mut params: Vec<&dyn ToSql> =
+        conditions.params.iter().map(|p| p as &dyn ToSql).collect();
+    params.push(&limit);
+    params.extend(filter_params);
+    let messages = stmt
+        .query_and_then(params.as_slice(), |row| Message::try_from(row))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(messages)
 }
 
 // ```
-// use mess_db::read::ReadMessages;
-// use mess_db::sqlite::read::fetch;
-// let read_messages = ReadMessages::default()
-//     .from_stream("some_stream_name")
+// use mess_db::read::GetMessages;
+// use mess_db::rusqlite::read::fetch;
+// let read = GetMessages::default()
+//     .in_stream("some_stream_name")
 //     .with_limit(5);
-// fetch(read_messages, &pool).unwrap();
+// fetch(read, &conn).unwrap();
 // ```
 pub fn fetch<'a>(
-    req: impl Into<DbGetMessages<'a>>,
+    read: impl IntoCursor,
     conn: &'a Connection,
 ) -> Result<Vec<Message<'a>>, Error> {
-    let req = req.into();
-    match req {
-        DbGetMessages::GetGlobalMessages { stream: _, global_pos, limit } => {
-            get_messages(conn, global_pos as i32, Some(limit as i32))
-        }
-        DbGetMessages::GetStreamMessages { stream, stream_pos: _, limit } => {
-            get_stream_messages(conn, &stream, Some(limit as i32))
-        }
-    }
+    get_cursor_messages(conn, &read.into_cursor()?)
 }
 
 pub fn get_latest_stream_message<'a>(
@@ -177,7 +253,7 @@ pub fn get_latest_stream_message<'a>(
 pub fn get_latest_stream_position(
     conn: &Connection,
     stream_name: &str,
-) -> Result<Option<i64>, Error> {
+) -> Result<Option<StreamPos>, Error> {
     let mut stmt = conn.prepare_cached(
         r#"
         SELECT position
@@ -187,10 +263,78 @@ pub fn get_latest_stream_position(
         LIMIT 1
         "#,
     )?;
-    let mut q = stmt.query_and_then(params![stream_name], |row| row.get(0))?;
+    let mut q = stmt.query_and_then(params![stream_name], |row| {
+        row.get::<_, i64>(0).map(|pos| StreamPos::decode(pos as u64))
+    })?;
     q.next().transpose().map_err(|e| e.into())
 }
 
+/// Summarizes a stream, or returns `None` if it has no messages.
+pub fn stream_info(
+    conn: &Connection,
+    stream_name: &str,
+) -> Result<Option<StreamInfo>, Error> {
+    let mut stmt = conn.prepare_cached(
+        r#"
+        SELECT
+            COUNT(*),
+            (
+                SELECT position
+                FROM messages
+                WHERE stream_name = ?1
+                ORDER BY global_position DESC
+                LIMIT 1
+            ),
+            MIN(global_position),
+            MAX(global_position),
+            MIN(ord),
+            MAX(ord)
+        FROM messages
+        WHERE stream_name = ?1"#,
+    )?;
+    let info = stmt.query_row(params![stream_name], |row| {
+        let count: i64 = row.get(0)?;
+        if count == 0 {
+            return Ok(None);
+        }
+        let get_u64 = |i| row.get::<_, i64>(i).map(|x| x as u64);
+        Ok(Some(StreamInfo {
+            position: StreamPos::decode(get_u64(1)?),
+            count: count as u64,
+            first_global: get_u64(2)?,
+            last_global: get_u64(3)?,
+            first_time: Tick::from_u64(get_u64(4)?),
+            last_time: Tick::from_u64(get_u64(5)?),
+        }))
+    })?;
+    Ok(info)
+}
+
+/// Lists up to `limit` stream names starting with `prefix`, after `after`.
+/// They're ordered as the RocksDB backend keys them, by name and then its
+/// separator, so listings page the same way on either backend.
+pub fn list_streams(
+    conn: &Connection,
+    prefix: &str,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<Vec<String>, Error> {
+    let mut stmt = conn.prepare_cached(
+        r#"
+        SELECT DISTINCT stream_name
+        FROM messages
+        WHERE substr(stream_name, 1, length(?1)) = ?1
+            AND (?2 IS NULL OR stream_name || '|' > ?2 || '|')
+        ORDER BY stream_name || '|'
+        LIMIT ?3"#,
+    )?;
+    let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+    let names = stmt
+        .query_map(params![prefix, after, limit], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(names)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -204,9 +348,24 @@ mod test {
         let conn = crate::rusqlite::test::new_memory_conn_with_migrations();
 
         let rows = (0..).map(|i| {
+            let pos = StreamPos::Sequential(i);
             [
-                (format!("{:x<6}.xxxxxx", i), "stream1", i, "X", i, None::<()>),
-                (format!("{:x<6}.xxxxxy", i), "stream2", i, "X", i, None::<()>),
+                (
+                    format!("{:x<6}.xxxxxx", i),
+                    "stream1",
+                    pos,
+                    "X",
+                    i,
+                    None::<()>,
+                ),
+                (
+                    format!("{:x<6}.xxxxxy", i),
+                    "stream2",
+                    pos,
+                    "X",
+                    i,
+                    None::<()>,
+                ),
             ]
         });
 
@@ -333,6 +492,347 @@ mod test {
         }
     }
 
+    mod fn_fetch {
+        use super::*;
+        use crate::read::GetMessages;
+        use pretty_assertions::assert_eq;
+
+        fn globals(messages: &[Message]) -> Vec<u64> {
+            messages.iter().map(|m| m.global_position).collect()
+        }
+
+        #[rstest]
+        fn it_honours_the_stream_of_a_global_read() {
+            let conn = test_db(5);
+            let read = GetMessages::default()
+                .in_stream("stream2")
+                .from_global(3)
+                .with_limit(10);
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(globals(&messages), vec![4, 6, 8, 10]);
+        }
+
+        #[rstest]
+        fn it_honours_the_stream_position() {
+            let conn = test_db(5);
+            let read = GetMessages::default()
+                .in_stream("stream1")
+                .from_stream_pos(StreamPos::Sequential(3));
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(globals(&messages), vec![7, 9]);
+        }
+
+        #[rstest]
+        fn it_reads_the_global_log_backward() {
+            let conn = test_db(5);
+            let read = GetMessages::default().backwards().with_limit(3);
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(globals(&messages), vec![10, 9, 8]);
+            let read = GetMessages::default().from_global(4).backwards();
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(globals(&messages), vec![4, 3, 2, 1]);
+        }
+
+        #[rstest]
+        fn it_reads_a_stream_backward_before_a_position() {
+            let conn = test_db(5);
+            let read = GetMessages::default()
+                .in_stream("stream2")
+                .from_stream_pos(StreamPos::Sequential(2))
+                .backwards();
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(globals(&messages), vec![6, 4, 2]);
+        }
+
+        #[rstest]
+        fn it_rejects_a_read_it_cannot_honour() {
+            let conn = test_db(5);
+            let read = GetMessages::default()
+                .in_stream("stream1")
+                .from_global(1)
+                .from_stream_pos(StreamPos::Sequential(0));
+            let res = fetch(read, &conn);
+            assert!(matches!(res, Err(Error::InvalidRead(_))));
+        }
+    }
+
+    mod filtered_reads {
+        use super::*;
+        use crate::read::GetMessages;
+        use pretty_assertions::assert_eq;
+
+        fn typed_db() -> Connection {
+            let conn = crate::rusqlite::test::new_memory_conn_with_migrations();
+            for (i, message_type) in
+                ["A", "B", "C", "A", "B", "C"].iter().enumerate()
+            {
+                conn.execute(
+                    r#"
+                    INSERT INTO messages (
+                        id,
+                        stream_name,
+                        position,
+                        message_type,
+                        data
+                    ) VALUES ($1, 'stream1', $2, $3, $4)"#,
+                    params![
+                        format!("id-{i}"),
+                        StreamPos::Sequential(i as u64),
+                        message_type,
+                        "{}"
+                    ],
+                )
+                .unwrap();
+            }
+            conn
+        }
+
+        #[rstest]
+        fn it_only_returns_global_messages_of_given_types() {
+            let conn = typed_db();
+            let read = GetMessages::default().of_types(&["A", "C"]);
+            let messages = fetch(read, &conn).unwrap();
+            let found: Vec<_> =
+                messages.iter().map(|m| m.message_type.as_ref()).collect();
+            assert_eq!(found, ["A", "C", "A", "C"]);
+        }
+
+        #[rstest]
+        fn the_limit_counts_only_matching_messages() {
+            let conn = typed_db();
+            let read = GetMessages::default()
+                .in_stream("stream1")
+                .of_types(&["B"])
+                .with_limit(2);
+            let messages = fetch(read, &conn).unwrap();
+            let found: Vec<_> =
+                messages.iter().map(|m| m.message_type.as_ref()).collect();
+            assert_eq!(found, ["B", "B"]);
+        }
+
+        #[rstest]
+        fn no_types_returns_nothing() {
+            let conn = typed_db();
+            let read = GetMessages::default().of_types(&[] as &[&str]);
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(messages.len(), 0);
+        }
+    }
+
+    mod category_reads {
+        use super::*;
+        use crate::read::GetMessages;
+        use pretty_assertions::assert_eq;
+
+        fn correlated_db() -> Connection {
+            let conn = crate::rusqlite::test::new_memory_conn_with_migrations();
+            let rows = [
+                ("account-1", Some("accountComponent-1")),
+                ("account-2", Some("billing-7")),
+                ("accountTransaction-1", Some("accountComponent-2")),
+                ("account-1", None),
+                ("account", Some("accountComponent")),
+                ("account-3", Some("accountComponent:command-3")),
+            ];
+            let mut positions = std::collections::HashMap::new();
+            for (i, (stream_name, correlation)) in rows.iter().enumerate() {
+                let position = positions
+                    .entry(*stream_name)
+                    .or_insert(StreamPos::Sequential(0));
+                let metadata = correlation.map(|c| {
+                    serde_json::json!({ "correlationStreamName": c })
+                        .to_string()
+                });
+                conn.execute(
+                    r#"
+                    INSERT INTO messages (
+                        id,
+                        stream_name,
+                        position,
+                        message_type,
+                        data,
+                        metadata
+                    ) VALUES ($1, $2, $3, 'X', '{}', $4)"#,
+                    params![
+                        format!("id-{i}"),
+                        stream_name,
+                        *position,
+                        metadata
+                    ],
+                )
+                .unwrap();
+                *position = position.next();
+            }
+            conn
+        }
+
+        fn stream_names(messages: Vec<Message>) -> Vec<String> {
+            messages.into_iter().map(|m| m.stream_name.into()).collect()
+        }
+
+        #[rstest]
+        fn it_only_returns_messages_in_the_category() {
+            let conn = correlated_db();
+            let read = GetMessages::default().in_category("account");
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(
+                stream_names(messages),
+                ["account-1", "account-2", "account-1", "account", "account-3"]
+            );
+        }
+
+        #[rstest]
+        fn it_starts_at_the_given_global_position() {
+            let conn = correlated_db();
+            let read =
+                GetMessages::default().in_category("account").from_global(3);
+            let messages = fetch(read, &conn).unwrap();
+            assert_eq!(messages[0].global_position, 4);
+        }
+
+        #[rstest]
+        fn it_only_returns_messages_correlated_to_the_category() {
+            let conn = correlated_db();
+            let read = GetMessages::default()
+                .in_category("account")
+                .correlation("accountComponent");
+            let messages = fetch(read, &conn).unwrap();
+            let positions: Vec<_> =
+                messages.iter().map(|m| m.global_position).collect();
+            assert_eq!(positions, [1, 5]);
+        }
+
+        #[rstest]
+        fn consumer_group_members_split_the_category() {
+            let conn = crate::rusqlite::test::new_memory_conn_with_migrations();
+            for i in 0..20 {
+                conn.execute(
+                    r#"
+                    INSERT INTO messages (
+                        id,
+                        stream_name,
+                        position,
+                        message_type,
+                        data
+                    ) VALUES ($1, $2, 0, 'X', '{}')"#,
+                    params![format!("id-{i}"), format!("account-{i}+a")],
+                )
+                .unwrap();
+            }
+            let mut seen = vec![];
+            for member in 0..3 {
+                let read = GetMessages::default()
+                    .in_category("account")
+                    .consumer_group(member, 3)
+                    .unwrap();
+                let group = read.filter.consumer_group.unwrap();
+                let messages = fetch(read, &conn).unwrap();
+                for message in &messages {
+                    assert!(group.is_assigned(&message.stream_name));
+                }
+                seen.extend(stream_names(messages));
+            }
+            assert_eq!(seen.len(), 20);
+        }
+
+        #[rstest]
+        fn global_reads_can_be_correlated() {
+            let conn = correlated_db();
+            let read = GetMessages::default().correlation("accountComponent");
+            let messages = fetch(read, &conn).unwrap();
+            let positions: Vec<_> =
+                messages.iter().map(|m| m.global_position).collect();
+            assert_eq!(positions, [1, 3, 5]);
+        }
+    }
+
+    mod time_range_reads {
+        use super::*;
+        use crate::read::{OptTimeRange, LIMIT_DEFAULT};
+        use pretty_assertions::assert_eq;
+
+        // Messages recorded at ords 100, 200, .. in alternating categories.
+        fn timed_db() -> Connection {
+            let conn = crate::rusqlite::test::new_memory_conn_with_migrations();
+            let streams = ["account-1", "billing-1", "account-2", "billing-2"];
+            for (i, stream_name) in streams.iter().enumerate() {
+                conn.execute(
+                    r#"
+                    INSERT INTO messages (
+                        id,
+                        stream_name,
+                        position,
+                        message_type,
+                        data,
+                        ord
+                    ) VALUES ($1, $2, 0, 'X', '{}', $3)"#,
+                    params![format!("id-{i}"), stream_name, (i + 1) * 100],
+                )
+                .unwrap();
+            }
+            conn
+        }
+
+        fn range(start: u64, end: u64, global_pos: u64) -> OptTimeRange {
+            OptTimeRange { start, end, global_pos }
+        }
+
+        fn between(
+            category: Option<&str>,
+            range: OptTimeRange,
+            limit: usize,
+        ) -> Cursor {
+            let category = category.map(str::to_owned);
+            Cursor {
+                read: CursorRead::Between { category, range },
+                limit,
+                filter: ReadFilter::default(),
+                direction: Direction::Forward,
+            }
+        }
+
+        fn positions(messages: Vec<Message>) -> Vec<u64> {
+            messages.iter().map(|m| m.global_position).collect()
+        }
+
+        #[rstest]
+        #[case(range(0, 1_000, 0), &[1, 2, 3, 4])]
+        #[case(range(200, 400, 0), &[2, 3])]
+        #[case(range(150, 401, 3), &[3, 4])]
+        #[case(range(500, 1_000, 0), &[])]
+        fn it_returns_messages_recorded_in_the_range(
+            #[case] range: OptTimeRange,
+            #[case] expected: &[u64],
+        ) {
+            let conn = timed_db();
+            let cursor = between(None, range, LIMIT_DEFAULT);
+            let messages = get_cursor_messages(&conn, &cursor).unwrap();
+            assert_eq!(positions(messages), expected);
+        }
+
+        #[rstest]
+        fn it_only_returns_messages_in_the_category() {
+            let conn = timed_db();
+            let cursor = between(Some("billing"), range(0, 1_000, 0), 1);
+            let messages = get_cursor_messages(&conn, &cursor).unwrap();
+            assert_eq!(positions(messages), [2]);
+        }
+
+        #[rstest]
+        #[case(0, Some(1))]
+        #[case(200, Some(2))]
+        #[case(201, Some(3))]
+        #[case(401, None)]
+        fn it_finds_the_position_at_a_time(
+            #[case] tick: u64,
+            #[case] expected: Option<u64>,
+        ) {
+            let conn = timed_db();
+            let pos = position_at_time(&conn, Tick::from_u64(tick)).unwrap();
+            assert_eq!(pos, expected);
+        }
+    }
+
     mod fn_get_latest_stream_message {
         use crate::StreamPos;
 
@@ -363,6 +863,91 @@ mod test {
         }
     }
 
+    mod fn_stream_info {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[rstest]
+        fn it_summarizes_the_stream() {
+            let conn = crate::rusqlite::test::new_memory_conn_with_migrations();
+            let rows = [
+                ("stream1", StreamPos::Sequential(0), 100),
+                ("stream2", StreamPos::Sequential(0), 200),
+                ("stream1", StreamPos::Sequential(1), 300),
+            ];
+            for (i, (stream_name, position, ord)) in
+                rows.into_iter().enumerate()
+            {
+                conn.execute(
+                    r#"
+                    INSERT INTO messages (
+                        id,
+                        stream_name,
+                        position,
+                        message_type,
+                        data,
+                        ord
+                    ) VALUES ($1, $2, $3, 'X', '{}', $4)"#,
+                    params![format!("id-{i}"), stream_name, position, ord],
+                )
+                .unwrap();
+            }
+            let info = stream_info(&conn, "stream1").unwrap();
+            assert_eq!(
+                info,
+                Some(StreamInfo {
+                    position: StreamPos::Sequential(1),
+                    count: 2,
+                    first_global: 1,
+                    last_global: 3,
+                    first_time: Tick::from_u64(100),
+                    last_time: Tick::from_u64(300),
+                })
+            );
+        }
+
+        #[rstest]
+        fn it_returns_none_if_no_stream() {
+            let conn = test_db(0);
+            assert_eq!(stream_info(&conn, "stream1").unwrap(), None);
+        }
+    }
+
+    mod fn_list_streams {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[rstest]
+        fn it_lists_streams_like_rocks() {
+            let conn = crate::rusqlite::test::new_memory_conn_with_migrations();
+            let streams = ["account", "account-2", "accountX-1", "account-1"];
+            for (i, stream_name) in streams.into_iter().enumerate() {
+                conn.execute(
+                    r#"
+                    INSERT INTO messages (
+                        id,
+                        stream_name,
+                        position,
+                        message_type,
+                        data
+                    ) VALUES ($1, $2, 0, 'X', '{}')"#,
+                    params![format!("id-{i}"), stream_name],
+                )
+                .unwrap();
+            }
+            let list = |prefix, after, limit| {
+                list_streams(&conn, prefix, after, limit).unwrap()
+            };
+            assert_eq!(
+                list("account", None, 10),
+                ["account-1", "account-2", "accountX-1", "account"]
+            );
+            assert_eq!(list("account-", Some("account-1"), 10), ["account-2"]);
+            assert_eq!(list("", None, 1), ["account-1"]);
+            assert!(list("billing", None, 10).is_empty());
+        }
+    }
+
     mod fn_get_latest_stream_position {
         use super::*;
         use pretty_assertions::assert_eq;
@@ -371,7 +956,7 @@ mod test {
             let conn = test_db(5);
             let position =
                 get_latest_stream_position(&conn, "stream1").unwrap();
-            assert_eq!(position, Some(4));
+            assert_eq!(position, Some(StreamPos::Sequential(4)));
         }
 
         #[rstest]
@@ -0,0 +1,252 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use rusqlite::{Connection, OpenFlags};
+
+use super::{
+    functions::register_functions,
+    migration::migrate,
+    position::{
+        load_position, reset_position, save_position,
+        write_raw_mess_with_position,
+    },
+    read::{get_cursor_messages, list_streams, position_at_time, stream_info},
+    write::{get_last_global_position, write_raw_mess},
+};
+use crate::{
+    clock::Tick,
+    error::{Error, Result},
+    position::ConsumerId,
+    read::Cursor,
+    storage::{Storage, StorageReader},
+    write::WriteMessage,
+    OwnedMessage, Position, StreamInfo,
+};
+
+/// How long a connection waits for another one to let go of the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs the service on a single SQLite file. The writer keeps one
+/// connection and every reader opens its own, read-only, which the
+/// write-ahead log lets read while the writer writes.
+///
+/// The file has to be on disk, since an in-memory database can't be opened
+/// a second time.
+pub struct SqliteStorage {
+    conn: Connection,
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    /// Opens the database at `path`, creating it if it's missing, and runs
+    /// the migrations it hasn't had yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let mut conn = Connection::open(&path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        migrate(&mut conn)?;
+        Ok(Self { conn, path })
+    }
+}
+
+/// A read-only connection to a [`SqliteStorage`].
+pub struct SqliteReader {
+    conn: Connection,
+}
+
+impl SqliteReader {
+    /// Opens a read-only connection to the database at `path`, which can
+    /// be written to by another process at the same time.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+            | OpenFlags::SQLITE_OPEN_URI;
+        let conn = Connection::open_with_flags(path, flags)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        register_functions(&conn)?;
+        Ok(Self { conn })
+    }
+}
+
+impl StorageReader for SqliteReader {
+    fn read(&self, cursor: &Cursor) -> Vec<Result<OwnedMessage>> {
+        match get_cursor_messages(&self.conn, cursor) {
+            Ok(messages) => {
+                messages.into_iter().map(|msg| Ok(msg.into())).collect()
+            }
+            Err(err) => vec![Err(err)],
+        }
+    }
+
+    fn position_at_time(&self, tick: Tick) -> Result<Option<u64>> {
+        position_at_time(&self.conn, tick)
+    }
+
+    fn stream_info(&self, stream: &str) -> Result<Option<StreamInfo>> {
+        stream_info(&self.conn, stream)
+    }
+
+    fn load_position(&self, consumer: &ConsumerId) -> Result<Option<u64>> {
+        load_position(&self.conn, consumer)
+    }
+
+    fn list_streams(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        list_streams(&self.conn, prefix, after, limit)
+    }
+}
+
+impl Storage for SqliteStorage {
+    type Reader = SqliteReader;
+
+    fn reader(&self) -> Result<Self::Reader> {
+        SqliteReader::open(&self.path)
+    }
+
+    fn write(
+        &mut self,
+        message: WriteMessage<'_>,
+        checkpoint: Option<(&ConsumerId, u64)>,
+    ) -> Result<Position> {
+        match checkpoint {
+            None => write_raw_mess(&self.conn, message),
+            Some((consumer, global_pos)) => write_raw_mess_with_position(
+                &mut self.conn,
+                message,
+                consumer,
+                global_pos,
+            ),
+        }
+    }
+
+    fn save_position(
+        &mut self,
+        consumer: &ConsumerId,
+        global_pos: u64,
+    ) -> Result<()> {
+        save_position(&self.conn, consumer, global_pos)
+    }
+
+    fn reset_position(&mut self, consumer: &ConsumerId) -> Result<()> {
+        reset_position(&self.conn, consumer)
+    }
+
+    fn last_global_position(&self) -> Result<u64> {
+        get_last_global_position(&self.conn)
+    }
+
+    fn backup(&self, path: &Path) -> Result<()> {
+        let path = path.to_str().ok_or_else(|| {
+            Error::Other(format!("{} is not UTF-8", path.display()))
+        })?;
+        self.conn.execute("VACUUM INTO ?1", [path])?;
+        Ok(())
+    }
+
+    fn close(self) -> Result<()> {
+        self.conn.close().map_err(|(_, err)| err.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert2::assert;
+    use ident::Id;
+
+    use super::*;
+    use crate::{read::ReadFilter, write::ExpectedVersion};
+
+    fn new_storage() -> SqliteStorage {
+        let path = std::env::temp_dir().join(format!("{}.db", Id::new()));
+        SqliteStorage::open(path).unwrap()
+    }
+
+    fn message(metadata: &'static [u8]) -> WriteMessage<'static> {
+        WriteMessage {
+            id: Id::new(),
+            stream_name: "account-1".into(),
+            message_type: "Opened".into(),
+            data: br#"{"a":1}"#[..].into(),
+            metadata: metadata.into(),
+            expected_version: ExpectedVersion::Any,
+        }
+    }
+
+    #[test]
+    fn readers_see_what_was_written() {
+        let mut storage = new_storage();
+        let reader = storage.reader().unwrap();
+        storage.write(message(b""), None).unwrap();
+        let pos = storage.write(message(b""), None).unwrap();
+        assert!(storage.last_global_position().unwrap() == pos.global);
+
+        let cursor = Cursor::stream("account-1".into(), None, 10);
+        let messages: Vec<_> =
+            reader.read(&cursor).into_iter().map(Result::unwrap).collect();
+        assert!(messages.len() == 2);
+        assert!(messages[1].data == br#"{"a":1}"#);
+        assert!(messages[1].metadata.is_none());
+    }
+
+    #[test]
+    fn json_metadata_can_be_filtered_on() {
+        let mut storage = new_storage();
+        let reader = storage.reader().unwrap();
+        let replies = br#"{"correlationStreamName":"replies-1"}"#;
+        storage.write(message(replies), None).unwrap();
+        storage.write(message(b"{}"), None).unwrap();
+
+        let filter = ReadFilter {
+            correlation: Some("replies".into()),
+            ..ReadFilter::default()
+        };
+        let cursor = Cursor::global(None, 0, 10).with_filter(filter);
+        let messages = reader.read(&cursor);
+        assert!(messages.len() == 1);
+        assert!(messages[0].as_ref().unwrap().global_position == 1);
+    }
+
+    #[test]
+    fn positions_are_written_with_the_message() {
+        let mut storage = new_storage();
+        let reader = storage.reader().unwrap();
+        let consumer = ConsumerId::new("projector");
+        storage.write(message(b""), Some((&consumer, 7))).unwrap();
+        assert!(reader.load_position(&consumer).unwrap() == Some(7));
+        storage.reset_position(&consumer).unwrap();
+        assert!(reader.load_position(&consumer).unwrap().is_none());
+    }
+
+    #[test]
+    fn readers_open_next_to_the_writer() {
+        let mut storage = new_storage();
+        storage.write(message(b""), None).unwrap();
+        let reader = SqliteReader::open(&storage.path).unwrap();
+        assert!(reader.stream_info("account-1").unwrap().unwrap().count == 1);
+        storage.write(message(b""), None).unwrap();
+        assert!(reader.stream_info("account-1").unwrap().unwrap().count == 2);
+        assert!(reader.conn.execute("DELETE FROM messages", []).is_err());
+    }
+
+    #[test]
+    fn backups_open_like_the_original() {
+        let mut storage = new_storage();
+        storage.write(message(b""), None).unwrap();
+        let path = std::env::temp_dir().join(format!("{}.db", Id::new()));
+        storage.backup(&path).unwrap();
+        assert!(storage.backup(&path).is_err());
+
+        let copy = SqliteStorage::open(&path).unwrap();
+        assert!(copy.last_global_position().unwrap() == 1);
+        let info = copy.reader().unwrap().stream_info("account-1").unwrap();
+        assert!(info.unwrap().count == 1);
+    }
+}
@@ -1,15 +1,19 @@
+use std::borrow::Cow;
+
 use ident::Id;
-use rusqlite::{params, Connection};
+use rusqlite::{params, types::Value, Connection};
 use serde::Serialize;
 
 // const ROWS_PER_BULK_INSERT: usize = 100;
 
 use crate::{
     error::{Error, Result},
-    write::WriteMessageOld,
+    write::{ExpectedVersion, WriteMessage, WriteMessageOld},
     Position, StreamPos,
 };
 
+use super::read::get_latest_stream_position;
+
 pub fn write_mess<D: Serialize, M: Serialize>(
     conn: &Connection,
     msg: WriteMessageOld<D, M>,
@@ -21,7 +25,7 @@ pub fn write_mess<D: Serialize, M: Serialize>(
         &msg.message_type,
         msg.data,
         msg.metadata,
-        msg.expected_stream_position,
+        msg.expected_version,
     )
 }
 
@@ -71,17 +75,104 @@ pub fn write_message(
     msg_type: &str,
     data: impl Serialize,
     meta: Option<impl Serialize>,
-    expected_stream_position: Option<StreamPos>,
+    expected_version: ExpectedVersion,
 ) -> Result<Position> {
-    let next_position = expected_stream_position
-        .map(|x| x.next())
-        .unwrap_or(StreamPos::Sequential(0));
-    let msg_id_str = msg_id.to_string();
+    let msg_id = msg_id.to_string();
+    if let Some(position) = written_before(conn, &msg_id, stream_name)? {
+        return Ok(position);
+    }
     let data = serde_json::to_string(&data)?;
     let meta = match meta {
         Some(m) => Some(serde_json::to_string(&m)?),
         None => None,
     };
+    insert_message(
+        conn,
+        &msg_id,
+        stream_name,
+        msg_type,
+        Value::Text(data),
+        meta.map(Value::Text),
+        expected_version,
+    )
+}
+
+/// Writes a message whose data and metadata are already encoded, as the
+/// service receives them. They are stored as text when they are UTF-8, which
+/// the JSON functions used by reads expect, and as blobs otherwise. Empty
+/// metadata is stored as `NULL`.
+pub fn write_raw_mess(
+    conn: &Connection,
+    msg: WriteMessage<'_>,
+) -> Result<Position> {
+    let msg_id = msg.id.to_string();
+    if let Some(position) = written_before(conn, &msg_id, &msg.stream_name)? {
+        return Ok(position);
+    }
+    let meta = (!msg.metadata.is_empty()).then(|| encoded(msg.metadata));
+    insert_message(
+        conn,
+        &msg_id,
+        &msg.stream_name,
+        &msg.message_type,
+        encoded(msg.data),
+        meta,
+        msg.expected_version,
+    )
+}
+
+/// Returns the position of the message with the same id, if one was written
+/// before, so a retried write returns it instead of writing again. Fails if
+/// it went to another stream.
+fn written_before(
+    conn: &Connection,
+    msg_id: &str,
+    stream_name: &str,
+) -> Result<Option<Position>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT global_position, position, stream_name FROM messages
+        WHERE id = ?",
+    )?;
+    let mut rows = stmt.query([msg_id])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    let (global, position, stream): (i64, i64, String) =
+        (row.get(0)?, row.get(1)?, row.get(2)?);
+    if stream != stream_name {
+        return Err(Error::IdInUse { id: msg_id.to_owned(), stream });
+    }
+    Ok(Some(Position::new(global as u64, StreamPos::decode(position as u64))))
+}
+
+fn encoded(bytes: Cow<'_, [u8]>) -> Value {
+    match String::from_utf8(bytes.into_owned()) {
+        Ok(text) => Value::Text(text),
+        Err(err) => Value::Blob(err.into_bytes()),
+    }
+}
+
+/// Returns the global position of the last message, or 0 if there is none.
+pub fn get_last_global_position(conn: &Connection) -> Result<u64> {
+    let mut stmt =
+        conn.prepare_cached("SELECT MAX(global_position) FROM messages")?;
+    let pos: Option<i64> = stmt.query_row([], |row| row.get(0))?;
+    Ok(pos.map_or(0, |pos| pos as u64))
+}
+
+/// Writes a message with any id, such as the UUIDs which message-db clients
+/// give.
+pub(crate) fn insert_message(
+    conn: &Connection,
+    msg_id: &str,
+    stream_name: &str,
+    msg_type: &str,
+    data: Value,
+    meta: Option<Value>,
+    expected_version: ExpectedVersion,
+) -> Result<Position> {
+    let current = get_latest_stream_position(conn, stream_name)?;
+    let next_position = expected_version.next_position(stream_name, current)?;
 
     let mut stmt = conn.prepare_cached(
         r#"
@@ -97,39 +188,46 @@ pub fn write_message(
     )?;
     let (global_position, position): (i64, i64) = stmt
         .query_row(
-            params![
-                msg_id_str,
-                stream_name,
-                next_position,
-                msg_type,
-                data,
-                meta
-            ],
+            params![msg_id, stream_name, next_position, msg_type, data, meta],
             |r| Ok((r.get(0)?, r.get(1)?)),
         )
-        .map_err(|err| match err {
-            rusqlite::Error::SqliteFailure(e, Some(ref msg)) => {
-                match (e.code, msg.as_str()) {
-                    (
-                        rusqlite::ErrorCode::ConstraintViolation,
-                        "stream position mismatch",
-                    ) => Error::WrongStreamPosition {
-                        stream: stream_name.into(),
-                        expected: expected_stream_position.map(|x| x.encode()),
-                        got: None,
-                    },
-                    _ => err.into(),
-                }
-            }
-            _ => err.into(),
+        .map_err(|err| {
+            insert_error(conn, err, stream_name, expected_version)
         })?;
 
     Ok(Position::new(
         global_position as u64,
-        StreamPos::Sequential(position.unsigned_abs()),
+        StreamPos::decode(position as u64),
     ))
 }
 
+/// Maps a failed insert to an error. When `check_stream_position` fired,
+/// another writer got to the stream between reading its position and
+/// inserting, so the error reports the position that writer left it at.
+fn insert_error(
+    conn: &Connection,
+    err: rusqlite::Error,
+    stream_name: &str,
+    expected_version: ExpectedVersion,
+) -> Error {
+    match err {
+        rusqlite::Error::SqliteFailure(e, Some(ref msg))
+            if e.code == rusqlite::ErrorCode::ConstraintViolation
+                && msg == "stream position mismatch" =>
+        {
+            match get_latest_stream_position(conn, stream_name) {
+                Ok(got) => Error::WrongStreamPosition {
+                    stream: stream_name.into(),
+                    expected: expected_version,
+                    got,
+                },
+                Err(err) => err,
+            }
+        }
+        _ => err.into(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -196,7 +294,7 @@ mod test {
                 "Donked",
                 data,
                 Some(&meta),
-                None,
+                ExpectedVersion::NoStream,
             )
             .unwrap();
             assert_eq!(
@@ -233,15 +331,99 @@ mod test {
                 "Donked",
                 json!({ "one": 1, "two": 2 }),
                 None::<()>,
-                Some(StreamPos::Sequential(77)),
+                ExpectedVersion::Exact(StreamPos::Sequential(77)),
             );
             let err = res.unwrap_err();
-            let Error::WrongStreamPosition { stream, expected: _, got: _ } =
-                err
+            let Error::WrongStreamPosition { stream, expected, got } = err
             else {
                 panic!("wrong error");
             };
             assert!(stream == "thing-xyz123.twothr");
+            assert!(
+                expected == ExpectedVersion::Exact(StreamPos::Sequential(77))
+            );
+            assert!(got.is_none());
+        }
+
+        #[rstest]
+        fn it_reports_the_decoded_current_position(test_db: Connection) {
+            for _ in 0..3 {
+                write_message(
+                    &test_db,
+                    Id::new(),
+                    "stream1",
+                    "X",
+                    "data",
+                    None::<()>,
+                    ExpectedVersion::Any,
+                )
+                .unwrap();
+            }
+            let err = write_message(
+                &test_db,
+                Id::new(),
+                "stream1",
+                "X",
+                "data",
+                None::<()>,
+                ExpectedVersion::Exact(StreamPos::Sequential(0)),
+            )
+            .unwrap_err();
+            assert!(let Error::WrongStreamPosition {
+                got: Some(StreamPos::Sequential(2)),
+                ..
+            } = err);
+            assert!(err.messages_behind() == Some(2));
+        }
+
+        #[rstest]
+        fn a_racing_write_reports_the_position_it_left(test_db: Connection) {
+            let insert = |position: StreamPos| {
+                test_db.execute(
+                    r#"
+                    INSERT INTO messages (
+                        id,
+                        stream_name,
+                        position,
+                        message_type,
+                        data
+                    ) VALUES (?1, 'stream1', ?2, 'X', '{}')"#,
+                    params![Id::new().to_string(), position],
+                )
+            };
+            insert(StreamPos::Sequential(0)).unwrap();
+            insert(StreamPos::Sequential(1)).unwrap();
+            // As if this writer read the stream before the second insert.
+            let err = insert(StreamPos::Sequential(1)).unwrap_err();
+            let expected = ExpectedVersion::Exact(StreamPos::Sequential(0));
+            let err = insert_error(&test_db, err, "stream1", expected);
+            assert!(let Error::WrongStreamPosition {
+                got: Some(StreamPos::Sequential(1)),
+                ..
+            } = err);
+            assert!(err.messages_behind() == Some(1));
+        }
+
+        #[rstest]
+        fn it_appends_to_any_stream(test_db: Connection) {
+            let write = |expected_version| {
+                write_message(
+                    &test_db,
+                    Id::new(),
+                    "audit-1",
+                    "X",
+                    "data",
+                    None::<()>,
+                    expected_version,
+                )
+            };
+            write(ExpectedVersion::Any).unwrap();
+            let pos = write(ExpectedVersion::Any).unwrap();
+            assert!(pos.stream == StreamPos::Sequential(1));
+            let pos = write(ExpectedVersion::StreamExists).unwrap();
+            assert!(pos.stream == StreamPos::Sequential(2));
+            let res = write(ExpectedVersion::NoStream);
+            assert!(let Err(Error::WrongStreamPosition { .. }) = res);
         }
 
         #[rstest]
@@ -253,7 +435,7 @@ mod test {
                 "X",
                 "data",
                 None::<()>,
-                None,
+                ExpectedVersion::NoStream,
             )
             .unwrap();
             let rec: MessageRow = test_db
@@ -274,7 +456,7 @@ mod test {
                 "X",
                 "data",
                 Some(&json!({ "some": "meta" })),
-                None,
+                ExpectedVersion::NoStream,
             )
             .unwrap();
             let rec: MessageRow = test_db
@@ -316,7 +498,7 @@ mod testprops {
                 &msg_type,
                 data,
                 Some(&meta),
-                None,
+                ExpectedVersion::NoStream,
             )
             .unwrap();
             assert!(pos == Position { global: 1, stream: StreamPos::Sequential(0) });
@@ -0,0 +1,90 @@
+//! The storage the service actor runs on.
+//!
+//! The actor owns a [`Storage`] and is the only one to write to it. Reads are
+//! answered by a pool of threads which each get their own [`StorageReader`],
+//! so a backend whose connections can't be shared still reads in parallel.
+//! With the `rocksdb` feature, which is on by default, RocksDB implements
+//! both with `rocks::storage::RocksStorage`, and with the `rusqlite` feature,
+//! SQLite with `rusqlite::storage::SqliteStorage`.
+
+use std::path::Path;
+
+use crate::{
+    clock::Tick, error::Result, position::ConsumerId, read::Cursor,
+    write::WriteMessage, OwnedMessage, Position, StreamInfo,
+};
+
+/// Answers the service's reads. A reader only ever sees what the writer has
+/// committed.
+pub trait StorageReader: Send + 'static {
+    /// Returns a page of the read the cursor describes. Reading stops at the
+    /// first error, which is the last item.
+    fn read(&self, cursor: &Cursor) -> Vec<Result<OwnedMessage>>;
+
+    /// Returns the global position of the first message recorded at or after
+    /// `tick`, or `None` if every message is older.
+    fn position_at_time(&self, tick: Tick) -> Result<Option<u64>>;
+
+    /// Summarizes a stream, or returns `None` if it has no messages.
+    fn stream_info(&self, stream: &str) -> Result<Option<StreamInfo>>;
+
+    /// Returns the consumer's last saved global position, if it has one.
+    fn load_position(&self, consumer: &ConsumerId) -> Result<Option<u64>>;
+
+    /// Returns up to `limit` names of streams starting with `prefix`, after
+    /// the stream named `after` if given. Names are ordered the same way on
+    /// every backend, so the last one returned can be passed as `after` to
+    /// list the rest.
+    fn list_streams(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>>;
+}
+
+/// The writable side of a backend, which also hands out its readers.
+pub trait Storage: Send + 'static {
+    type Reader: StorageReader;
+
+    /// Opens another reader on the same data.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the backend could not open another connection.
+    fn reader(&self) -> Result<Self::Reader>;
+
+    /// Writes the message, along with the consumer's position if given, so
+    /// either both are stored or neither is.
+    fn write(
+        &mut self,
+        message: WriteMessage<'_>,
+        checkpoint: Option<(&ConsumerId, u64)>,
+    ) -> Result<Position>;
+
+    fn save_position(
+        &mut self,
+        consumer: &ConsumerId,
+        global_pos: u64,
+    ) -> Result<()>;
+
+    /// Forgets the consumer's position so it starts again from the beginning.
+    fn reset_position(&mut self, consumer: &ConsumerId) -> Result<()>;
+
+    /// Returns the global position of the last message, or 0 if there is
+    /// none.
+    fn last_global_position(&self) -> Result<u64>;
+
+    /// Writes a consistent copy of the store to `path`, which must not exist
+    /// yet. The copy opens like the original.
+    fn backup(&self, path: &Path) -> Result<()>;
+
+    /// Records the backend's own stats, such as RocksDB's memtable size, as
+    /// [`crate::metrics`]. The actor calls it every
+    /// [`crate::metrics::SAMPLE_INTERVAL`].
+    fn record_metrics(&self) {}
+
+    /// Syncs what was written to disk and closes the storage. The actor
+    /// calls it once every reader has been dropped.
+    fn close(self) -> Result<()>;
+}
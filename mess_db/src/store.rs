@@ -0,0 +1,237 @@
+//! The operations every way of reaching a store shares.
+
+use std::future::Future;
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    error::Result,
+    position::{Checkpoint, ConsumerId},
+    read::IntoCursor,
+    svc::{ActorHandle, Page, SubscribeFilter, SubscriptionEvent},
+    write::WriteMessage,
+    OwnedMessage, Position, StreamInfo,
+};
+
+/// A message store, embedded through an [`ActorHandle`] or served by another
+/// process, such as over gRPC with the `mess_client` crate or over a local
+/// socket with [`IpcClient`](crate::ipc::IpcClient). Code written against it
+/// runs on any of them.
+pub trait MessageStore: Clone + Send + Sync + 'static {
+    /// Writes the message if its stream is as it expects.
+    fn put_message(
+        &self,
+        wm: WriteMessage<'_>,
+    ) -> impl Future<Output = Result<Position>> + Send;
+
+    /// Reads one page of messages.
+    fn fetch_messages(
+        &self,
+        read: impl IntoCursor + Send,
+    ) -> impl Future<Output = Result<Vec<Result<OwnedMessage>>>> + Send;
+
+    /// Reads one page of messages along with the cursor for the next page.
+    fn fetch_page(
+        &self,
+        read: impl IntoCursor + Send,
+    ) -> impl Future<Output = Result<Page>> + Send;
+
+    /// Summarizes the stream, or returns `None` if it has no messages.
+    fn stream_info(
+        &self,
+        stream: &str,
+    ) -> impl Future<Output = Result<Option<StreamInfo>>> + Send;
+
+    /// Streams every message the read matches, page after page. The stream
+    /// ends after the first error.
+    fn stream_messages(
+        &self,
+        read: impl IntoCursor,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static;
+
+    /// Streams the messages matching `filter` from `from_global` on, first
+    /// those already written and then new ones. The stream ends after the
+    /// first error.
+    fn subscribe(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static;
+
+    /// Subscribes like [`MessageStore::subscribe`], also telling how far the
+    /// subscription has read past messages the filter left out. Stores which
+    /// can't tell only send the messages.
+    fn subscribe_with_progress(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<SubscriptionEvent>> + Send + 'static {
+        self.subscribe(from_global, filter)
+            .map(|msg| msg.map(SubscriptionEvent::Message))
+    }
+}
+
+/// Keeps consumers' positions, so they continue where they stopped.
+pub trait PositionStore: Clone + Send + Sync + 'static {
+    /// Returns the consumer's last saved global position, if it has one.
+    fn load_position(
+        &self,
+        consumer: &ConsumerId,
+    ) -> impl Future<Output = Result<Option<u64>>> + Send;
+
+    fn save_position(
+        &self,
+        consumer: &ConsumerId,
+        global_pos: u64,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Loads the consumer's position into a [`Checkpoint`] which is due for
+    /// saving every `save_interval` messages handled.
+    fn load_checkpoint(
+        &self,
+        consumer: ConsumerId,
+        save_interval: u64,
+    ) -> impl Future<Output = Result<Checkpoint>> + Send {
+        async move {
+            let saved = self.load_position(&consumer).await?;
+            Ok(Checkpoint::new(consumer, saved, save_interval))
+        }
+    }
+
+    /// Records that the message at `global_pos` was handled, saving the
+    /// position if the checkpoint's interval is due. Returns whether it
+    /// saved.
+    fn checkpoint(
+        &self,
+        checkpoint: &mut Checkpoint,
+        global_pos: u64,
+    ) -> impl Future<Output = Result<bool>> + Send {
+        async move {
+            let Some(pos) = checkpoint.handled(global_pos) else {
+                return Ok(false);
+            };
+            self.save_position(checkpoint.consumer(), pos).await?;
+            checkpoint.saved(pos);
+            Ok(true)
+        }
+    }
+}
+
+impl MessageStore for ActorHandle {
+    fn put_message(
+        &self,
+        wm: WriteMessage<'_>,
+    ) -> impl Future<Output = Result<Position>> + Send {
+        Self::put_message(self, wm)
+    }
+
+    fn fetch_messages(
+        &self,
+        read: impl IntoCursor + Send,
+    ) -> impl Future<Output = Result<Vec<Result<OwnedMessage>>>> + Send {
+        Self::fetch_messages(self, read)
+    }
+
+    fn fetch_page(
+        &self,
+        read: impl IntoCursor + Send,
+    ) -> impl Future<Output = Result<Page>> + Send {
+        Self::fetch_page(self, read)
+    }
+
+    fn stream_info(
+        &self,
+        stream: &str,
+    ) -> impl Future<Output = Result<Option<StreamInfo>>> + Send {
+        Self::stream_info(self, stream)
+    }
+
+    fn stream_messages(
+        &self,
+        read: impl IntoCursor,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        Self::stream_messages(self, read)
+    }
+
+    fn subscribe(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        Self::subscribe(self, from_global, filter)
+    }
+
+    fn subscribe_with_progress(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<SubscriptionEvent>> + Send + 'static {
+        Self::subscribe_with_progress(self, from_global, filter)
+    }
+}
+
+impl PositionStore for ActorHandle {
+    fn load_position(
+        &self,
+        consumer: &ConsumerId,
+    ) -> impl Future<Output = Result<Option<u64>>> + Send {
+        Self::load_position(self, consumer)
+    }
+
+    fn save_position(
+        &self,
+        consumer: &ConsumerId,
+        global_pos: u64,
+    ) -> impl Future<Output = Result<()>> + Send {
+        Self::save_position(self, consumer, global_pos)
+    }
+}
+
+#[cfg(all(test, feature = "rocksdb"))]
+mod test {
+    use super::*;
+    use crate::{
+        read::GetMessages,
+        rocks::{db::DB, storage::RocksStorage},
+        write::ExpectedVersion,
+        StreamPos,
+    };
+    use assert2::assert;
+    use futures::StreamExt;
+    use ident::Id;
+
+    async fn append_twice(store: &impl MessageStore, stream: &str) {
+        for n in 0..2 {
+            store
+                .put_message(WriteMessage {
+                    id: Id::new(),
+                    stream_name: stream.into(),
+                    message_type: "Counted".into(),
+                    data: vec![n].into(),
+                    metadata: vec![].into(),
+                    expected_version: ExpectedVersion::Any,
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn the_embedded_handle_is_a_store() {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        let handle: ActorHandle =
+            ActorHandle::new(RocksStorage::new(DB::new(path).unwrap()))
+                .unwrap();
+        append_twice(&handle, "count-1").await;
+
+        let info = MessageStore::stream_info(&handle, "count-1").await.unwrap();
+        assert!(info.unwrap().position == StreamPos::Sequential(1));
+        let read = GetMessages::default().in_stream("count-1").with_limit(1);
+        let streamed: Vec<_> =
+            MessageStore::stream_messages(&handle, read).collect().await;
+        assert!(streamed.len() == 2);
+        let filter = SubscribeFilter::in_stream("count-1");
+        let mut sub = Box::pin(MessageStore::subscribe(&handle, 0, filter));
+        assert!(sub.next().await.unwrap().unwrap().data == [0]);
+    }
+}
@@ -1,66 +1,131 @@
-use tokio::sync::{mpsc, oneshot};
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures::{Stream, StreamExt};
+use metrics::{counter, gauge, histogram};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc, oneshot, watch, Mutex,
+    },
+    task::JoinHandle,
+    time::MissedTickBehavior,
+};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error};
 
 use crate::{
+    clock::Tick,
     error::{Error, Result},
-    read::{GetMessages, OptGlobalPos, OptStream, OptStreamPos, Unset},
-    rocks::{db::DB, read::Fetch, write::WriteSerializer},
+    metrics::{
+        APPENDS, APPEND_SECONDS, CONFLICTS, CONSUMER_LAG, QUEUED,
+        QUEUE_SECONDS, READ_BATCH, READ_SECONDS, SAMPLE_INTERVAL,
+    },
+    position::{Checkpoint, ConsumerId},
+    read::{self, Cursor, IntoCursor, ReadFilter},
+    storage::{Storage, StorageReader},
     write::{OwnedWriteMessage, WriteMessage},
-    Message, OwnedMessage, Position, StreamPos,
+    Message, OwnedMessage, Position, StreamInfo,
 };
 
-#[derive(Debug)]
+/// How many fetched chunks may wait in a message stream's channel before the
+/// feeder stops asking the actor for more.
+const STREAM_CHUNK_BUFFER: usize = 2;
+
+/// How many written messages a subscriber may fall behind before it has to
+/// catch up from storage instead.
+const LIVE_BUFFER: usize = 1024;
+
+/// How many reader threads [`ActorHandle::new`] starts.
+pub const DEFAULT_READERS: usize = 4;
+
+#[derive(Clone, Debug)]
 pub enum RequestBody {
-    GetGlobalMessages {
-        stream: Option<String>,
+    /// Reads a page of messages, answered with [`ResponseBody::Messages`]
+    /// and the cursor for the next page. Build it from any [`IntoCursor`].
+    Read(Cursor),
+    Write(OwnedWriteMessage),
+    /// Writes the message and saves the consumer's position in the same
+    /// batch.
+    WriteWithPosition {
+        message: OwnedWriteMessage,
+        consumer: ConsumerId,
         global_pos: u64,
-        limit: usize,
     },
-    GetStreamMessages {
-        stream: String,
-        stream_pos: Option<StreamPos>,
+    Subscribe,
+    LoadPosition(ConsumerId),
+    SavePosition {
+        consumer: ConsumerId,
+        global_pos: u64,
+    },
+    ResetPosition(ConsumerId),
+    /// Looks up the global position of the first message recorded at or
+    /// after the tick, answered with [`ResponseBody::Position`].
+    PositionAtTime(Tick),
+    StreamInfo(String),
+    /// Lists stream names, answered with [`ResponseBody::Streams`].
+    ListStreams {
+        prefix: String,
+        after: Option<String>,
         limit: usize,
     },
-    Write(OwnedWriteMessage),
+    /// Copies the store to a path which doesn't exist yet. The writer makes
+    /// the copy, so it holds every write answered before it.
+    Backup(PathBuf),
 }
 
-impl From<GetMessages<Unset, OptGlobalPos, Unset>> for RequestBody {
-    fn from(val: GetMessages<Unset, OptGlobalPos, Unset>) -> Self {
-        RequestBody::GetGlobalMessages {
-            stream: None,
-            global_pos: val.start_global_position.0,
-            limit: val.limit,
-        }
+impl From<Cursor> for RequestBody {
+    fn from(cursor: Cursor) -> Self {
+        Self::Read(cursor)
     }
 }
 
-impl From<GetMessages<OptStream<'_>, OptGlobalPos, Unset>> for RequestBody {
-    fn from(val: GetMessages<OptStream, OptGlobalPos, Unset>) -> Self {
-        RequestBody::GetGlobalMessages {
-            stream: Some(val.stream.0.to_string()),
-            global_pos: val.start_global_position.0,
-            limit: val.limit,
+impl RequestBody {
+    /// Whether the request only reads, so it can be answered by the reader
+    /// pool instead of waiting behind writes.
+    #[must_use]
+    pub const fn is_read(&self) -> bool {
+        match self {
+            Self::Read(_)
+            | Self::LoadPosition(_)
+            | Self::PositionAtTime(_)
+            | Self::StreamInfo(_)
+            | Self::ListStreams { .. } => true,
+            // Subscribing reads the head, which has to line up with the
+            // writer's broadcasts.
+            Self::Write(_)
+            | Self::WriteWithPosition { .. }
+            | Self::Subscribe
+            | Self::SavePosition { .. }
+            | Self::ResetPosition(_)
+            | Self::Backup(_) => false,
         }
     }
-}
 
-impl From<GetMessages<OptStream<'_>, Unset, Unset>> for RequestBody {
-    fn from(val: GetMessages<OptStream, Unset, Unset>) -> Self {
-        RequestBody::GetStreamMessages {
-            stream: val.stream.0.to_string(),
-            stream_pos: None,
-            limit: val.limit,
+    /// The lane the request waits in, as labelled in the metrics.
+    const fn lane(&self) -> &'static str {
+        if self.is_read() {
+            "reads"
+        } else {
+            "writes"
         }
     }
-}
 
-impl From<GetMessages<OptStream<'_>, Unset, OptStreamPos>> for RequestBody {
-    fn from(val: GetMessages<OptStream, Unset, OptStreamPos>) -> Self {
-        RequestBody::GetStreamMessages {
-            stream: val.stream.0.to_string(),
-            stream_pos: Some(val.start_stream_position.0),
-            limit: val.limit,
+    /// What kind of read the request is, as labelled in the metrics.
+    const fn read_kind(&self) -> &'static str {
+        match self {
+            Self::Read(_) => "read",
+            Self::LoadPosition(_) => "load_position",
+            Self::PositionAtTime(_) => "position_at_time",
+            Self::StreamInfo(_) => "stream_info",
+            Self::ListStreams { .. } => "list_streams",
+            _ => "other",
         }
     }
 }
@@ -69,6 +134,8 @@ impl From<GetMessages<OptStream<'_>, Unset, OptStreamPos>> for RequestBody {
 pub struct Request {
     pub(crate) body: RequestBody,
     pub(crate) response_chan: oneshot::Sender<Response>,
+    // When the request was sent, for timing how long it waited.
+    queued: Instant,
 }
 
 impl Request {
@@ -76,7 +143,7 @@ impl Request {
         body: RequestBody,
         response_chan: oneshot::Sender<Response>,
     ) -> Request {
-        Request { body, response_chan }
+        Request { body, response_chan, queued: Instant::now() }
     }
 }
 
@@ -88,9 +155,49 @@ pub type DynOwnedMessageIter =
 
 #[derive(Debug)]
 pub enum ResponseBody {
-    Messages { messages: Vec<Result<OwnedMessage>> },
-    Write { pos: Result<Position> },
-    Err,
+    Messages {
+        messages: Vec<Result<OwnedMessage>>,
+        cursor: Cursor,
+    },
+    Write {
+        pos: Result<Position>,
+    },
+    /// The consumer's stored position after the request.
+    Position {
+        pos: Result<Option<u64>>,
+    },
+    /// Every message written after `head` will be sent on `live`.
+    Subscribed {
+        head: Result<u64>,
+        live: broadcast::Receiver<Arc<OwnedMessage>>,
+    },
+    /// The stream's summary, or `None` if it has no messages.
+    StreamInfo {
+        info: Result<Option<StreamInfo>>,
+    },
+    Streams {
+        names: Result<Vec<String>>,
+    },
+    BackedUp {
+        res: Result<()>,
+    },
+    /// The request failed before it could be answered, such as when it was
+    /// rejected during shutdown or its handler panicked.
+    Err(Error),
+}
+
+impl ResponseBody {
+    /// Returns the error to report for a response which does not answer the
+    /// request it was sent for.
+    fn into_error(self) -> Error {
+        match self {
+            Self::Err(err) => err,
+            resp => {
+                error!(?resp, "unexpected service response body");
+                Error::SvcResponse
+            }
+        }
+    }
 }
 
 // impl std::fmt::Debug for ResponseBody {
@@ -107,6 +214,13 @@ pub enum ResponseBody {
 //     }
 // }
 
+/// A page of messages and the cursor which continues after it.
+#[derive(Debug)]
+pub struct Page {
+    pub messages: Vec<Result<OwnedMessage>>,
+    pub cursor: Cursor,
+}
+
 #[derive(Debug)]
 pub struct Response {
     pub body: ResponseBody,
@@ -124,7 +238,7 @@ const _: () = {
     //         message_type: Cow::Borrowed(""),
     //         data: Cow::Borrowed(U),
     //         metadata: Cow::Borrowed(U),
-    //         expected_stream_position: None,
+    //         expected_version: ExpectedVersion::NoStream,
     //     }),
     // };
     // assert!(is_sync(&REQ));
@@ -136,129 +250,1916 @@ const _: () = {
     // assert!(is_sync(&RES));
 };
 
-pub struct Actor {
+pub struct Actor<St> {
     inbox: mpsc::Receiver<Request>,
-    // Only the actor can touch the DB.
-    db: DB,
-    ser: WriteSerializer,
+    // Only the actor writes; the reader pool has readers of its own.
+    storage: St,
     token: CancellationToken,
+    // Messages are broadcast to subscribers right after they are written.
+    live: broadcast::Sender<Arc<OwnedMessage>>,
+    // The last written global position, for waiting on writes to land.
+    written: watch::Sender<u64>,
+    // The positions consumers saved, for reporting how far behind they are.
+    consumers: HashMap<ConsumerId, u64>,
 }
 
-impl Actor {
-    async fn handle_req(&mut self, req: Request) -> Result<()> {
-        if self.token.is_cancelled() {
-            return Err(Error::Cancelled);
-        }
-        let resp = match req.body {
-            RequestBody::GetGlobalMessages { stream, global_pos, limit } => {
-                let opts = GetMessages::default()
-                    .from_global(global_pos)
-                    .with_limit(limit);
-                let messages = Fetch::<OptGlobalPos>::fetch(&self.db, opts);
-                let messages: Vec<_> =
-                    messages.map(|res| res.map(|msg| msg.into())).collect();
-                Response { body: ResponseBody::Messages { messages } }
-            }
-            RequestBody::GetStreamMessages { stream, stream_pos, limit } => {
-                let opts =
-                    GetMessages::default().in_stream(&stream).with_limit(limit);
-                let messages = Fetch::<OptStream>::fetch(&self.db, opts);
-                let messages: Vec<_> =
-                    messages.map(|res| res.map(|msg| msg.into())).collect();
-                Response { body: ResponseBody::Messages { messages } }
-            }
+impl<St: Storage> Actor<St> {
+    fn handle_req(&mut self, body: RequestBody) -> Response {
+        match body {
             RequestBody::Write(message) => {
-                let pos = crate::rocks::write::write_mess(
-                    &self.db,
-                    message.into(),
-                    &mut self.ser,
-                );
+                let pos = self.write(message, None);
                 Response { body: ResponseBody::Write { pos } }
             }
-        };
-        debug!(?resp, "responding with");
-        let _ = req.response_chan.send(resp);
-        Ok(())
+            RequestBody::WriteWithPosition {
+                message,
+                consumer,
+                global_pos,
+            } => {
+                let pos = self.write(message, Some((&consumer, global_pos)));
+                if pos.is_ok() {
+                    self.saved(consumer, global_pos);
+                }
+                Response { body: ResponseBody::Write { pos } }
+            }
+            RequestBody::SavePosition { consumer, global_pos } => {
+                let pos = self
+                    .storage
+                    .save_position(&consumer, global_pos)
+                    .map(|()| Some(global_pos));
+                if pos.is_ok() {
+                    self.saved(consumer, global_pos);
+                }
+                Response { body: ResponseBody::Position { pos } }
+            }
+            RequestBody::ResetPosition(consumer) => {
+                let pos = self.storage.reset_position(&consumer).map(|()| None);
+                if pos.is_ok() {
+                    self.saved(consumer, 0);
+                }
+                Response { body: ResponseBody::Position { pos } }
+            }
+            RequestBody::Subscribe => {
+                let head = self.storage.last_global_position();
+                let live = self.live.subscribe();
+                Response { body: ResponseBody::Subscribed { head, live } }
+            }
+            RequestBody::Backup(path) => {
+                let res = self.storage.backup(&path);
+                Response { body: ResponseBody::BackedUp { res } }
+            }
+            body => {
+                error!(?body, "not a write request");
+                Response { body: ResponseBody::Err(Error::SvcResponse) }
+            }
+        }
     }
 }
 
-async fn run_actor(mut actor: Actor) {
-    while let Some(req) = actor.inbox.recv().await {
+/// Answers a read request. Reads never touch the writer's state, so any
+/// number of them can run alongside it.
+fn handle_read(reader: &impl StorageReader, body: RequestBody) -> Response {
+    let started = Instant::now();
+    let kind = body.read_kind();
+    let resp = read_request(reader, body);
+    histogram!(READ_SECONDS, "request" => kind)
+        .record(started.elapsed().as_secs_f64());
+    resp
+}
+
+fn read_request(reader: &impl StorageReader, body: RequestBody) -> Response {
+    match body {
+        RequestBody::Read(cursor) => {
+            let messages = reader.read(&cursor);
+            histogram!(READ_BATCH).record(messages.len() as f64);
+            let cursor = cursor.after_page(&messages);
+            Response { body: ResponseBody::Messages { messages, cursor } }
+        }
+        RequestBody::PositionAtTime(tick) => {
+            let pos = reader.position_at_time(tick);
+            Response { body: ResponseBody::Position { pos } }
+        }
+        RequestBody::StreamInfo(stream) => {
+            let info = reader.stream_info(&stream);
+            Response { body: ResponseBody::StreamInfo { info } }
+        }
+        RequestBody::ListStreams { prefix, after, limit } => {
+            let names = reader.list_streams(&prefix, after.as_deref(), limit);
+            Response { body: ResponseBody::Streams { names } }
+        }
+        RequestBody::LoadPosition(consumer) => {
+            let pos = reader.load_position(&consumer);
+            Response { body: ResponseBody::Position { pos } }
+        }
+        body => {
+            error!(?body, "not a read request");
+            Response { body: ResponseBody::Err(Error::SvcResponse) }
+        }
+    }
+}
+
+impl<St: Storage> Actor<St> {
+    /// Writes the message, along with the consumer's position if given, and
+    /// broadcasts it to subscribers.
+    fn write(
+        &mut self,
+        message: OwnedWriteMessage,
+        checkpoint: Option<(&ConsumerId, u64)>,
+    ) -> Result<Position> {
+        // Only keep a copy to broadcast when someone is listening.
+        let live = (self.live.receiver_count() > 0).then(|| message.clone());
+        let started = Instant::now();
+        let pos = self.storage.write(message.into(), checkpoint);
+        histogram!(APPEND_SECONDS).record(started.elapsed().as_secs_f64());
+        match pos.as_ref() {
+            Ok(pos) => {
+                counter!(APPENDS).increment(1);
+                self.written.send_replace(pos.global);
+            }
+            Err(Error::WrongStreamPosition { .. }) => {
+                counter!(CONFLICTS).increment(1);
+            }
+            Err(_) => {}
+        }
+        if let (Some(message), Ok(pos)) = (live, pos.as_ref()) {
+            let message = OwnedMessage {
+                global_position: pos.global,
+                stream_position: pos.stream,
+                stream_name: message.stream_name,
+                message_type: message.message_type,
+                data: message.data,
+                metadata: (!message.metadata.is_empty())
+                    .then_some(message.metadata),
+            };
+            // Fails only when every subscriber is gone.
+            let _ = self.live.send(Arc::new(message));
+        }
+        pos
+    }
+
+    /// Remembers the consumer's saved position and reports its lag.
+    fn saved(&mut self, consumer: ConsumerId, global_pos: u64) {
+        self.record_lag(&consumer, global_pos);
+        self.consumers.insert(consumer, global_pos);
+    }
+
+    fn record_lag(&self, consumer: &ConsumerId, global_pos: u64) {
+        let member = consumer
+            .group_member()
+            .map_or_else(String::new, |member| member.to_string());
+        let lag = self.written.borrow().saturating_sub(global_pos);
+        gauge!(
+            CONSUMER_LAG,
+            "consumer" => consumer.name().to_owned(),
+            "member" => member,
+        )
+        .set(lag as f64);
+    }
+
+    /// Records what isn't recorded as it changes: how far behind the
+    /// consumers are and the storage's own stats.
+    fn sample(&self) {
+        for (consumer, &global_pos) in &self.consumers {
+            self.record_lag(consumer, global_pos);
+        }
+        self.storage.record_metrics();
+    }
+}
+
+/// Answers the request with `handler`, turning a panic into an error
+/// response so that one bad request does not stop the rest.
+fn answer(req: Request, handler: impl FnOnce(RequestBody) -> Response) {
+    let Request { body, response_chan, queued } = req;
+    let lane = body.lane();
+    gauge!(QUEUED, "lane" => lane).decrement(1.0);
+    histogram!(QUEUE_SECONDS, "lane" => lane)
+        .record(queued.elapsed().as_secs_f64());
+    let resp = panic::catch_unwind(AssertUnwindSafe(|| handler(body)))
+        .unwrap_or_else(|panic| {
+            let msg = panic
+                .downcast_ref::<&str>()
+                .map(|msg| (*msg).to_owned())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            error!(msg, "request handler panicked");
+            Response { body: ResponseBody::Err(Error::Panicked(msg)) }
+        });
+    debug!(?resp, "responding with");
+    let _ = response_chan.send(resp);
+}
+
+fn reject(req: Request) {
+    gauge!(QUEUED, "lane" => req.body.lane()).decrement(1.0);
+    let body = ResponseBody::Err(Error::Cancelled);
+    let _ = req.response_chan.send(Response { body });
+}
+
+/// Handles writes until the handles are dropped or killed, then rejects the
+/// queued requests, waits for the readers and closes the storage.
+async fn run_actor<St: Storage>(
+    mut actor: Actor<St>,
+    readers: Vec<thread::JoinHandle<()>>,
+) -> Result<()> {
+    let mut sample = tokio::time::interval(SAMPLE_INTERVAL);
+    sample.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let req = tokio::select! {
+            biased;
+            () = actor.token.cancelled() => break,
+            _ = sample.tick() => {
+                actor.sample();
+                continue;
+            }
+            req = actor.inbox.recv() => match req {
+                Some(req) => req,
+                None => break,
+            },
+        };
         debug!(?req, "got request");
-        if actor.token.is_cancelled() {
-            debug!("actor cancelled");
+        answer(req, |body| actor.handle_req(body));
+    }
+    debug!("actor stopping");
+    actor.inbox.close();
+    while let Some(req) = actor.inbox.recv().await {
+        reject(req);
+    }
+    // The readers only stop on their own once every handle is dropped.
+    actor.token.cancel();
+    let storage = actor.storage;
+    tokio::task::spawn_blocking(move || {
+        for reader in readers {
+            let _ = reader.join();
+        }
+        storage.close()
+    })
+    .await??;
+    debug!("actor stopped");
+    Ok(())
+}
+
+/// Answers read requests until the handles are dropped or killed, then
+/// rejects the queued ones. The readers take turns waiting on the shared
+/// inbox.
+fn run_reader(
+    reader: impl StorageReader,
+    inbox: Arc<Mutex<mpsc::Receiver<Request>>>,
+    token: CancellationToken,
+) {
+    loop {
+        let next = futures::executor::block_on(async {
+            let mut inbox = inbox.lock().await;
+            tokio::select! {
+                biased;
+                () = token.cancelled() => None,
+                req = inbox.recv() => req,
+            }
+        });
+        let Some(req) = next else {
             break;
+        };
+        debug!(?req, "got read request");
+        answer(req, |body| handle_read(&reader, body));
+    }
+    let mut inbox = inbox.blocking_lock();
+    inbox.close();
+    while let Ok(req) = inbox.try_recv() {
+        reject(req);
+    }
+    debug!("reader stopped");
+}
+
+/// The channels requests are sent on: reads go to the reader pool and
+/// everything else to the single writer.
+#[derive(Clone)]
+struct Lanes {
+    writes: mpsc::Sender<Request>,
+    reads: mpsc::Sender<Request>,
+}
+
+impl Lanes {
+    /// Fails with [`Error::Cancelled`] once the lane stopped taking requests.
+    async fn send(&self, req: Request) -> Result<()> {
+        let name = req.body.lane();
+        let lane = if req.body.is_read() { &self.reads } else { &self.writes };
+        // Counted before it's sent, so the handler's decrement can't come
+        // first.
+        let queued = gauge!(QUEUED, "lane" => name);
+        queued.increment(1.0);
+        let res = lane.send(req).await.map_err(|_| Error::Cancelled);
+        if res.is_err() {
+            queued.decrement(1.0);
         }
-        actor.handle_req(req).await.unwrap();
+        res
     }
-    debug!("actor killed");
+
+    fn depths(&self) -> QueueDepths {
+        let depth = |lane: &mpsc::Sender<Request>| {
+            lane.max_capacity() - lane.capacity()
+        };
+        QueueDepths { reads: depth(&self.reads), writes: depth(&self.writes) }
+    }
+}
+
+/// How many requests are waiting in each lane.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueDepths {
+    pub reads: usize,
+    pub writes: usize,
 }
 
 #[derive(Clone)]
 pub struct ActorHandle<const S: usize = 4096> {
-    outbox: mpsc::Sender<Request>,
+    lanes: Lanes,
     token: CancellationToken,
+    written: watch::Receiver<u64>,
+    // Taken by the first call to shutdown.
+    actor: Arc<Mutex<Option<JoinHandle<Result<()>>>>>,
 }
 
 impl<const S: usize> ActorHandle<S> {
-    #[must_use]
-    pub fn new(db: DB) -> Self {
+    /// Starts the actor on `storage` with [`DEFAULT_READERS`] readers.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the storage could not be read from or could not open its
+    /// readers.
+    pub fn new(storage: impl Storage) -> Result<Self> {
+        Self::with_readers(storage, DEFAULT_READERS)
+    }
+
+    /// Starts the writer along with `readers` threads which answer reads,
+    /// so reads neither wait behind writes nor block the async runtime.
+    /// At least one reader is started.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the storage could not be read from or could not open its
+    /// readers.
+    pub fn with_readers(storage: impl Storage, readers: usize) -> Result<Self> {
+        let last_global = storage.last_global_position()?;
+        let readers = (0..readers.max(1))
+            .map(|_| storage.reader())
+            .collect::<Result<Vec<_>>>()?;
         // TODO: REMOVE MAGIC NUMBER!
-        let (outbox, inbox) = mpsc::channel(S);
+        let (writes, inbox) = mpsc::channel(S);
+        let (reads, read_inbox) = mpsc::channel(S);
         let token = CancellationToken::new();
+        let (live, _) = broadcast::channel(LIVE_BUFFER);
+        let (written, written_recv) = watch::channel(last_global);
+        let read_inbox = Arc::new(Mutex::new(read_inbox));
+        let readers = readers
+            .into_iter()
+            .map(|reader| {
+                let (inbox, token) = (read_inbox.clone(), token.clone());
+                thread::spawn(move || run_reader(reader, inbox, token))
+            })
+            .collect();
         let actor = Actor {
             inbox,
-            db,
+            storage,
             token: token.clone(),
-            ser: WriteSerializer::new(),
+            live,
+            written,
+            consumers: HashMap::new(),
         };
-        tokio::spawn(run_actor(actor));
-        Self { outbox, token }
+        let actor = tokio::spawn(run_actor(actor, readers));
+        let lanes = Lanes { writes, reads };
+        let actor = Arc::new(Mutex::new(Some(actor)));
+        Ok(Self { lanes, token, written: written_recv, actor })
+    }
+
+    /// Returns how many requests are waiting for the writer and for the
+    /// reader pool.
+    #[must_use]
+    pub fn queue_depths(&self) -> QueueDepths {
+        self.lanes.depths()
     }
 
+    /// Waits until the message at `global_pos` has been written and can be
+    /// read, such as after another task's write returned its position.
+    pub async fn wait_for_global(
+        &self,
+        global_pos: u64,
+        timeout: Duration,
+    ) -> Result<()> {
+        let mut written = self.written.clone();
+        let wait = written.wait_for(|&last| last >= global_pos);
+        // Drop the borrowed value before `written` goes out of scope.
+        let res = tokio::time::timeout(timeout, wait)
+            .await
+            .map(|res| res.map(|_| ()));
+        match res {
+            Ok(Ok(_)) => Ok(()),
+            // The actor is gone.
+            Ok(Err(_)) => Err(Error::Cancelled),
+            Err(_) => Err(Error::Timeout { global_pos }),
+        }
+    }
+
+    /// Stops taking requests and rejects the queued ones, without waiting
+    /// for the actor to stop.
     pub fn kill(&self) {
         self.token.cancel()
     }
 
+    /// Stops taking requests, rejects the queued ones with
+    /// [`Error::Cancelled`] and waits for the readers and the writer to stop.
+    /// The storage syncs what was written to disk before it is closed, after
+    /// which it can be opened again. Shuts down every clone of the
+    /// handle.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.token.cancel();
+        // Later callers wait here until the first one is done.
+        let mut actor = self.actor.lock().await;
+        match actor.take() {
+            Some(actor) => actor.await?,
+            None => Ok(()),
+        }
+    }
+
     pub async fn put_message(&self, wm: WriteMessage<'_>) -> Result<Position> {
         if self.token.is_cancelled() {
             return Err(Error::Cancelled);
         }
         let (send, recv) = oneshot::channel();
-        let req = Request {
-            body: RequestBody::Write(wm.into()),
-            response_chan: send,
-        };
-        // Ignore send errors and handle it on the recv end below.
-        let _ = self.outbox.send(req).await;
+        let req = Request::new(RequestBody::Write(wm.into()), send);
+        self.lanes.send(req).await?;
         let res = recv.await?;
         debug!("put messages");
         match res.body {
             ResponseBody::Write { pos } => pos,
-            resp => {
-                error!(?resp, "unexpected service response body");
-                Err(Error::SvcResponse)
-            }
+            resp => Err(resp.into_error()),
         }
     }
 
+    /// Writes the message and saves the consumer's position in the same
+    /// batch, so handling a message and emitting its result happen exactly
+    /// once.
+    pub async fn put_message_with_position(
+        &self,
+        wm: WriteMessage<'_>,
+        consumer: &ConsumerId,
+        global_pos: u64,
+    ) -> Result<Position> {
+        if self.token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let (send, recv) = oneshot::channel();
+        let body = RequestBody::WriteWithPosition {
+            message: wm.into(),
+            consumer: consumer.clone(),
+            global_pos,
+        };
+        self.lanes.send(Request::new(body, send)).await?;
+        match recv.await?.body {
+            ResponseBody::Write { pos } => pos,
+            resp => Err(resp.into_error()),
+        }
+    }
+
+    async fn position_request(&self, body: RequestBody) -> Result<Option<u64>> {
+        if self.token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let (send, recv) = oneshot::channel();
+        self.lanes.send(Request::new(body, send)).await?;
+        match recv.await?.body {
+            ResponseBody::Position { pos } => pos,
+            resp => Err(resp.into_error()),
+        }
+    }
+
+    /// Returns the global position of the first message recorded at or after
+    /// `tick`, or `None` if every message is older. Resume a global read from
+    /// it to replay everything since a point in time.
+    pub async fn position_at_time(&self, tick: Tick) -> Result<Option<u64>> {
+        self.position_request(RequestBody::PositionAtTime(tick)).await
+    }
+
+    /// Summarizes the stream without reading its messages, or returns `None`
+    /// if it has none. Its `position` is the one to expect when writing next.
+    pub async fn stream_info(
+        &self,
+        stream: &str,
+    ) -> Result<Option<StreamInfo>> {
+        if self.token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let (send, recv) = oneshot::channel();
+        let body = RequestBody::StreamInfo(stream.to_owned());
+        self.lanes.send(Request::new(body, send)).await?;
+        match recv.await?.body {
+            ResponseBody::StreamInfo { info } => info,
+            resp => Err(resp.into_error()),
+        }
+    }
+
+    /// Returns up to `limit` names of streams starting with `prefix`, after
+    /// the stream named `after` if given. Pass the last name back as `after`
+    /// to list the next ones.
+    pub async fn list_streams(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        if self.token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let (send, recv) = oneshot::channel();
+        let body = RequestBody::ListStreams {
+            prefix: prefix.to_owned(),
+            after: after.map(ToOwned::to_owned),
+            limit,
+        };
+        self.lanes.send(Request::new(body, send)).await?;
+        match recv.await?.body {
+            ResponseBody::Streams { names } => names,
+            resp => Err(resp.into_error()),
+        }
+    }
+
+    /// Writes a consistent copy of the store to `path`, which must not exist
+    /// yet, holding every write answered before the call. Writes wait while
+    /// the copy is made.
+    pub async fn backup(&self, path: impl Into<PathBuf>) -> Result<()> {
+        if self.token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let (send, recv) = oneshot::channel();
+        let body = RequestBody::Backup(path.into());
+        self.lanes.send(Request::new(body, send)).await?;
+        match recv.await?.body {
+            ResponseBody::BackedUp { res } => res,
+            resp => Err(resp.into_error()),
+        }
+    }
+
+    /// Returns the consumer's last saved global position, if it has one.
+    pub async fn load_position(
+        &self,
+        consumer: &ConsumerId,
+    ) -> Result<Option<u64>> {
+        self.position_request(RequestBody::LoadPosition(consumer.clone())).await
+    }
+
+    pub async fn save_position(
+        &self,
+        consumer: &ConsumerId,
+        global_pos: u64,
+    ) -> Result<()> {
+        let body = RequestBody::SavePosition {
+            consumer: consumer.clone(),
+            global_pos,
+        };
+        self.position_request(body).await.map(|_| ())
+    }
+
+    /// Forgets the consumer's position so it starts again from the beginning.
+    pub async fn reset_position(&self, consumer: &ConsumerId) -> Result<()> {
+        self.position_request(RequestBody::ResetPosition(consumer.clone()))
+            .await
+            .map(|_| ())
+    }
+
+    /// Loads the consumer's position into a [`Checkpoint`] which is due for
+    /// saving every `save_interval` messages handled.
+    pub async fn load_checkpoint(
+        &self,
+        consumer: ConsumerId,
+        save_interval: u64,
+    ) -> Result<Checkpoint> {
+        let saved = self.load_position(&consumer).await?;
+        Ok(Checkpoint::new(consumer, saved, save_interval))
+    }
+
+    /// Records that the message at `global_pos` was handled, saving the
+    /// position if the checkpoint's interval is due. Returns whether it
+    /// saved.
+    pub async fn checkpoint(
+        &self,
+        checkpoint: &mut Checkpoint,
+        global_pos: u64,
+    ) -> Result<bool> {
+        let Some(pos) = checkpoint.handled(global_pos) else {
+            return Ok(false);
+        };
+        self.save_position(checkpoint.consumer(), pos).await?;
+        checkpoint.saved(pos);
+        Ok(true)
+    }
+
     pub async fn fetch_messages(
         &self,
-        req_body: impl Into<RequestBody>,
+        read: impl IntoCursor,
     ) -> Result<Vec<Result<OwnedMessage>>> {
         let (send, recv) = oneshot::channel();
-        let req_body = req_body.into();
-        let req = Request::new(req_body, send);
-        // Ignore send errors and handle it on the recv end below.
-        let _ = self.outbox.send(req).await;
-        let resp = recv.await.unwrap();
+        let req = Request::new(read.into_cursor()?.into(), send);
+        self.lanes.send(req).await?;
+        let resp = recv.await?;
         debug!("fetch messages");
         match resp.body {
-            ResponseBody::Messages { messages } => Ok(messages),
-            resp => {
-                error!(?resp, "unexpected service response body");
-                Err(Error::SvcResponse)
+            ResponseBody::Messages { messages, .. } => Ok(messages),
+            resp => Err(resp.into_error()),
+        }
+    }
+
+    /// Fetch a page of messages along with the cursor for the next page.
+    pub async fn fetch_page(&self, read: impl IntoCursor) -> Result<Page> {
+        fetch_page(&self.lanes, read.into_cursor()?).await
+    }
+
+    /// Stream the messages matching the request without collecting them all
+    /// in memory first.
+    ///
+    /// The messages are read by the actor in chunks of the request's limit and
+    /// fed through a bounded channel, so a slow consumer holds back further
+    /// reads rather than letting them pile up. The stream ends after the first
+    /// chunk that comes back short of the limit, or after the first error.
+    /// Dropping the stream stops the reads.
+    pub fn stream_messages(
+        &self,
+        read: impl IntoCursor,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        let (send, recv) = mpsc::channel(STREAM_CHUNK_BUFFER);
+        tokio::spawn(feed_chunks(
+            self.lanes.clone(),
+            self.token.clone(),
+            read.into_cursor(),
+            send,
+        ));
+        futures::stream::unfold(recv, |mut recv| async move {
+            recv.recv().await.map(|chunk| (chunk, recv))
+        })
+        .flat_map(futures::stream::iter)
+    }
+
+    /// Subscribe to the messages matching `filter` from the given global
+    /// position onward.
+    ///
+    /// The stream first catches up from storage and then switches to the
+    /// messages the actor broadcasts right after writing them, without gaps
+    /// or duplicates at the handoff. Writers never wait for subscribers: a
+    /// subscriber which falls too far behind catches up from storage again.
+    /// The stream ends after the first error, and dropping it stops the
+    /// subscription.
+    pub fn subscribe(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<OwnedMessage>> + Send + 'static {
+        self.feed(from_global, filter, false).filter_map(|event| async {
+            match event {
+                Ok(SubscriptionEvent::Message(msg)) => Some(Ok(msg)),
+                Ok(SubscriptionEvent::Scanned(_)) => None,
+                Err(err) => Some(Err(err)),
+            }
+        })
+    }
+
+    /// Subscribes like [`ActorHandle::subscribe`], also telling how far the
+    /// subscription has read the global log past messages the filter left
+    /// out.
+    pub fn subscribe_with_progress(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+    ) -> impl Stream<Item = Result<SubscriptionEvent>> + Send + 'static {
+        self.feed(from_global, filter, true)
+    }
+
+    fn feed(
+        &self,
+        from_global: u64,
+        filter: SubscribeFilter,
+        progress: bool,
+    ) -> impl Stream<Item = Result<SubscriptionEvent>> + Send + 'static {
+        let (send, recv) = mpsc::channel(STREAM_CHUNK_BUFFER);
+        tokio::spawn(feed_subscription(
+            self.lanes.clone(),
+            self.token.clone(),
+            from_global,
+            filter,
+            progress,
+            send,
+        ));
+        futures::stream::unfold(recv, |mut recv| async move {
+            recv.recv().await.map(|chunk| (chunk, recv))
+        })
+        .flat_map(futures::stream::iter)
+    }
+}
+
+/// What [`ActorHandle::subscribe_with_progress`] sends.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SubscriptionEvent {
+    Message(OwnedMessage),
+    /// Every message up to this global position was read, and the ones
+    /// matching the filter were sent before this.
+    Scanned(u64),
+}
+
+#[derive(
+    Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+enum SubscribeScope {
+    #[default]
+    All,
+    Stream(String),
+    Category(String),
+    // Caught up from the global log, since storage reads cover a single
+    // category at most.
+    Categories(Vec<String>),
+}
+
+/// Which messages a subscription receives.
+#[derive(
+    Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct SubscribeFilter {
+    scope: SubscribeScope,
+    filter: ReadFilter,
+}
+
+impl SubscribeFilter {
+    /// Receive every message.
+    #[must_use]
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Receive the messages of a single stream.
+    #[must_use]
+    pub fn in_stream(stream_name: &str) -> Self {
+        let scope = SubscribeScope::Stream(stream_name.to_owned());
+        Self { scope, ..Self::default() }
+    }
+
+    /// Receive the messages of every stream in a category.
+    #[must_use]
+    pub fn in_category(category: &str) -> Self {
+        let scope = SubscribeScope::Category(category.to_owned());
+        Self { scope, ..Self::default() }
+    }
+
+    /// Receive the messages of every stream in any of the categories.
+    #[must_use]
+    pub fn in_categories(categories: &[impl AsRef<str>]) -> Self {
+        let scope = match categories {
+            [category] => {
+                SubscribeScope::Category(category.as_ref().to_owned())
+            }
+            _ => SubscribeScope::Categories(
+                categories.iter().map(|c| c.as_ref().to_owned()).collect(),
+            ),
+        };
+        Self { scope, ..Self::default() }
+    }
+
+    /// Only receive messages whose type is one of `types`.
+    #[must_use]
+    pub fn of_types(mut self, types: &[impl AsRef<str>]) -> Self {
+        let types = types.iter().map(|t| t.as_ref().to_owned()).collect();
+        self.filter.types = Some(types);
+        self
+    }
+
+    /// Returns the stream the filter is limited to, if it is.
+    #[must_use]
+    pub fn stream(&self) -> Option<&str> {
+        match &self.scope {
+            SubscribeScope::Stream(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    /// Returns the categories the filter is limited to, which are none if
+    /// it isn't.
+    #[must_use]
+    pub fn categories(&self) -> &[String] {
+        match &self.scope {
+            SubscribeScope::Category(category) => {
+                std::slice::from_ref(category)
+            }
+            SubscribeScope::Categories(categories) => categories,
+            SubscribeScope::All | SubscribeScope::Stream(_) => &[],
+        }
+    }
+
+    /// Returns the message types the filter lets through, if it filters
+    /// them.
+    #[must_use]
+    pub fn types(&self) -> Option<&[String]> {
+        self.filter.types.as_deref()
+    }
+
+    fn matches(&self, msg: &OwnedMessage) -> bool {
+        let in_scope = match &self.scope {
+            SubscribeScope::All => true,
+            SubscribeScope::Stream(stream) => &msg.stream_name == stream,
+            SubscribeScope::Category(category) => {
+                read::category(&msg.stream_name) == category
+            }
+            SubscribeScope::Categories(categories) => {
+                let category = read::category(&msg.stream_name);
+                categories.iter().any(|c| c == category)
+            }
+        };
+        in_scope
+            && self.filter.wants_type(&msg.message_type)
+            && self.filter.wants_stream(&msg.stream_name)
+            && self.filter.wants_metadata(msg.metadata.as_deref())
+    }
+
+    /// Returns the storage read for catching up from `global_pos`.
+    fn read(&self, global_pos: u64) -> Cursor {
+        let limit = read::LIMIT_DEFAULT;
+        let cursor = match &self.scope {
+            SubscribeScope::All | SubscribeScope::Categories(_) => {
+                Cursor::global(None, global_pos, limit)
+            }
+            SubscribeScope::Stream(stream) => {
+                Cursor::global(Some(stream.clone()), global_pos, limit)
+            }
+            SubscribeScope::Category(category) => {
+                Cursor::category(category.clone(), global_pos, limit)
+            }
+        };
+        cursor.with_filter(self.filter.clone())
+    }
+}
+
+async fn fetch_page(lanes: &Lanes, cursor: Cursor) -> Result<Page> {
+    let (send, recv) = oneshot::channel();
+    lanes.send(Request::new(cursor.into(), send)).await?;
+    match recv.await?.body {
+        ResponseBody::Messages { messages, cursor } => {
+            Ok(Page { messages, cursor })
+        }
+        resp => Err(resp.into_error()),
+    }
+}
+
+/// Returns the last global position in storage along with a receiver for
+/// every message written after it.
+async fn subscribe_live(
+    lanes: &Lanes,
+) -> Result<(u64, broadcast::Receiver<Arc<OwnedMessage>>)> {
+    let (send, recv) = oneshot::channel();
+    lanes.send(Request::new(RequestBody::Subscribe, send)).await?;
+    match recv.await?.body {
+        ResponseBody::Subscribed { head, live } => Ok((head?, live)),
+        resp => Err(resp.into_error()),
+    }
+}
+
+async fn feed_subscription(
+    lanes: Lanes,
+    token: CancellationToken,
+    from_global: u64,
+    filter: SubscribeFilter,
+    progress: bool,
+    chunks: mpsc::Sender<Vec<Result<SubscriptionEvent>>>,
+) {
+    // The next global position the subscriber has not seen yet.
+    let mut next = from_global;
+    loop {
+        let (head, mut live) = match subscribe_live(&lanes).await {
+            Ok(subscribed) => subscribed,
+            Err(err) => {
+                let _ = chunks.send(vec![Err(err)]).await;
+                return;
+            }
+        };
+
+        // Everything up to head is in storage, and everything after it will
+        // come through the live receiver.
+        while next <= head {
+            let Ok(permit) = chunks.reserve().await else {
+                debug!("subscription dropped");
+                return;
+            };
+            if token.is_cancelled() {
+                permit.send(vec![Err(Error::Cancelled)]);
+                return;
+            }
+            let page = match fetch_page(&lanes, filter.read(next)).await {
+                Ok(page) => page.messages,
+                Err(err) => {
+                    permit.send(vec![Err(err)]);
+                    return;
+                }
+            };
+            if page.iter().any(Result::is_err) {
+                permit.send(events(page));
+                return;
+            }
+            let full = page.len() >= read::LIMIT_DEFAULT;
+            let mut caught_up: Vec<_> = page
+                .into_iter()
+                .take_while(|msg| {
+                    msg.as_ref().is_ok_and(|msg| msg.global_position <= head)
+                })
+                .collect();
+            next = match caught_up.last() {
+                Some(Ok(last)) if full => last.global_position + 1,
+                _ => head + 1,
+            };
+            // Some scopes are only narrowed down after reading.
+            caught_up
+                .retain(|msg| msg.as_ref().is_ok_and(|m| filter.matches(m)));
+            let mut caught_up = events(caught_up);
+            if progress {
+                caught_up.push(Ok(SubscriptionEvent::Scanned(next - 1)));
+            }
+            permit.send(caught_up);
+        }
+
+        loop {
+            let msg = tokio::select! {
+                msg = live.recv() => msg,
+                () = chunks.closed() => {
+                    debug!("subscription dropped");
+                    return;
+                }
+                () = token.cancelled() => {
+                    let _ = chunks.send(vec![Err(Error::Cancelled)]).await;
+                    return;
+                }
+            };
+            match msg {
+                Ok(msg) => {
+                    if msg.global_position < next {
+                        continue;
+                    }
+                    next = msg.global_position + 1;
+                    let event = if filter.matches(&msg) {
+                        SubscriptionEvent::Message(OwnedMessage::clone(&msg))
+                    } else if progress {
+                        SubscriptionEvent::Scanned(msg.global_position)
+                    } else {
+                        continue;
+                    };
+                    if chunks.send(vec![Ok(event)]).await.is_err() {
+                        debug!("subscription dropped");
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "subscriber lagged, catching up");
+                    break;
+                }
+                Err(RecvError::Closed) => {
+                    let _ = chunks.send(vec![Err(Error::Cancelled)]).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn events(
+    messages: Vec<Result<OwnedMessage>>,
+) -> Vec<Result<SubscriptionEvent>> {
+    messages
+        .into_iter()
+        .map(|msg| msg.map(SubscriptionEvent::Message))
+        .collect()
+}
+
+async fn feed_chunks(
+    lanes: Lanes,
+    token: CancellationToken,
+    cursor: Result<Cursor>,
+    chunks: mpsc::Sender<Vec<Result<OwnedMessage>>>,
+) {
+    let cursor = match cursor {
+        Ok(cursor) => cursor,
+        Err(err) => {
+            let _ = chunks.send(vec![Err(err)]).await;
+            return;
+        }
+    };
+    let mut next = Some(cursor);
+    while let Some(cursor) = next.take() {
+        // Wait for room in the channel before reading the next chunk. This
+        // also fails once the stream has been dropped.
+        let Ok(permit) = chunks.reserve().await else {
+            debug!("message stream dropped");
+            return;
+        };
+        if token.is_cancelled() {
+            permit.send(vec![Err(Error::Cancelled)]);
+            return;
+        }
+        let limit = cursor.limit();
+        let page = match fetch_page(&lanes, cursor).await {
+            Ok(page) => page,
+            Err(err) => {
+                permit.send(vec![Err(err)]);
+                return;
+            }
+        };
+        let failed = page.messages.iter().any(Result::is_err);
+        if !failed && page.messages.len() >= limit {
+            next = Some(page.cursor);
+        }
+        permit.send(page.messages);
+    }
+    debug!("message stream complete");
+}
+
+#[cfg(all(test, feature = "rocksdb"))]
+mod test {
+    use std::borrow::Cow;
+
+    use futures::StreamExt;
+    use ident::Id;
+
+    use super::*;
+    use crate::{
+        read::GetMessages,
+        rocks::{db::DB, storage::RocksStorage},
+        write::ExpectedVersion,
+        StreamPos,
+    };
+
+    fn rocks(path: impl AsRef<std::path::Path>) -> RocksStorage {
+        RocksStorage::new(DB::new(path).unwrap())
+    }
+
+    fn new_handle() -> ActorHandle {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        ActorHandle::new(rocks(path)).unwrap()
+    }
+
+    async fn put_messages(handle: &ActorHandle, stream: &str, count: u64) {
+        for _ in 0..count {
+            handle
+                .put_message(WriteMessage {
+                    id: Id::new(),
+                    stream_name: stream.into(),
+                    message_type: "someMsgType".into(),
+                    data: Cow::Borrowed(b"{}"),
+                    metadata: Cow::Borrowed(b""),
+                    expected_version: ExpectedVersion::Any,
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Writes a message and retries it, then writes its id to another
+    /// stream.
+    async fn assert_writes_once(handle: &ActorHandle) {
+        let wm = WriteMessage {
+            id: Id::new(),
+            stream_name: "account-1".into(),
+            message_type: "Opened".into(),
+            data: Cow::Borrowed(b"{}"),
+            metadata: Cow::Borrowed(b""),
+            expected_version: ExpectedVersion::NoStream,
+        };
+        let first = handle.put_message(wm.clone()).await.unwrap();
+        let retry = handle.put_message(wm.clone()).await.unwrap();
+        assert!(retry == first);
+        let elsewhere = WriteMessage { stream_name: "account-2".into(), ..wm };
+        let res = handle.put_message(elsewhere).await;
+        assert!(matches!(res, Err(Error::IdInUse { .. })));
+        let info = handle.stream_info("account-1").await.unwrap().unwrap();
+        assert!(info.count == 1);
+        assert!(handle.stream_info("account-2").await.unwrap().is_none());
+    }
+
+    /// Reads a category whose name is also a stream, and one named like a
+    /// stream in it, which only the category's own streams are in.
+    async fn assert_reads_categories(handle: &ActorHandle) {
+        for stream in ["account-1", "account", "accountX-1", "account-2+a"] {
+            put_messages(handle, stream, 1).await;
+        }
+        let cases: [(&str, &[&str]); 2] = [
+            ("account", &["account-1", "account", "account-2+a"]),
+            ("account-1", &[]),
+        ];
+        for (category, expected) in cases {
+            let read = GetMessages::default().in_category(category);
+            let page = handle.fetch_page(read).await.unwrap();
+            let streams: Vec<_> = page
+                .messages
+                .into_iter()
+                .map(|msg| msg.unwrap().stream_name)
+                .collect();
+            assert!(streams == expected, "category {category}");
+        }
+    }
+
+    mod idempotency {
+        use super::*;
+
+        #[tokio::test]
+        async fn a_retried_write_returns_the_original_position() {
+            assert_writes_once(&new_handle()).await;
+        }
+
+        #[tokio::test]
+        async fn retries_are_recognized_after_a_restart() {
+            let path = std::env::temp_dir().join(Id::new().to_string());
+            let handle: ActorHandle = ActorHandle::new(rocks(&path)).unwrap();
+            let wm = WriteMessage {
+                id: Id::new(),
+                stream_name: "account-1".into(),
+                message_type: "Opened".into(),
+                data: Cow::Borrowed(b"{}"),
+                metadata: Cow::Borrowed(b""),
+                expected_version: ExpectedVersion::NoStream,
+            };
+            let first = handle.put_message(wm.clone()).await.unwrap();
+            handle.shutdown().await.unwrap();
+
+            let handle: ActorHandle = ActorHandle::new(rocks(&path)).unwrap();
+            assert!(handle.put_message(wm).await.unwrap() == first);
+        }
+    }
+
+    mod fetch_page {
+        use super::*;
+        use assert2::assert;
+
+        #[tokio::test]
+        async fn a_category_holds_the_streams_named_for_it() {
+            assert_reads_categories(&new_handle()).await;
+        }
+
+        #[tokio::test]
+        async fn resuming_the_cursor_continues_where_the_page_ended() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 7).await;
+            let req = GetMessages::default().in_stream("stream1").with_limit(3);
+            let first = handle.fetch_page(req).await.unwrap();
+            assert!(first.messages.len() == 3);
+
+            let cursor: Cursor = first.cursor.to_string().parse().unwrap();
+            let second =
+                handle.fetch_page(GetMessages::resume(cursor)).await.unwrap();
+            let positions: Vec<_> = second
+                .messages
+                .into_iter()
+                .map(|msg| msg.unwrap().stream_position)
+                .collect();
+            assert!(positions == [3, 4, 5].map(StreamPos::Sequential));
+        }
+
+        #[tokio::test]
+        async fn an_empty_page_returns_the_same_cursor() {
+            let handle = new_handle();
+            let req = GetMessages::default().in_stream("stream1").with_limit(3);
+            let page = handle.fetch_page(req).await.unwrap();
+            assert!(page.messages.is_empty());
+            assert!(page.cursor == Cursor::stream("stream1".into(), None, 3));
+        }
+
+        #[tokio::test]
+        async fn a_global_read_in_a_stream_only_returns_that_stream() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 2).await;
+            put_messages(&handle, "stream2", 2).await;
+            put_messages(&handle, "stream1", 2).await;
+            let req =
+                GetMessages::default().in_stream("stream1").from_global(2);
+            let page = handle.fetch_page(req).await.unwrap();
+            let positions: Vec<_> = page
+                .messages
+                .into_iter()
+                .map(|msg| msg.unwrap().global_position)
+                .collect();
+            assert!(positions == [2, 5, 6]);
+        }
+
+        #[tokio::test]
+        async fn a_read_it_cannot_honour_is_rejected() {
+            let handle = new_handle();
+            let req = GetMessages::default()
+                .in_category("account")
+                .from_stream_pos(StreamPos::Sequential(1));
+            let res = handle.fetch_page(req).await;
+            assert!(let Err(Error::InvalidRead(_)) = res);
+        }
+
+        #[tokio::test]
+        async fn category_pages_resume_within_the_category() {
+            let handle = new_handle();
+            put_messages(&handle, "account-1", 2).await;
+            put_messages(&handle, "billing-1", 2).await;
+            put_messages(&handle, "account-2", 2).await;
+            let req =
+                GetMessages::default().in_category("account").with_limit(3);
+            let first = handle.fetch_page(req).await.unwrap();
+            let second = handle
+                .fetch_page(GetMessages::resume(first.cursor))
+                .await
+                .unwrap();
+            let streams: Vec<_> = second
+                .messages
+                .into_iter()
+                .map(|msg| msg.unwrap().stream_name)
+                .collect();
+            assert!(streams == ["account-2"]);
+        }
+
+        #[tokio::test]
+        async fn time_range_pages_resume_within_the_range() {
+            let handle = new_handle();
+            put_messages(&handle, "account-1", 1).await;
+            // Step past the clock's resolution of 50ms.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let start = Tick::from(std::time::SystemTime::now());
+            put_messages(&handle, "account-2", 3).await;
+            let end = Tick::from_u64(u64::MAX);
+            let first_pos = handle.position_at_time(start).await.unwrap();
+            assert!(first_pos == Some(2));
+
+            let req = GetMessages::default().between(start, end).with_limit(2);
+            let first = handle.fetch_page(req).await.unwrap();
+            assert!(first.messages.len() == 2);
+            let second = handle
+                .fetch_page(GetMessages::resume(first.cursor))
+                .await
+                .unwrap();
+            let positions: Vec<_> = second
+                .messages
+                .into_iter()
+                .map(|msg| msg.unwrap().global_position)
+                .collect();
+            assert!(positions == [4]);
+        }
+    }
+
+    mod stream_messages {
+        use super::*;
+        use assert2::assert;
+
+        #[tokio::test]
+        async fn it_streams_global_messages_across_chunks() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 25).await;
+            let req = GetMessages::default().from_global(0).with_limit(10);
+            let messages: Vec<_> = handle
+                .stream_messages(req)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<_>>()
+                .unwrap();
+            assert!(messages.len() == 25);
+            assert!(messages
+                .windows(2)
+                .all(|w| w[0].global_position < w[1].global_position));
+        }
+
+        #[tokio::test]
+        async fn it_streams_stream_messages_across_chunks() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 12).await;
+            put_messages(&handle, "stream2", 3).await;
+            let req = GetMessages::default().in_stream("stream1").with_limit(5);
+            let messages: Vec<_> = handle
+                .stream_messages(req)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<_>>()
+                .unwrap();
+            assert!(messages.len() == 12);
+            for (i, msg) in messages.iter().enumerate() {
+                assert!(msg.stream_name == "stream1");
+                assert!(msg.stream_position == StreamPos::Sequential(i as u64));
+            }
+        }
+
+        #[tokio::test]
+        async fn it_streams_backward_across_chunks() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 12).await;
+            let req = GetMessages::default()
+                .in_stream("stream1")
+                .with_limit(5)
+                .backwards();
+            let positions: Vec<_> = handle
+                .stream_messages(req)
+                .map(|msg| msg.unwrap().stream_position.position())
+                .collect()
+                .await;
+            assert!(positions == (0..12).rev().collect::<Vec<_>>());
+        }
+
+        #[tokio::test]
+        async fn dropping_the_stream_stops_reading() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 30).await;
+            let req = GetMessages::default().from_global(0).with_limit(1);
+            let mut stream = Box::pin(handle.stream_messages(req));
+            assert!(let Some(Ok(_)) = stream.next().await);
+            drop(stream);
+            // The actor is still free to take writes.
+            put_messages(&handle, "stream2", 1).await;
+        }
+    }
+
+    mod stream_info {
+        use super::*;
+        use assert2::assert;
+
+        #[tokio::test]
+        async fn it_summarizes_the_stream() {
+            let handle = new_handle();
+            put_messages(&handle, "account-1", 2).await;
+            put_messages(&handle, "account-2", 1).await;
+            let info = handle.stream_info("account-1").await.unwrap().unwrap();
+            assert!(info.position == StreamPos::Sequential(1));
+            assert!(info.count == 2);
+            assert!(info.first_global == 1);
+            assert!(info.last_global == 2);
+            assert!(info.first_time < info.last_time);
+        }
+
+        #[tokio::test]
+        async fn a_missing_stream_has_no_info() {
+            let handle = new_handle();
+            put_messages(&handle, "account-1", 1).await;
+            let info = handle.stream_info("account").await.unwrap();
+            assert!(info.is_none());
+        }
+    }
+
+    mod list_streams {
+        use super::*;
+        use assert2::assert;
+
+        #[tokio::test]
+        async fn it_pages_through_the_names() {
+            let handle = new_handle();
+            for stream in ["account-2", "account-1", "billing-1", "account-3"] {
+                put_messages(&handle, stream, 2).await;
+            }
+            let first = handle.list_streams("account-", None, 2).await.unwrap();
+            assert!(first == ["account-1", "account-2"]);
+            let rest = handle
+                .list_streams("account-", Some("account-2"), 2)
+                .await
+                .unwrap();
+            assert!(rest == ["account-3"]);
+        }
+    }
+
+    mod backup {
+        use super::*;
+        use assert2::assert;
+
+        #[tokio::test]
+        async fn the_copy_has_what_was_written() {
+            let handle = new_handle();
+            put_messages(&handle, "account-1", 2).await;
+            let path = std::env::temp_dir().join(Id::new().to_string());
+            handle.backup(&path).await.unwrap();
+            put_messages(&handle, "account-1", 1).await;
+
+            let copy: ActorHandle = ActorHandle::new(rocks(&path)).unwrap();
+            let info = copy.stream_info("account-1").await.unwrap().unwrap();
+            assert!(info.count == 2);
+        }
+    }
+
+    mod wait_for_global {
+        use super::*;
+        use assert2::assert;
+
+        #[tokio::test]
+        async fn it_resolves_for_written_positions() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 3).await;
+            let res = handle.wait_for_global(3, Duration::ZERO).await;
+            assert!(res.is_ok());
+        }
+
+        #[tokio::test]
+        async fn it_resolves_once_the_position_is_written() {
+            let handle = new_handle();
+            let waiter = handle.clone();
+            let wait = tokio::spawn(async move {
+                waiter.wait_for_global(2, Duration::from_secs(5)).await
+            });
+            put_messages(&handle, "stream1", 2).await;
+            assert!(wait.await.unwrap().is_ok());
+            let messages = handle
+                .fetch_messages(GetMessages::default().from_global(2))
+                .await
+                .unwrap();
+            assert!(messages.len() == 1);
+        }
+
+        #[tokio::test]
+        async fn it_times_out() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 1).await;
+            let res =
+                handle.wait_for_global(2, Duration::from_millis(10)).await;
+            assert!(let Err(Error::Timeout { global_pos: 2 }) = res);
+        }
+    }
+
+    mod positions {
+        use super::*;
+        use assert2::assert;
+
+        #[tokio::test]
+        async fn it_saves_loads_and_resets_positions() {
+            let handle = new_handle();
+            let consumer = ConsumerId::new("projector").member(2);
+            assert!(handle.load_position(&consumer).await.unwrap().is_none());
+            handle.save_position(&consumer, 9).await.unwrap();
+            assert!(handle.load_position(&consumer).await.unwrap() == Some(9));
+            handle.reset_position(&consumer).await.unwrap();
+            assert!(handle.load_position(&consumer).await.unwrap().is_none());
+        }
+
+        #[tokio::test]
+        async fn checkpoints_save_on_their_interval() {
+            let handle = new_handle();
+            let consumer = ConsumerId::new("projector");
+            let mut cp =
+                handle.load_checkpoint(consumer.clone(), 3).await.unwrap();
+            let mut saves = vec![];
+            for pos in 1..=7 {
+                saves.push(handle.checkpoint(&mut cp, pos).await.unwrap());
+            }
+            assert!(saves == [true, false, false, true, false, false, true]);
+            assert!(handle.load_position(&consumer).await.unwrap() == Some(7));
+
+            let cp = handle.load_checkpoint(consumer, 3).await.unwrap();
+            assert!(cp.next() == 8);
+        }
+
+        #[tokio::test]
+        async fn it_writes_and_saves_the_position_together() {
+            let handle = new_handle();
+            let consumer = ConsumerId::new("projector");
+            let wm = WriteMessage {
+                id: Id::new(),
+                stream_name: "stream1".into(),
+                message_type: "someMsgType".into(),
+                data: Cow::Borrowed(b"{}"),
+                metadata: Cow::Borrowed(b""),
+                expected_version: ExpectedVersion::NoStream,
+            };
+            handle
+                .put_message_with_position(wm.clone(), &consumer, 4)
+                .await
+                .unwrap();
+            let wm = WriteMessage { id: Id::new(), ..wm };
+            let res = handle.put_message_with_position(wm, &consumer, 5).await;
+            assert!(res.is_err());
+            assert!(handle.load_position(&consumer).await.unwrap() == Some(4));
+        }
+    }
+
+    mod subscribe {
+        use std::time::Duration;
+
+        use super::*;
+        use assert2::assert;
+
+        async fn next_n(
+            stream: &mut (impl Stream<Item = Result<OwnedMessage>> + Unpin),
+            n: usize,
+        ) -> Vec<OwnedMessage> {
+            let take = stream.take(n).collect::<Vec<_>>();
+            tokio::time::timeout(Duration::from_secs(5), take)
+                .await
+                .expect("subscription stalled")
+                .into_iter()
+                .collect::<Result<_>>()
+                .unwrap()
+        }
+
+        fn assert_gapless(messages: &[OwnedMessage]) {
+            assert!(messages
+                .windows(2)
+                .all(|w| w[0].global_position + 1 == w[1].global_position));
+        }
+
+        #[tokio::test]
+        async fn it_catches_up_then_follows_live_writes() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 5).await;
+            let mut sub = Box::pin(handle.subscribe(0, SubscribeFilter::all()));
+            let mut messages = next_n(&mut sub, 5).await;
+            put_messages(&handle, "stream2", 3).await;
+            messages.extend(next_n(&mut sub, 3).await);
+            assert!(messages.len() == 8);
+            assert_gapless(&messages);
+            let streams: Vec<_> =
+                messages.iter().map(|m| m.stream_name.as_str()).collect();
+            assert!(streams[..5] == ["stream1"; 5]);
+            assert!(streams[5..] == ["stream2"; 3]);
+        }
+
+        #[tokio::test]
+        async fn it_starts_from_the_given_global_position() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 5).await;
+            let first = handle
+                .fetch_messages(GetMessages::default().from_global(0))
+                .await
+                .unwrap()
+                .remove(0)
+                .unwrap()
+                .global_position;
+            let mut sub =
+                Box::pin(handle.subscribe(first + 3, SubscribeFilter::all()));
+            let messages = next_n(&mut sub, 2).await;
+            assert!(messages[0].global_position == first + 3);
+            assert_gapless(&messages);
+        }
+
+        #[tokio::test]
+        async fn it_filters_by_category_during_catch_up_and_live() {
+            let handle = new_handle();
+            put_messages(&handle, "cat-1", 2).await;
+            put_messages(&handle, "other-1", 2).await;
+            let mut sub = Box::pin(
+                handle.subscribe(0, SubscribeFilter::in_category("cat")),
+            );
+            let mut messages = next_n(&mut sub, 2).await;
+            put_messages(&handle, "other-2", 2).await;
+            put_messages(&handle, "cat-2", 1).await;
+            messages.extend(next_n(&mut sub, 1).await);
+            let streams: Vec<_> =
+                messages.iter().map(|m| m.stream_name.as_str()).collect();
+            assert!(streams == ["cat-1", "cat-1", "cat-2"]);
+        }
+
+        #[tokio::test]
+        async fn it_tells_how_far_it_has_read() {
+            let handle = new_handle();
+            put_messages(&handle, "cat-1", 1).await;
+            put_messages(&handle, "other-1", 1).await;
+            let filter = SubscribeFilter::in_category("cat");
+            let mut sub = Box::pin(handle.subscribe_with_progress(0, filter));
+            let mut events = Vec::new();
+            for _ in 0..2 {
+                events.push(sub.next().await.unwrap().unwrap());
+            }
+            assert!(let SubscriptionEvent::Message(_) = &events[0]);
+            assert!(events[1] == SubscriptionEvent::Scanned(2));
+
+            put_messages(&handle, "other-2", 1).await;
+            let event = sub.next().await.unwrap().unwrap();
+            assert!(event == SubscriptionEvent::Scanned(3));
+        }
+
+        #[tokio::test]
+        async fn it_subscribes_to_several_categories() {
+            let handle = new_handle();
+            put_messages(&handle, "a-1", 1).await;
+            put_messages(&handle, "other-1", 2).await;
+            put_messages(&handle, "b-1", 1).await;
+            let filter = SubscribeFilter::in_categories(&["a", "b"]);
+            let mut sub = Box::pin(handle.subscribe(0, filter));
+            let mut messages = next_n(&mut sub, 2).await;
+            put_messages(&handle, "other-2", 1).await;
+            put_messages(&handle, "b-2", 1).await;
+            messages.extend(next_n(&mut sub, 1).await);
+            let streams: Vec<_> =
+                messages.iter().map(|m| m.stream_name.as_str()).collect();
+            assert!(streams == ["a-1", "b-1", "b-2"]);
+        }
+
+        #[tokio::test]
+        async fn it_filters_by_stream_and_type() {
+            let handle = new_handle();
+            let mut sub = Box::pin(handle.subscribe(
+                0,
+                SubscribeFilter::in_stream("stream1").of_types(&["Wanted"]),
+            ));
+            for (stream, message_type) in [
+                ("stream1", "Unwanted"),
+                ("stream2", "Wanted"),
+                ("stream1", "Wanted"),
+            ] {
+                handle
+                    .put_message(WriteMessage {
+                        id: Id::new(),
+                        stream_name: stream.into(),
+                        message_type: message_type.into(),
+                        data: Cow::Borrowed(b"{}"),
+                        metadata: Cow::Borrowed(b""),
+                        expected_version: ExpectedVersion::Any,
+                    })
+                    .await
+                    .unwrap();
+            }
+            let messages = next_n(&mut sub, 1).await;
+            assert!(messages[0].stream_name == "stream1");
+            assert!(messages[0].message_type == "Wanted");
+        }
+
+        #[tokio::test]
+        async fn a_lagging_subscriber_catches_up_from_storage() {
+            let handle = new_handle();
+            put_messages(&handle, "stream1", 1).await;
+            let mut sub = Box::pin(handle.subscribe(0, SubscribeFilter::all()));
+            let mut messages = next_n(&mut sub, 1).await;
+            // Writers never wait on the subscriber, which overflows the
+            // live buffer while nobody polls it.
+            let count = LIVE_BUFFER + 50;
+            put_messages(&handle, "stream2", count as u64).await;
+            messages.extend(next_n(&mut sub, count).await);
+            assert!(messages.len() == count + 1);
+            assert_gapless(&messages);
+        }
+    }
+
+    mod lanes {
+        use super::*;
+        use assert2::assert;
+
+        type Inboxes =
+            (mpsc::Receiver<Request>, Arc<Mutex<mpsc::Receiver<Request>>>);
+
+        /// A handle whose writer never picks up its requests, served by
+        /// `readers` reader threads. The lanes stay open while the returned
+        /// inboxes are alive.
+        fn stalled_writer(readers: usize) -> (ActorHandle, Inboxes) {
+            let path = std::env::temp_dir().join(Id::new().to_string());
+            let db = Arc::new(DB::new(path).unwrap());
+            let (writes, write_inbox) = mpsc::channel(4096);
+            let (reads, read_inbox) = mpsc::channel(4096);
+            let token = CancellationToken::new();
+            let read_inbox = Arc::new(Mutex::new(read_inbox));
+            for _ in 0..readers {
+                let (db, inbox, token) =
+                    (db.clone(), read_inbox.clone(), token.clone());
+                thread::spawn(move || run_reader(db, inbox, token));
+            }
+            let (_, written) = watch::channel(0);
+            let lanes = Lanes { writes, reads };
+            let actor = Arc::new(Mutex::new(None));
+            let handle = ActorHandle { lanes, token, written, actor };
+            (handle, (write_inbox, read_inbox))
+        }
+
+        #[tokio::test]
+        async fn reads_do_not_wait_behind_writes() {
+            let (handle, _inboxes) = stalled_writer(1);
+            let writer = handle.clone();
+            tokio::spawn(
+                async move { put_messages(&writer, "stream1", 1).await },
+            );
+            while handle.queue_depths().writes == 0 {
+                tokio::task::yield_now().await;
+            }
+            let info = handle.stream_info("stream1").await.unwrap();
+            assert!(info.is_none());
+            let messages = handle
+                .fetch_messages(GetMessages::default().from_global(0))
+                .await
+                .unwrap();
+            assert!(messages.is_empty());
+            assert!(
+                handle.queue_depths() == QueueDepths { reads: 0, writes: 1 }
+            );
+        }
+
+        #[tokio::test]
+        async fn it_reports_the_depth_of_each_lane() {
+            let (handle, _inboxes) = stalled_writer(0);
+            assert!(handle.queue_depths() == QueueDepths::default());
+            for stream in ["stream1", "stream2", "stream3"] {
+                let reader = handle.clone();
+                tokio::spawn(async move { reader.stream_info(stream).await });
+            }
+            let writer = handle.clone();
+            tokio::spawn(
+                async move { put_messages(&writer, "stream1", 1).await },
+            );
+            let expected = QueueDepths { reads: 3, writes: 1 };
+            while handle.queue_depths() != expected {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        #[tokio::test]
+        async fn the_pool_reads_its_own_writes() {
+            let path = std::env::temp_dir().join(Id::new().to_string());
+            let handle: ActorHandle =
+                ActorHandle::with_readers(rocks(path), 3).unwrap();
+            put_messages(&handle, "stream1", 5).await;
+            let reads: Vec<_> = (0..6)
+                .map(|_| {
+                    let handle = handle.clone();
+                    tokio::spawn(async move {
+                        handle.stream_info("stream1").await.unwrap().unwrap()
+                    })
+                })
+                .collect();
+            for read in reads {
+                let info = read.await.unwrap();
+                assert!(info.position == StreamPos::Sequential(4));
+            }
+            assert!(handle.queue_depths() == QueueDepths::default());
+        }
+    }
+
+    mod shutdown {
+        use super::*;
+        use assert2::assert;
+
+        fn message(expected_version: ExpectedVersion) -> WriteMessage<'static> {
+            WriteMessage {
+                id: Id::new(),
+                stream_name: "stream1".into(),
+                message_type: "someMsgType".into(),
+                data: Cow::Borrowed(b"{}"),
+                metadata: Cow::Borrowed(b""),
+                expected_version,
+            }
+        }
+
+        #[tokio::test]
+        async fn it_closes_the_db_so_it_can_be_reopened() {
+            let path = std::env::temp_dir().join(Id::new().to_string());
+            let handle: ActorHandle = ActorHandle::new(rocks(&path)).unwrap();
+            put_messages(&handle, "stream1", 3).await;
+            handle.clone().shutdown().await.unwrap();
+            // A second shutdown finds it already stopped.
+            handle.shutdown().await.unwrap();
+
+            let handle: ActorHandle = ActorHandle::new(rocks(&path)).unwrap();
+            let info = handle.stream_info("stream1").await.unwrap().unwrap();
+            assert!(info.count == 3);
+            handle.shutdown().await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn requests_after_shutdown_are_cancelled() {
+            let handle = new_handle();
+            handle.shutdown().await.unwrap();
+            let res = handle.put_message(message(ExpectedVersion::Any)).await;
+            assert!(let Err(Error::Cancelled) = res);
+            let read = GetMessages::default().from_global(0);
+            let res = handle.fetch_messages(read).await;
+            assert!(let Err(Error::Cancelled) = res);
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn queued_requests_are_answered_or_cancelled() {
+            let handle = new_handle();
+            let writes: Vec<_> = (0..200)
+                .map(|_| {
+                    let handle = handle.clone();
+                    tokio::spawn(async move {
+                        handle.put_message(message(ExpectedVersion::Any)).await
+                    })
+                })
+                .collect();
+            handle.shutdown().await.unwrap();
+            for write in writes {
+                let res = write.await.unwrap();
+                assert!(res.is_ok() || matches!(res, Err(Error::Cancelled)));
+            }
+        }
+
+        /// Panics writing to `panic-1`, and otherwise writes to RocksDB.
+        struct Panicking(RocksStorage);
+
+        impl Storage for Panicking {
+            type Reader = Arc<DB>;
+
+            fn reader(&self) -> Result<Self::Reader> {
+                self.0.reader()
+            }
+
+            fn write(
+                &mut self,
+                message: WriteMessage<'_>,
+                checkpoint: Option<(&ConsumerId, u64)>,
+            ) -> Result<Position> {
+                if message.stream_name == "panic-1" {
+                    panic!("asked to panic");
+                }
+                self.0.write(message, checkpoint)
+            }
+
+            fn save_position(
+                &mut self,
+                consumer: &ConsumerId,
+                global_pos: u64,
+            ) -> Result<()> {
+                self.0.save_position(consumer, global_pos)
+            }
+
+            fn reset_position(&mut self, consumer: &ConsumerId) -> Result<()> {
+                self.0.reset_position(consumer)
+            }
+
+            fn last_global_position(&self) -> Result<u64> {
+                self.0.last_global_position()
+            }
+
+            fn backup(&self, path: &std::path::Path) -> Result<()> {
+                self.0.backup(path)
+            }
+
+            fn close(self) -> Result<()> {
+                self.0.close()
+            }
+        }
+
+        #[tokio::test]
+        async fn a_panicking_request_does_not_stop_the_actor() {
+            let path = std::env::temp_dir().join(Id::new().to_string());
+            let handle: ActorHandle =
+                ActorHandle::new(Panicking(rocks(path))).unwrap();
+            let msg = WriteMessage {
+                stream_name: "panic-1".into(),
+                ..message(ExpectedVersion::Any)
+            };
+            let res = handle.put_message(msg).await;
+            assert!(let Err(Error::Panicked(_)) = res);
+            put_messages(&handle, "stream1", 1).await;
+            let info = handle.stream_info("stream1").await.unwrap().unwrap();
+            assert!(info.count == 1);
+        }
+
+        #[tokio::test]
+        async fn a_relaxed_write_is_rejected() {
+            let handle = new_handle();
+            let relaxed = ExpectedVersion::Exact(StreamPos::Relaxed(0));
+            let res = handle.put_message(message(relaxed)).await;
+            assert!(let Err(Error::UnsupportedRelaxed { .. }) = res);
+        }
+    }
+
+    #[cfg(feature = "rusqlite")]
+    mod sqlite {
+        use super::*;
+        use crate::rusqlite::storage::SqliteStorage;
+        use assert2::assert;
+
+        fn sqlite(path: &std::path::Path) -> ActorHandle {
+            ActorHandle::new(SqliteStorage::open(path).unwrap()).unwrap()
+        }
+
+        #[tokio::test]
+        async fn it_serves_the_same_api_from_a_file() {
+            let path = std::env::temp_dir().join(format!("{}.db", Id::new()));
+            let handle = sqlite(&path);
+            put_messages(&handle, "account-1", 3).await;
+            put_messages(&handle, "account-2", 2).await;
+            let read = GetMessages::default().in_category("account");
+            let page = handle.fetch_page(read.with_limit(4)).await.unwrap();
+            assert!(page.messages.len() == 4);
+            let rest = handle.fetch_page(GetMessages::resume(page.cursor));
+            assert!(rest.await.unwrap().messages.len() == 1);
+            let info = handle.stream_info("account-1").await.unwrap().unwrap();
+            assert!(info.position == StreamPos::Sequential(2));
+            handle.shutdown().await.unwrap();
+
+            let handle = sqlite(&path);
+            let consumer = ConsumerId::new("projector");
+            handle.save_position(&consumer, 4).await.unwrap();
+            assert!(handle.load_position(&consumer).await.unwrap() == Some(4));
+            let info = handle.stream_info("account-2").await.unwrap().unwrap();
+            assert!(info.count == 2);
+        }
+
+        #[tokio::test]
+        async fn a_retried_write_returns_the_original_position() {
+            let path = std::env::temp_dir().join(format!("{}.db", Id::new()));
+            assert_writes_once(&sqlite(&path)).await;
+        }
+
+        #[tokio::test]
+        async fn a_category_holds_the_streams_named_for_it() {
+            let path = std::env::temp_dir().join(format!("{}.db", Id::new()));
+            assert_reads_categories(&sqlite(&path)).await;
+        }
+
+        #[tokio::test]
+        async fn subscribers_catch_up_and_follow_writes() {
+            let path = std::env::temp_dir().join(format!("{}.db", Id::new()));
+            let handle = sqlite(&path);
+            put_messages(&handle, "stream1", 2).await;
+            let mut sub = Box::pin(
+                handle.subscribe(0, SubscribeFilter::in_stream("stream1")),
+            );
+            for global in 1..=2 {
+                let msg = sub.next().await.unwrap().unwrap();
+                assert!(msg.global_position == global);
             }
+            put_messages(&handle, "stream2", 1).await;
+            put_messages(&handle, "stream1", 1).await;
+            let msg = sub.next().await.unwrap().unwrap();
+            assert!(msg.global_position == 4);
         }
     }
 }
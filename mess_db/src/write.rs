@@ -2,7 +2,55 @@ use std::borrow::Cow;
 
 use ident::Id;
 
-use crate::StreamPos;
+use crate::{
+    error::{Error, Result},
+    StreamPos,
+};
+
+/// What a write expects of its stream before appending to it.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub enum ExpectedVersion {
+    /// Append whatever the stream's position, creating it if need be.
+    Any,
+    /// The stream must not have any messages yet.
+    NoStream,
+    /// The stream must already have at least one message.
+    StreamExists,
+    /// The stream's last message must be at exactly this position.
+    Exact(StreamPos),
+}
+
+impl ExpectedVersion {
+    /// Returns the position of the next message in a stream whose last
+    /// message is at `current`, or an error if the expectation doesn't hold.
+    pub fn next_position(
+        self,
+        stream: &str,
+        current: Option<StreamPos>,
+    ) -> Result<StreamPos> {
+        match (self, current) {
+            (Self::Any | Self::NoStream, None) => Ok(StreamPos::Sequential(0)),
+            (Self::Any | Self::StreamExists, Some(pos)) => Ok(pos.next()),
+            (Self::Exact(expected), Some(pos)) if expected == pos => {
+                Ok(pos.next())
+            }
+            (expected, got) => Err(Error::WrongStreamPosition {
+                stream: stream.to_owned(),
+                expected,
+                got,
+            }),
+        }
+    }
+}
+
+/// `None` expects a new stream, as writes did before [`ExpectedVersion`].
+impl From<Option<StreamPos>> for ExpectedVersion {
+    fn from(value: Option<StreamPos>) -> Self {
+        value.map_or(Self::NoStream, Self::Exact)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct WriteMessageOld<'a, D, M> {
@@ -11,7 +59,7 @@ pub struct WriteMessageOld<'a, D, M> {
     pub message_type: Cow<'a, str>,
     pub data: D,
     pub metadata: Option<M>,
-    pub expected_stream_position: Option<StreamPos>,
+    pub expected_version: ExpectedVersion,
 }
 
 #[derive(Clone, Debug)]
@@ -21,7 +69,7 @@ pub struct WriteMessage<'a> {
     pub message_type: Cow<'a, str>,
     pub data: Cow<'a, [u8]>,
     pub metadata: Cow<'a, [u8]>,
-    pub expected_stream_position: Option<StreamPos>,
+    pub expected_version: ExpectedVersion,
 }
 
 #[derive(Clone, Debug)]
@@ -31,7 +79,7 @@ pub struct OwnedWriteMessage {
     pub message_type: String,
     pub data: Vec<u8>,
     pub metadata: Vec<u8>,
-    pub expected_stream_position: Option<StreamPos>,
+    pub expected_version: ExpectedVersion,
 }
 
 impl From<WriteMessage<'_>> for OwnedWriteMessage {
@@ -42,7 +90,7 @@ impl From<WriteMessage<'_>> for OwnedWriteMessage {
             message_type: msg.message_type.to_string(),
             data: msg.data.to_vec(),
             metadata: msg.metadata.to_vec(),
-            expected_stream_position: msg.expected_stream_position,
+            expected_version: msg.expected_version,
         }
     }
 }
@@ -55,7 +103,7 @@ impl From<OwnedWriteMessage> for WriteMessage<'_> {
             message_type: msg.message_type.into(),
             data: msg.data.into(),
             metadata: msg.metadata.into(),
-            expected_stream_position: msg.expected_stream_position,
+            expected_version: msg.expected_version,
         }
     }
 }
@@ -67,7 +115,7 @@ pub struct WriteSerialMessage<'a> {
     pub message_type: Cow<'a, str>,
     pub data: Cow<'a, [u8]>,
     pub metadata: Cow<'a, [u8]>,
-    pub expected_position: Option<StreamPos>,
+    pub expected_version: ExpectedVersion,
 }
 
 impl<'a> From<WriteMessage<'a>> for WriteSerialMessage<'a> {
@@ -78,7 +126,39 @@ impl<'a> From<WriteMessage<'a>> for WriteSerialMessage<'a> {
             message_type: msg.message_type,
             data: msg.data,
             metadata: msg.metadata,
-            expected_position: msg.expected_stream_position,
+            expected_version: msg.expected_version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_expected_version {
+    use super::*;
+    use assert2::assert;
+    use rstest::*;
+
+    use ExpectedVersion::*;
+    use StreamPos::Sequential;
+
+    #[rstest]
+    #[case(Any, None, Some(Sequential(0)))]
+    #[case(Any, Some(Sequential(3)), Some(Sequential(4)))]
+    #[case(NoStream, None, Some(Sequential(0)))]
+    #[case(NoStream, Some(Sequential(0)), None)]
+    #[case(StreamExists, None, None)]
+    #[case(StreamExists, Some(Sequential(3)), Some(Sequential(4)))]
+    #[case(Exact(Sequential(3)), Some(Sequential(3)), Some(Sequential(4)))]
+    #[case(Exact(Sequential(3)), Some(Sequential(4)), None)]
+    #[case(Exact(Sequential(0)), None, None)]
+    fn it_checks_the_current_position(
+        #[case] expected: ExpectedVersion,
+        #[case] current: Option<StreamPos>,
+        #[case] next: Option<StreamPos>,
+    ) {
+        let res = expected.next_position("stream1", current);
+        match next {
+            Some(next) => assert!(res.unwrap() == next),
+            None => assert!(let Err(Error::WrongStreamPosition { .. }) = res),
         }
     }
 }
@@ -9,20 +9,34 @@ pub enum Error<'a> {
     #[error(transparent)]
     DBError(#[from] mess_db::error::Error),
     #[error("{0}")]
-    External(Box<dyn DisplayErr + 'a>),
+    External(Box<dyn DisplayErr + Send + Sync + 'a>),
     #[error("{0}")]
     ExternalString(String),
+    #[error("projection stopped before reaching global position {0}")]
+    Stopped(u64),
+    /// A failure which may succeed if tried again, such as a timeout.
+    #[error("transient: {0}")]
+    Transient(String),
 }
 
 impl<'a> Error<'a> {
     #[inline]
-    pub fn external(err: impl DisplayErr + 'a) -> Self {
+    pub fn external(err: impl DisplayErr + Send + Sync + 'a) -> Self {
         Self::External(Box::new(err))
     }
     #[inline]
     pub fn external_to_string(err: impl std::fmt::Display) -> Self {
         Self::ExternalString(err.to_string())
     }
+    #[inline]
+    pub fn transient(err: impl std::fmt::Display) -> Self {
+        Self::Transient(err.to_string())
+    }
+    #[inline]
+    #[must_use]
+    pub const fn is_transient(&self) -> bool {
+        matches!(self, Self::Transient(_))
+    }
 }
 
 pub type Result<'a, T> = core::result::Result<T, Error<'a>>;
@@ -5,6 +5,7 @@
 )]
 
 pub mod error;
+pub mod projection;
 pub mod streams;
 
 use std::{
@@ -14,8 +15,10 @@ use std::{
 
 use crate::error::Error;
 use ident::Id;
+pub use mess_db::write::ExpectedVersion;
 use mess_db::{
-    svc::ActorHandle, write::WriteMessage, Message, Position, StreamPos,
+    store::MessageStore, svc::ActorHandle, write::WriteMessage, Message,
+    Position, StreamPos,
 };
 use parking_lot::RwLock;
 use quick_cache::sync::Cache;
@@ -79,6 +82,42 @@ impl From<StreamPos> for Version {
     }
 }
 
+/// Expect the stream to be at exactly this version.
+impl From<Version> for ExpectedVersion {
+    #[inline]
+    fn from(value: Version) -> Self {
+        Self::Exact(value.into())
+    }
+}
+
+/// What [`EventDB::put`] expects of the stream: an [`ExpectedVersion`], a
+/// [`Version`] to expect exactly, or `Option<Version>`, where `None` expects
+/// no stream.
+pub trait IntoExpectedVersion {
+    fn into_expected_version(self) -> ExpectedVersion;
+}
+
+impl IntoExpectedVersion for ExpectedVersion {
+    #[inline]
+    fn into_expected_version(self) -> ExpectedVersion {
+        self
+    }
+}
+
+impl IntoExpectedVersion for Version {
+    #[inline]
+    fn into_expected_version(self) -> ExpectedVersion {
+        self.into()
+    }
+}
+
+impl IntoExpectedVersion for Option<Version> {
+    #[inline]
+    fn into_expected_version(self) -> ExpectedVersion {
+        self.map_or(ExpectedVersion::NoStream, Into::into)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Component<Data> {
     entity: Entity,
@@ -186,30 +225,52 @@ impl<Data> Deref for ComponentCache<Data> {
     }
 }
 
-pub struct EventDB {
-    db_actor: ActorHandle,
+/// Events over a [`MessageStore`]: the embedded [`ActorHandle`] by default,
+/// or a remote store such as a `mess_client::Client`.
+pub struct EventDB<S = ActorHandle> {
+    store: S,
 }
 
-impl EventDB {
+impl<S: MessageStore> EventDB<S> {
     #[must_use]
     #[inline]
-    pub fn new(db_actor: ActorHandle) -> Self {
-        Self { db_actor }
+    pub const fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    /// Return the stream's current version, which a write appending to it
+    /// should expect, or `None` if the stream has no messages. The stream is
+    /// not replayed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the database can't be read.
+    #[inline]
+    pub async fn version(
+        &self,
+        stream_name: &str,
+    ) -> Result<Option<Version>, Error> {
+        let info = self.store.stream_info(stream_name).await?;
+        Ok(info.map(|info| info.position.into()))
     }
 
-    /// Write the given event as a message in the database.
+    /// Write the given event as a message in the database, if the stream is
+    /// as `expected_version` expects. A [`Version`] expects exactly that
+    /// version, and `None` expects no stream.
     ///
     /// # Errors
     ///
     /// This function will return an error if there is an error in serializing
-    /// the data or metadata of the event.
+    /// the data or metadata of the event, or if the stream's version is not
+    /// as expected.
     #[inline]
     pub async fn put(
         &self,
         stream_name: &str,
         event: &impl Event,
-        expected_version: Option<Version>,
+        expected_version: impl IntoExpectedVersion,
     ) -> Result<Position, Error> {
+        let expected_version = expected_version.into_expected_version();
         // let msg = event.into();
         debug!(stream_name, ?expected_version, "putting message");
         let stream_name = stream_name.to_string().into();
@@ -221,9 +282,9 @@ impl EventDB {
             message_type: event.name(),
             data,
             metadata,
-            expected_stream_position: expected_version.map(Into::into),
+            expected_version,
         };
-        let put_res = self.db_actor.put_message(req);
+        let put_res = self.store.put_message(req);
         put_res.await.map_err(Error::from)
         // Ok(Position { global: 0, stream: StreamPos::Sequential(0) })
     }
@@ -235,7 +296,11 @@ pub struct ComponentStore<Data, Db> {
     event_db: Db,
 }
 
-impl<Data, Db: AsRef<EventDB>> ComponentStore<Data, Db> {
+impl<Data, Db, S> ComponentStore<Data, Db>
+where
+    Db: Deref<Target = EventDB<S>>,
+    S: MessageStore,
+{
     #[must_use]
     #[inline]
     // pub fn new(db: Arc<EventDB<Conn>>) -> Self {
@@ -261,7 +326,7 @@ impl<Data, Db: AsRef<EventDB>> ComponentStore<Data, Db> {
             return Ok(Component { entity, data: cached });
         }
         let mut comp = Component::<Data>::new(entity);
-        let fetch = self.event_db.as_ref().db_actor.fetch_messages(
+        let fetch = self.event_db.store.fetch_messages(
             mess_db::read::GetMessages::default().in_stream(stream_name),
         );
         let messages = fetch.await?;
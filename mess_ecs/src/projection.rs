@@ -0,0 +1,602 @@
+//! Projections keep a read model up to date by handling every message in
+//! one or more categories, in global order.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use mess_db::{
+    position::{Checkpoint, ConsumerId},
+    store::{MessageStore, PositionStore},
+    svc::{ActorHandle, SubscribeFilter, SubscriptionEvent},
+    Message,
+};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
+
+use crate::error::{Error, Result};
+pub use mess_db::retry::Retry;
+
+pub trait Projection {
+    /// Apply a single message to the projection.
+    ///
+    /// # Errors
+    ///
+    /// Return [`Error::Transient`] for failures worth retrying; the runner
+    /// stops on any other error.
+    fn handle(&mut self, message: &Message<'_>) -> Result<'static, ()>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum State {
+    #[default]
+    Starting,
+    Running,
+    Retrying,
+    Stopped,
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Status {
+    pub state: State,
+    /// The global position of the last message the projection handled.
+    pub last_position: Option<u64>,
+    /// The global position the projection has read up to, counting the
+    /// messages it doesn't handle.
+    pub scanned: Option<u64>,
+    /// How many messages were handled since the runner started.
+    pub processed: u64,
+    /// How many times a transient failure was retried.
+    pub retries: u64,
+}
+
+/// Watches and stops a running projection.
+#[derive(Clone, Debug)]
+pub struct ProjectionHandle {
+    status: watch::Receiver<Status>,
+    token: CancellationToken,
+}
+
+impl ProjectionHandle {
+    #[must_use]
+    #[inline]
+    pub fn status(&self) -> Status {
+        self.status.borrow().clone()
+    }
+
+    #[inline]
+    pub fn stop(&self) {
+        self.token.cancel();
+    }
+
+    /// Waits until the projection has read past `global_pos`, having
+    /// handled the message there if it is one the projection handles.
+    ///
+    /// # Errors
+    ///
+    /// Returns a timeout error if the projection does not get there in time,
+    /// or [`Error::Stopped`] if it stops first.
+    #[inline]
+    pub async fn wait_for_global(
+        &self,
+        global_pos: u64,
+        timeout: Duration,
+    ) -> Result<'static, ()> {
+        let mut status = self.status.clone();
+        let wait = status.wait_for(|status| {
+            status.scanned >= Some(global_pos)
+                || matches!(status.state, State::Stopped | State::Failed(_))
+        });
+        let reached = tokio::time::timeout(timeout, wait)
+            .await
+            .map(|res| res.map(|status| status.scanned >= Some(global_pos)));
+        match reached {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false) | Err(_)) => Err(Error::Stopped(global_pos)),
+            Err(_) => Err(mess_db::error::Error::Timeout { global_pos }.into()),
+        }
+    }
+}
+
+/// Runs a projection on any store which also keeps positions, such as the
+/// embedded [`ActorHandle`].
+pub struct ProjectionRunner<P, S = ActorHandle> {
+    db: S,
+    name: String,
+    projection: P,
+    categories: Vec<String>,
+    save_interval: u64,
+    retry: Retry,
+    token: CancellationToken,
+    status: watch::Sender<Status>,
+}
+
+impl<P, S> ProjectionRunner<P, S>
+where
+    P: Projection,
+    S: MessageStore + PositionStore,
+{
+    /// Creates a runner which stores its position under `name`.
+    #[must_use]
+    #[inline]
+    pub fn new(db: S, name: &str, projection: P) -> Self {
+        Self {
+            db,
+            name: name.to_owned(),
+            projection,
+            categories: Vec::new(),
+            save_interval: 100,
+            retry: Retry::default(),
+            token: CancellationToken::new(),
+            status: watch::Sender::new(Status::default()),
+        }
+    }
+
+    /// Handle the messages of these categories. Without any, the projection
+    /// handles every message.
+    #[must_use]
+    #[inline]
+    pub fn categories(mut self, categories: &[impl AsRef<str>]) -> Self {
+        self.categories =
+            categories.iter().map(|c| c.as_ref().to_owned()).collect();
+        self
+    }
+
+    /// Save the position every `save_interval` messages. The position is
+    /// also saved when the runner stops.
+    #[must_use]
+    #[inline]
+    pub const fn save_interval(mut self, save_interval: u64) -> Self {
+        self.save_interval = save_interval;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Stop the runner when `token` is cancelled, such as a child of the
+    /// application's shutdown token.
+    #[must_use]
+    #[inline]
+    pub fn with_token(mut self, token: CancellationToken) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Returns the token which stops the runner when cancelled.
+    #[must_use]
+    #[inline]
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn status(&self) -> watch::Receiver<Status> {
+        self.status.subscribe()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn handle(&self) -> ProjectionHandle {
+        ProjectionHandle { status: self.status(), token: self.token() }
+    }
+
+    fn filter(&self) -> SubscribeFilter {
+        if self.categories.is_empty() {
+            SubscribeFilter::all()
+        } else {
+            SubscribeFilter::in_categories(&self.categories)
+        }
+    }
+
+    /// Catches up from the saved position, then handles messages as they
+    /// are written until the token is cancelled or the database stops.
+    ///
+    /// # Errors
+    ///
+    /// Returns the projection's error when it fails with something other
+    /// than a transient error, or once retries run out.
+    #[inline]
+    pub async fn run(mut self) -> Result<'static, Status> {
+        let consumer = ConsumerId::new(&self.name);
+        let mut checkpoint =
+            self.db.load_checkpoint(consumer, self.save_interval).await?;
+        self.status.send_modify(|status| {
+            status.state = State::Running;
+            status.last_position = checkpoint.position();
+            status.scanned = checkpoint.position();
+        });
+
+        let res = self.follow(&mut checkpoint).await;
+        let res = match (res, checkpoint.unsaved()) {
+            (res, Some(pos)) => {
+                let saved =
+                    self.db.save_position(checkpoint.consumer(), pos).await;
+                res.and(saved.map_err(Error::from))
+            }
+            (res, None) => res,
+        };
+        self.status.send_modify(|status| {
+            status.state = match &res {
+                Ok(()) => State::Stopped,
+                Err(err) => State::Failed(err.to_string()),
+            };
+        });
+        match res {
+            Ok(()) => Ok(self.status.borrow().clone()),
+            Err(err) => {
+                error!(name = self.name, ?err, "projection failed");
+                Err(err)
+            }
+        }
+    }
+
+    /// Subscribes from the checkpoint, subscribing again after read errors.
+    async fn follow(
+        &mut self,
+        checkpoint: &mut Checkpoint,
+    ) -> Result<'static, ()> {
+        let mut attempt = 0;
+        loop {
+            let mut messages = Box::pin(
+                self.db
+                    .subscribe_with_progress(checkpoint.next(), self.filter()),
+            );
+            let err = loop {
+                let next = tokio::select! {
+                    () = self.token.cancelled() => return Ok(()),
+                    next = messages.next() => next,
+                };
+                match next {
+                    None | Some(Err(mess_db::error::Error::Cancelled)) => {
+                        return Ok(());
+                    }
+                    Some(Err(err)) => break err,
+                    Some(Ok(SubscriptionEvent::Message(msg))) => {
+                        if !self.apply(msg.into(), checkpoint).await? {
+                            return Ok(());
+                        }
+                        attempt = 0;
+                    }
+                    Some(Ok(SubscriptionEvent::Scanned(pos))) => {
+                        self.status.send_if_modified(|status| {
+                            let scanned = status.scanned.max(Some(pos));
+                            let modified = status.scanned != scanned;
+                            status.scanned = scanned;
+                            modified
+                        });
+                    }
+                }
+            };
+            attempt += 1;
+            if attempt > self.retry.max_retries {
+                return Err(err.into());
+            }
+            warn!(name = self.name, ?err, attempt, "resubscribing");
+            if !self.back_off(attempt).await {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handles the message, retrying transient failures, and records it in
+    /// the checkpoint. Returns false if the runner was stopped meanwhile.
+    async fn apply(
+        &mut self,
+        msg: Message<'_>,
+        checkpoint: &mut Checkpoint,
+    ) -> Result<'static, bool> {
+        let mut attempt = 0;
+        while let Err(err) = self.projection.handle(&msg) {
+            attempt += 1;
+            if !err.is_transient() || attempt > self.retry.max_retries {
+                return Err(err);
+            }
+            warn!(name = self.name, ?err, attempt, "retrying message");
+            if !self.back_off(attempt).await {
+                return Ok(false);
+            }
+        }
+        self.db.checkpoint(checkpoint, msg.global_position).await?;
+        self.status.send_modify(|status| {
+            status.state = State::Running;
+            status.last_position = Some(msg.global_position);
+            status.scanned = Some(msg.global_position);
+            status.processed += 1;
+        });
+        debug!(name = self.name, pos = msg.global_position, "handled");
+        Ok(true)
+    }
+
+    /// Waits out the backoff for `attempt`. Returns false if the runner was
+    /// stopped meanwhile.
+    async fn back_off(&self, attempt: u32) -> bool {
+        self.status.send_modify(|status| {
+            status.state = State::Retrying;
+            status.retries += 1;
+        });
+        tokio::select! {
+            () = tokio::time::sleep(self.retry.backoff(attempt)) => true,
+            () = self.token.cancelled() => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{borrow::Cow, collections::HashMap, future::Future, sync::Arc};
+
+    use assert2::assert;
+    use futures::Stream;
+    use ident::Id;
+    use mess_db::{
+        read::IntoCursor,
+        rocks::{db::DB, storage::RocksStorage},
+        svc::Page,
+        write::{ExpectedVersion, WriteMessage},
+        OwnedMessage, Position, StreamInfo,
+    };
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        seen: Arc<Mutex<Vec<String>>>,
+        // Fail this many times with the given error before succeeding.
+        failures: Arc<Mutex<u32>>,
+        transient: bool,
+    }
+
+    impl Projection for Recorder {
+        fn handle(&mut self, message: &Message<'_>) -> Result<'static, ()> {
+            let mut failures = self.failures.lock();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(if self.transient {
+                    Error::transient("not yet")
+                } else {
+                    Error::external_to_string("broken")
+                });
+            }
+            self.seen.lock().push(message.stream_name.to_string());
+            Ok(())
+        }
+    }
+
+    /// Reads and writes through the handle but keeps positions to itself.
+    #[derive(Clone)]
+    struct OwnPositions {
+        db: ActorHandle,
+        positions: Arc<Mutex<HashMap<ConsumerId, u64>>>,
+    }
+
+    impl MessageStore for OwnPositions {
+        fn put_message(
+            &self,
+            wm: WriteMessage<'_>,
+        ) -> impl Future<Output = mess_db::error::Result<Position>> + Send
+        {
+            self.db.put_message(wm)
+        }
+
+        fn fetch_messages(
+            &self,
+            read: impl IntoCursor + Send,
+        ) -> impl Future<
+            Output = mess_db::error::Result<
+                Vec<mess_db::error::Result<OwnedMessage>>,
+            >,
+        > + Send {
+            self.db.fetch_messages(read)
+        }
+
+        fn fetch_page(
+            &self,
+            read: impl IntoCursor + Send,
+        ) -> impl Future<Output = mess_db::error::Result<Page>> + Send {
+            self.db.fetch_page(read)
+        }
+
+        fn stream_info(
+            &self,
+            stream: &str,
+        ) -> impl Future<Output = mess_db::error::Result<Option<StreamInfo>>> + Send
+        {
+            self.db.stream_info(stream)
+        }
+
+        fn stream_messages(
+            &self,
+            read: impl IntoCursor,
+        ) -> impl Stream<Item = mess_db::error::Result<OwnedMessage>> + Send + 'static
+        {
+            self.db.stream_messages(read)
+        }
+
+        fn subscribe(
+            &self,
+            from_global: u64,
+            filter: SubscribeFilter,
+        ) -> impl Stream<Item = mess_db::error::Result<OwnedMessage>> + Send + 'static
+        {
+            self.db.subscribe(from_global, filter)
+        }
+    }
+
+    impl PositionStore for OwnPositions {
+        async fn load_position(
+            &self,
+            consumer: &ConsumerId,
+        ) -> mess_db::error::Result<Option<u64>> {
+            Ok(self.positions.lock().get(consumer).copied())
+        }
+
+        async fn save_position(
+            &self,
+            consumer: &ConsumerId,
+            global_pos: u64,
+        ) -> mess_db::error::Result<()> {
+            self.positions.lock().insert(consumer.clone(), global_pos);
+            Ok(())
+        }
+    }
+
+    fn new_handle() -> ActorHandle {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        ActorHandle::new(RocksStorage::new(DB::new(path).unwrap())).unwrap()
+    }
+
+    async fn put(db: &ActorHandle, stream: &str) {
+        db.put_message(WriteMessage {
+            id: Id::new(),
+            stream_name: stream.into(),
+            message_type: "someMsgType".into(),
+            data: Cow::Borrowed(b"{}"),
+            metadata: Cow::Borrowed(b""),
+            expected_version: ExpectedVersion::Any,
+        })
+        .await
+        .unwrap();
+    }
+
+    async fn wait_for(
+        status: &mut watch::Receiver<Status>,
+        f: impl FnMut(&Status) -> bool,
+    ) {
+        tokio::time::timeout(Duration::from_secs(5), status.wait_for(f))
+            .await
+            .expect("projection stalled")
+            .unwrap();
+    }
+
+    const fn quick_retry() -> Retry {
+        Retry {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_projects_categories_and_resumes_from_its_checkpoint() {
+        let db = new_handle();
+        put(&db, "a-1").await;
+        put(&db, "other-1").await;
+        let recorder = Recorder::default();
+        let runner = ProjectionRunner::new(db.clone(), "p", recorder.clone())
+            .categories(&["a", "b"])
+            .save_interval(10);
+        let (mut status, token) = (runner.status(), runner.token());
+        let task = tokio::spawn(runner.run());
+        put(&db, "b-1").await;
+        wait_for(&mut status, |s| s.processed == 2).await;
+        token.cancel();
+        let status = task.await.unwrap().unwrap();
+        assert!(status.state == State::Stopped);
+        assert!(status.last_position == Some(3));
+        assert!(*recorder.seen.lock() == ["a-1", "b-1"]);
+
+        // Stopping saved the position even though the interval wasn't due.
+        put(&db, "a-2").await;
+        let runner = ProjectionRunner::new(db.clone(), "p", recorder.clone())
+            .categories(&["a", "b"]);
+        let (mut status, token) = (runner.status(), runner.token());
+        let task = tokio::spawn(runner.run());
+        wait_for(&mut status, |s| s.processed == 1).await;
+        token.cancel();
+        task.await.unwrap().unwrap();
+        assert!(*recorder.seen.lock() == ["a-1", "b-1", "a-2"]);
+    }
+
+    #[tokio::test]
+    async fn it_retries_transient_failures() {
+        let db = new_handle();
+        put(&db, "a-1").await;
+        let recorder = Recorder { transient: true, ..Recorder::default() };
+        *recorder.failures.lock() = 2;
+        let runner = ProjectionRunner::new(db.clone(), "p", recorder.clone())
+            .retry(quick_retry());
+        let (mut status, token) = (runner.status(), runner.token());
+        let task = tokio::spawn(runner.run());
+        wait_for(&mut status, |s| s.processed == 1).await;
+        token.cancel();
+        let status = task.await.unwrap().unwrap();
+        assert!(status.retries == 2);
+        assert!(*recorder.seen.lock() == ["a-1"]);
+    }
+
+    #[tokio::test]
+    async fn it_stops_on_other_failures() {
+        let db = new_handle();
+        put(&db, "a-1").await;
+        put(&db, "a-2").await;
+        let recorder = Recorder::default();
+        let runner = ProjectionRunner::new(db.clone(), "p", recorder.clone())
+            .retry(quick_retry());
+        let mut status = runner.status();
+        let task = tokio::spawn(runner.run());
+        wait_for(&mut status, |s| s.processed == 2).await;
+        *recorder.failures.lock() = 1;
+        put(&db, "a-3").await;
+        assert!(task.await.unwrap().is_err());
+        let status = status.borrow().clone();
+        assert!(status.state == State::Failed("broken".to_owned()));
+        // The messages handled before the failure are not handled again.
+        let consumer = ConsumerId::new("p");
+        assert!(db.load_position(&consumer).await.unwrap() == Some(2));
+    }
+
+    #[tokio::test]
+    async fn waiting_resolves_once_the_position_is_projected() {
+        let db = new_handle();
+        let recorder = Recorder::default();
+        let runner = ProjectionRunner::new(db.clone(), "p", recorder.clone())
+            .categories(&["a"]);
+        let projection = runner.handle();
+        let task = tokio::spawn(runner.run());
+        put(&db, "a-1").await;
+        let timeout = Duration::from_secs(5);
+        projection.wait_for_global(1, timeout).await.unwrap();
+        assert!(*recorder.seen.lock() == ["a-1"]);
+
+        let res =
+            projection.wait_for_global(2, Duration::from_millis(10)).await;
+        assert!(let Err(Error::DBError(mess_db::error::Error::Timeout { .. })) = res);
+
+        // Positions the projection doesn't handle are waited for too.
+        put(&db, "other-1").await;
+        projection.wait_for_global(2, timeout).await.unwrap();
+        assert!(*recorder.seen.lock() == ["a-1"]);
+
+        projection.stop();
+        task.await.unwrap().unwrap();
+        let res = projection.wait_for_global(3, timeout).await;
+        assert!(let Err(Error::Stopped(3)) = res);
+    }
+
+    #[tokio::test]
+    async fn it_runs_on_any_store_which_keeps_positions() {
+        let db = new_handle();
+        let store = OwnPositions { db: db.clone(), positions: Arc::default() };
+        put(&db, "a-1").await;
+        let recorder = Recorder::default();
+        let runner =
+            ProjectionRunner::new(store.clone(), "p", recorder.clone());
+        let (mut status, token) = (runner.status(), runner.token());
+        let task = tokio::spawn(runner.run());
+        wait_for(&mut status, |s| s.processed == 1).await;
+        token.cancel();
+        task.await.unwrap().unwrap();
+        assert!(*recorder.seen.lock() == ["a-1"]);
+        let consumer = ConsumerId::new("p");
+        assert!(store.positions.lock().get(&consumer) == Some(&1));
+        assert!(db.load_position(&consumer).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,447 @@
+//! Translations between the generated types and those of [`mess_db`].
+//!
+//! Infallible ones are `From` impls. Those which can be handed something no
+//! store accepts are functions returning the [`Error`] to answer with.
+
+use std::borrow::Cow;
+
+use ident::Id;
+use mess_db::{
+    clock::Tick,
+    error::{Error, Result},
+    read::{
+        Cursor, GetMessages, IntoCursor, OptGlobalPos, OptStreamPos,
+        OptTimeRange, Unset,
+    },
+    svc::SubscribeFilter,
+    write::{ExpectedVersion, WriteMessage},
+    OwnedMessage, Position, StreamInfo, StreamPos,
+};
+
+use crate::pb;
+
+impl From<StreamPos> for pb::StreamPosition {
+    fn from(pos: StreamPos) -> Self {
+        Self {
+            position: pos.position(),
+            relaxed: matches!(pos, StreamPos::Relaxed(_)),
+        }
+    }
+}
+
+impl From<pb::StreamPosition> for StreamPos {
+    fn from(pos: pb::StreamPosition) -> Self {
+        match pos.relaxed {
+            true => Self::Relaxed(pos.position),
+            false => Self::Sequential(pos.position),
+        }
+    }
+}
+
+/// Reads a position the sender left out as the start of a stream.
+fn stream_pos(pos: Option<pb::StreamPosition>) -> StreamPos {
+    pos.unwrap_or_default().into()
+}
+
+impl From<Position> for pb::Position {
+    fn from(pos: Position) -> Self {
+        Self {
+            global_position: pos.global,
+            stream_position: Some(pos.stream.into()),
+        }
+    }
+}
+
+impl From<pb::Position> for Position {
+    fn from(pos: pb::Position) -> Self {
+        Self::new(pos.global_position, stream_pos(pos.stream_position))
+    }
+}
+
+impl From<ExpectedVersion> for pb::ExpectedVersion {
+    fn from(version: ExpectedVersion) -> Self {
+        use pb::expected_version::Kind;
+
+        let (kind, position) = match version {
+            ExpectedVersion::Any => (Kind::Any, None),
+            ExpectedVersion::NoStream => (Kind::NoStream, None),
+            ExpectedVersion::StreamExists => (Kind::StreamExists, None),
+            ExpectedVersion::Exact(pos) => (Kind::Exact, Some(pos.into())),
+        };
+        Self { kind: kind.into(), position }
+    }
+}
+
+/// Returns the expected version, which is [`ExpectedVersion::Any`] when the
+/// sender left it out.
+///
+/// # Errors
+///
+/// Fails on an unknown kind, or an exact one without its position.
+pub fn expected_version(
+    version: Option<pb::ExpectedVersion>,
+) -> Result<ExpectedVersion> {
+    use pb::expected_version::Kind;
+
+    let Some(version) = version else {
+        return Ok(ExpectedVersion::Any);
+    };
+    let kind = Kind::try_from(version.kind).map_err(|_| {
+        Error::DeserError(format!("unknown expected version {}", version.kind))
+    })?;
+    match (kind, version.position) {
+        (Kind::Any, _) => Ok(ExpectedVersion::Any),
+        (Kind::NoStream, _) => Ok(ExpectedVersion::NoStream),
+        (Kind::StreamExists, _) => Ok(ExpectedVersion::StreamExists),
+        (Kind::Exact, Some(pos)) => Ok(ExpectedVersion::Exact(pos.into())),
+        (Kind::Exact, None) => Err(Error::DeserError(
+            "an exact expected version needs its position".into(),
+        )),
+    }
+}
+
+impl From<WriteMessage<'_>> for pb::WriteMessage {
+    fn from(msg: WriteMessage<'_>) -> Self {
+        Self {
+            id: msg.id.to_string(),
+            stream_name: msg.stream_name.into_owned(),
+            message_type: msg.message_type.into_owned(),
+            data: msg.data.into_owned(),
+            metadata: msg.metadata.into_owned(),
+            expected_version: Some(msg.expected_version.into()),
+        }
+    }
+}
+
+/// Returns the message an append writes.
+///
+/// # Errors
+///
+/// Fails if the request has no message, or on an invalid one.
+pub fn append_message(req: pb::AppendRequest) -> Result<WriteMessage<'static>> {
+    let msg = req
+        .message
+        .ok_or_else(|| Error::DeserError("an append needs a message".into()))?;
+    write_message(msg)
+}
+
+/// Returns the message to write, with a new id if it came without one.
+///
+/// # Errors
+///
+/// Fails on an id which doesn't parse or an invalid expected version.
+pub fn write_message(msg: pb::WriteMessage) -> Result<WriteMessage<'static>> {
+    let id = match msg.id.as_str() {
+        "" => Id::new(),
+        id => id
+            .parse()
+            .map_err(|_| Error::DeserError(format!("invalid id {id}")))?,
+    };
+    Ok(WriteMessage {
+        id,
+        stream_name: Cow::Owned(msg.stream_name),
+        message_type: Cow::Owned(msg.message_type),
+        data: Cow::Owned(msg.data),
+        metadata: Cow::Owned(msg.metadata),
+        expected_version: expected_version(msg.expected_version)?,
+    })
+}
+
+impl From<OwnedMessage> for pb::Message {
+    fn from(msg: OwnedMessage) -> Self {
+        Self {
+            global_position: msg.global_position,
+            stream_position: Some(msg.stream_position.into()),
+            stream_name: msg.stream_name,
+            message_type: msg.message_type,
+            data: msg.data,
+            metadata: msg.metadata,
+        }
+    }
+}
+
+impl From<pb::Message> for OwnedMessage {
+    fn from(msg: pb::Message) -> Self {
+        Self {
+            global_position: msg.global_position,
+            stream_position: stream_pos(msg.stream_position),
+            stream_name: msg.stream_name,
+            message_type: msg.message_type,
+            data: msg.data,
+            metadata: msg.metadata,
+        }
+    }
+}
+
+impl From<StreamInfo> for pb::StreamInfo {
+    fn from(info: StreamInfo) -> Self {
+        Self {
+            position: Some(info.position.into()),
+            count: info.count,
+            first_global: info.first_global,
+            last_global: info.last_global,
+            first_time: info.first_time.to_u64(),
+            last_time: info.last_time.to_u64(),
+        }
+    }
+}
+
+impl From<pb::StreamInfo> for StreamInfo {
+    fn from(info: pb::StreamInfo) -> Self {
+        Self {
+            position: stream_pos(info.position),
+            count: info.count,
+            first_global: info.first_global,
+            last_global: info.last_global,
+            first_time: Tick::from_u64(info.first_time),
+            last_time: Tick::from_u64(info.last_time),
+        }
+    }
+}
+
+impl From<Cursor> for pb::ReadRequest {
+    /// Any read can be sent as the cursor it starts from.
+    fn from(cursor: Cursor) -> Self {
+        Self { cursor: cursor.to_string(), ..Self::default() }
+    }
+}
+
+/// Returns the cursor a read starts from.
+///
+/// # Errors
+///
+/// Fails with [`Error::InvalidRead`] for a combination of options no read
+/// can honour, and with [`Error::DeserError`] for a cursor which doesn't
+/// parse.
+pub fn read_cursor(req: &pb::ReadRequest) -> Result<Cursor> {
+    if !req.cursor.is_empty() {
+        let only_cursor = pb::ReadRequest {
+            cursor: req.cursor.clone(),
+            ..Default::default()
+        };
+        if *req != only_cursor {
+            return Err(Error::InvalidRead(
+                "a cursor can't be combined with other options",
+            ));
+        }
+        return GetMessages::resume(req.cursor.parse()?).into_cursor();
+    }
+    let read = match req.types.as_slice() {
+        [] => GetMessages::default(),
+        types => GetMessages::default().of_types(types),
+    };
+    let read = match req.limit {
+        0 => read,
+        limit => read.with_limit(limit as usize),
+    };
+    let read = match req.backwards {
+        true => read.backwards(),
+        false => read,
+    };
+    match (&req.stream, &req.category) {
+        (Some(_), Some(_)) => {
+            Err(Error::InvalidRead("read a stream or a category, not both"))
+        }
+        (Some(stream), None) => {
+            if req.correlation.is_some() || req.consumer_group.is_some() {
+                return Err(Error::InvalidRead(
+                    "stream reads take no correlation or consumer group",
+                ));
+            }
+            positioned(req, read.in_stream(stream))
+        }
+        (None, Some(category)) => {
+            let read = read.in_category(category);
+            let read = match &req.correlation {
+                Some(correlation) => read.correlation(correlation),
+                None => read,
+            };
+            let read = match req.consumer_group {
+                Some(group) => read.consumer_group(group.member, group.size)?,
+                None => read,
+            };
+            positioned(req, read)
+        }
+        (None, None) => {
+            if req.consumer_group.is_some() {
+                return Err(Error::InvalidRead(
+                    "only category reads take a consumer group",
+                ));
+            }
+            let read = match &req.correlation {
+                Some(correlation) => read.correlation(correlation),
+                None => read,
+            };
+            positioned(req, read)
+        }
+    }
+}
+
+/// Sets the start positions of a read, whose scope is already set.
+fn positioned<S>(
+    req: &pb::ReadRequest,
+    read: GetMessages<S, Unset, Unset>,
+) -> Result<Cursor>
+where
+    GetMessages<S, Unset, Unset>: IntoCursor,
+    GetMessages<S, Unset, OptStreamPos>: IntoCursor,
+    GetMessages<S, OptGlobalPos, Unset>: IntoCursor,
+    GetMessages<S, OptGlobalPos, OptStreamPos>: IntoCursor,
+    GetMessages<S, OptTimeRange, Unset>: IntoCursor,
+    GetMessages<S, OptTimeRange, OptStreamPos>: IntoCursor,
+{
+    let from_stream = req.from_stream.map(StreamPos::from);
+    let between = req
+        .between
+        .map(|range| (Tick::from_u64(range.start), Tick::from_u64(range.end)));
+    match (req.from_global, between, from_stream) {
+        (Some(_), Some(_), _) => Err(Error::InvalidRead(
+            "start from a global position or a time range, not both",
+        )),
+        (None, None, None) => read.into_cursor(),
+        (None, None, Some(pos)) => read.from_stream_pos(pos).into_cursor(),
+        (Some(global), None, None) => read.from_global(global).into_cursor(),
+        (Some(global), None, Some(pos)) => {
+            read.from_global(global).from_stream_pos(pos).into_cursor()
+        }
+        (None, Some((start, end)), None) => {
+            read.between(start, end).into_cursor()
+        }
+        (None, Some((start, end)), Some(pos)) => {
+            read.between(start, end).from_stream_pos(pos).into_cursor()
+        }
+    }
+}
+
+/// Returns the subscription's filter.
+///
+/// # Errors
+///
+/// Fails if it names both a stream and categories.
+pub fn subscribe_filter(req: &pb::SubscribeRequest) -> Result<SubscribeFilter> {
+    let filter = match (&req.stream, req.categories.as_slice()) {
+        (Some(_), [_, ..]) => {
+            return Err(Error::InvalidRead(
+                "subscribe to a stream or to categories, not both",
+            ))
+        }
+        (Some(stream), []) => SubscribeFilter::in_stream(stream),
+        (None, []) => SubscribeFilter::all(),
+        (None, categories) => SubscribeFilter::in_categories(categories),
+    };
+    Ok(match req.types.as_slice() {
+        [] => filter,
+        types => filter.of_types(types),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+
+    fn request(req: pb::ReadRequest) -> Result<Cursor> {
+        read_cursor(&req)
+    }
+
+    #[test]
+    fn an_empty_request_reads_the_whole_log() {
+        let cursor = request(pb::ReadRequest::default()).unwrap();
+        assert!(cursor == GetMessages::default().into_cursor().unwrap());
+    }
+
+    #[test]
+    fn it_reads_a_category_with_its_filters() {
+        let req = pb::ReadRequest {
+            category: Some("account".into()),
+            from_global: Some(7),
+            limit: 20,
+            types: vec!["Opened".into()],
+            consumer_group: Some(pb::ConsumerGroup { member: 1, size: 2 }),
+            ..Default::default()
+        };
+        let expected = GetMessages::default()
+            .of_types(&["Opened"])
+            .with_limit(20)
+            .in_category("account")
+            .consumer_group(1, 2)
+            .unwrap()
+            .from_global(7)
+            .into_cursor()
+            .unwrap();
+        assert!(request(req).unwrap() == expected);
+    }
+
+    #[test]
+    fn it_reads_a_stream_backwards() {
+        let req = pb::ReadRequest {
+            stream: Some("account-1".into()),
+            from_stream: Some(StreamPos::Sequential(3).into()),
+            backwards: true,
+            ..Default::default()
+        };
+        let expected = GetMessages::default()
+            .backwards()
+            .in_stream("account-1")
+            .from_stream_pos(StreamPos::Sequential(3))
+            .into_cursor()
+            .unwrap();
+        assert!(request(req).unwrap() == expected);
+    }
+
+    #[test]
+    fn a_cursor_round_trips() {
+        let cursor = Cursor::category("account".into(), 4, 10);
+        let req = pb::ReadRequest::from(cursor.clone());
+        assert!(request(req).unwrap() == cursor);
+    }
+
+    #[test]
+    fn it_rejects_reads_no_store_can_honour() {
+        let both = pb::ReadRequest {
+            stream: Some("account-1".into()),
+            category: Some("account".into()),
+            ..Default::default()
+        };
+        assert!(let Err(Error::InvalidRead(_)) = request(both));
+        let cursor_and_limit = pb::ReadRequest {
+            cursor: Cursor::global(None, 0, 10).to_string(),
+            limit: 5,
+            ..Default::default()
+        };
+        assert!(let Err(Error::InvalidRead(_)) = request(cursor_and_limit));
+        let grouped_stream = pb::ReadRequest {
+            stream: Some("account-1".into()),
+            consumer_group: Some(pb::ConsumerGroup { member: 0, size: 1 }),
+            ..Default::default()
+        };
+        assert!(let Err(Error::InvalidRead(_)) = request(grouped_stream));
+    }
+
+    #[test]
+    fn write_messages_round_trip() {
+        let msg = WriteMessage {
+            id: Id::new(),
+            stream_name: "account-1".into(),
+            message_type: "Opened".into(),
+            data: b"{}".as_slice().into(),
+            metadata: Cow::Borrowed(&[]),
+            expected_version: ExpectedVersion::Exact(StreamPos::Relaxed(2)),
+        };
+        let back = write_message(pb::WriteMessage::from(msg.clone())).unwrap();
+        assert!(back.id == msg.id);
+        assert!(back.stream_name == msg.stream_name);
+        assert!(back.data == msg.data);
+        assert!(back.expected_version == msg.expected_version);
+    }
+
+    #[test]
+    fn an_exact_version_needs_its_position() {
+        let version = pb::ExpectedVersion {
+            kind: pb::expected_version::Kind::Exact.into(),
+            position: None,
+        };
+        assert!(let Err(Error::DeserError(_)) = expected_version(Some(version)));
+        assert!(expected_version(None).unwrap() == ExpectedVersion::Any);
+    }
+}
@@ -0,0 +1,18 @@
+//! The mess store as a gRPC service, for services which want typed,
+//! streaming access to a store served by another process.
+//!
+//! [`pb`] holds the types and the client generated from
+//! `proto/mess/v1/mess.proto`, [`MessService`] serves them over an
+//! [`ActorHandle`](mess_db::svc::ActorHandle), and [`convert`] translates
+//! between them and the types of [`mess_db`].
+
+pub mod convert;
+pub mod server;
+pub mod status;
+
+pub use server::MessService;
+
+#[allow(clippy::all, clippy::pedantic, missing_docs)]
+pub mod pb {
+    tonic::include_proto!("mess.v1");
+}
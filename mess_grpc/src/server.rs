@@ -0,0 +1,291 @@
+use futures::{stream::BoxStream, StreamExt};
+use mess_db::{error::Error, svc::ActorHandle};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    convert::{append_message, read_cursor, subscribe_filter},
+    pb::{
+        self, message_store_server::MessageStore,
+        message_store_server::MessageStoreServer,
+    },
+    status::status,
+};
+
+type Messages = BoxStream<'static, Result<pb::Message, Status>>;
+
+/// Serves the store behind an [`ActorHandle`].
+///
+/// ```no_run
+/// # async fn serve(db: mess_db::svc::ActorHandle) -> Result<(), tonic::transport::Error> {
+/// use mess_grpc::MessService;
+///
+/// tonic::transport::Server::builder()
+///     .add_service(MessService::new(db).into_server())
+///     .serve("127.0.0.1:50051".parse().unwrap())
+///     .await
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MessService {
+    db: ActorHandle,
+}
+
+impl MessService {
+    #[must_use]
+    pub const fn new(db: ActorHandle) -> Self {
+        Self { db }
+    }
+
+    #[must_use]
+    pub fn into_server(self) -> MessageStoreServer<Self> {
+        MessageStoreServer::new(self)
+    }
+}
+
+/// Ends the stream after the first error, which is sent as its status.
+fn messages(
+    stream: impl futures::Stream<Item = mess_db::error::Result<mess_db::OwnedMessage>>
+        + Send
+        + 'static,
+) -> Messages {
+    stream
+        .map(|msg| msg.map(pb::Message::from).map_err(|err| status(&err)))
+        .boxed()
+}
+
+#[tonic::async_trait]
+impl MessageStore for MessService {
+    async fn append(
+        &self,
+        req: Request<pb::AppendRequest>,
+    ) -> Result<Response<pb::AppendResponse>, Status> {
+        let msg =
+            append_message(req.into_inner()).map_err(|err| status(&err))?;
+        let pos = self.db.put_message(msg).await.map_err(|err| status(&err))?;
+        Ok(Response::new(pb::AppendResponse { position: Some(pos.into()) }))
+    }
+
+    async fn fetch(
+        &self,
+        req: Request<pb::ReadRequest>,
+    ) -> Result<Response<pb::Page>, Status> {
+        let cursor = read_cursor(req.get_ref()).map_err(|err| status(&err))?;
+        let page =
+            self.db.fetch_page(cursor).await.map_err(|err| status(&err))?;
+        let messages = page
+            .messages
+            .into_iter()
+            .map(|msg| msg.map(pb::Message::from))
+            .collect::<Result<_, Error>>()
+            .map_err(|err| status(&err))?;
+        let cursor = page.cursor.to_string();
+        Ok(Response::new(pb::Page { messages, cursor }))
+    }
+
+    type ReadStream = Messages;
+
+    async fn read(
+        &self,
+        req: Request<pb::ReadRequest>,
+    ) -> Result<Response<Self::ReadStream>, Status> {
+        let cursor = read_cursor(req.get_ref()).map_err(|err| status(&err))?;
+        Ok(Response::new(messages(self.db.stream_messages(cursor))))
+    }
+
+    type SubscribeStream = Messages;
+
+    async fn subscribe(
+        &self,
+        req: Request<pb::SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let req = req.into_inner();
+        let filter = subscribe_filter(&req).map_err(|err| status(&err))?;
+        let stream = self.db.subscribe(req.from_global, filter);
+        Ok(Response::new(messages(stream)))
+    }
+
+    async fn get_stream_info(
+        &self,
+        req: Request<pb::StreamInfoRequest>,
+    ) -> Result<Response<pb::StreamInfo>, Status> {
+        let stream = &req.get_ref().stream_name;
+        match self.db.stream_info(stream).await {
+            Ok(Some(info)) => Ok(Response::new(info.into())),
+            Ok(None) => Err(Status::not_found(format!("no stream {stream}"))),
+            Err(err) => Err(status(&err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+    use futures::TryStreamExt;
+    use ident::Id;
+    use mess_db::{
+        rocks::{db::DB, storage::RocksStorage},
+        write::ExpectedVersion,
+        StreamPos,
+    };
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{transport::Channel, Code};
+
+    use crate::{
+        pb::message_store_client::MessageStoreClient, status::CURRENT_POSITION,
+    };
+
+    async fn serve() -> (MessageStoreClient<Channel>, ActorHandle) {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        let db = ActorHandle::new(RocksStorage::new(DB::new(path).unwrap()))
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = MessService::new(db.clone()).into_server();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let client = MessageStoreClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+        (client, db)
+    }
+
+    fn write(
+        stream: &str,
+        n: u8,
+        version: ExpectedVersion,
+    ) -> pb::WriteMessage {
+        pb::WriteMessage {
+            stream_name: stream.into(),
+            message_type: "Deposited".into(),
+            data: vec![n],
+            expected_version: Some(version.into()),
+            ..Default::default()
+        }
+    }
+
+    async fn append(
+        client: &mut MessageStoreClient<Channel>,
+        message: pb::WriteMessage,
+    ) -> Result<pb::Position, Status> {
+        let req = pb::AppendRequest { message: Some(message) };
+        Ok(client.append(req).await?.into_inner().position.unwrap())
+    }
+
+    #[tokio::test]
+    async fn it_appends_messages_and_reads_them_back() {
+        let (mut client, _db) = serve().await;
+        for n in 0..3 {
+            let msg = write("account-1", n, ExpectedVersion::Any);
+            let pos = append(&mut client, msg).await.unwrap();
+            assert!(pos.stream_position.unwrap().position == u64::from(n));
+        }
+
+        let req = pb::ReadRequest {
+            stream: Some("account-1".into()),
+            from_stream: Some(StreamPos::Sequential(1).into()),
+            ..Default::default()
+        };
+        let page = client.fetch(req).await.unwrap().into_inner();
+        let data: Vec<_> = page.messages.iter().map(|m| m.data[0]).collect();
+        assert!(data == [1, 2]);
+        assert!(!page.cursor.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_conflict_reports_the_current_position() {
+        let (mut client, _db) = serve().await;
+        let msg = write("account-1", 0, ExpectedVersion::NoStream);
+        append(&mut client, msg).await.unwrap();
+        let msg = write("account-1", 1, ExpectedVersion::NoStream);
+        let status = append(&mut client, msg).await.unwrap_err();
+        assert!(status.code() == Code::Aborted);
+        assert!(status.metadata().get(CURRENT_POSITION).unwrap() == "0");
+
+        let req = pb::StreamInfoRequest { stream_name: "account-1".into() };
+        let info = client.get_stream_info(req).await.unwrap().into_inner();
+        assert!(info.count == 1);
+    }
+
+    #[tokio::test]
+    async fn reads_stream_every_page() {
+        let (mut client, _db) = serve().await;
+        for n in 0..5 {
+            let msg = write(&format!("account-{n}"), n, ExpectedVersion::Any);
+            append(&mut client, msg).await.unwrap();
+        }
+        let req = pb::ReadRequest {
+            category: Some("account".into()),
+            limit: 2,
+            ..Default::default()
+        };
+        let messages: Vec<_> = client
+            .read(req)
+            .await
+            .unwrap()
+            .into_inner()
+            .try_collect()
+            .await
+            .unwrap();
+        let data: Vec<_> = messages.iter().map(|m| m.data[0]).collect();
+        assert!(data == [0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_new_messages() {
+        let (mut client, _db) = serve().await;
+        append(&mut client, write("account-1", 0, ExpectedVersion::Any))
+            .await
+            .unwrap();
+        let req = pb::SubscribeRequest {
+            stream: Some("account-1".into()),
+            ..Default::default()
+        };
+        let mut sub = client.subscribe(req).await.unwrap().into_inner();
+        let first = sub.message().await.unwrap().unwrap();
+        assert!(first.data == [0]);
+
+        append(&mut client, write("account-1", 1, ExpectedVersion::Any))
+            .await
+            .unwrap();
+        let second = sub.message().await.unwrap().unwrap();
+        assert!(second.data == [1]);
+    }
+
+    #[tokio::test]
+    async fn an_append_without_a_message_is_an_invalid_argument() {
+        let (mut client, _db) = serve().await;
+        let req = pb::AppendRequest { message: None };
+        let status = client.append(req).await.unwrap_err();
+        assert!(status.code() == Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn invalid_reads_are_invalid_arguments() {
+        let (mut client, _db) = serve().await;
+        let req = pb::ReadRequest {
+            from_stream: Some(StreamPos::Sequential(1).into()),
+            ..Default::default()
+        };
+        let status = client.fetch(req).await.unwrap_err();
+        assert!(status.code() == Code::InvalidArgument);
+
+        let req = pb::StreamInfoRequest { stream_name: "none-1".into() };
+        let status = client.get_stream_info(req).await.unwrap_err();
+        assert!(status.code() == Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn a_stopped_store_is_unavailable() {
+        let (mut client, db) = serve().await;
+        db.shutdown().await.unwrap();
+        let status = append(&mut client, write("a-1", 0, ExpectedVersion::Any))
+            .await
+            .unwrap_err();
+        assert!(status.code() == Code::Unavailable);
+    }
+}
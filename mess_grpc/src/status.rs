@@ -0,0 +1,61 @@
+//! How store errors are answered.
+
+use mess_db::error::Error;
+use tonic::{Code, Status};
+
+/// Metadata on an `ABORTED` status with the position of the stream's last
+/// message. It's absent when the stream has none.
+pub const CURRENT_POSITION: &str = "mess-current-position";
+
+#[must_use]
+pub const fn code(err: &Error) -> Code {
+    match err {
+        // The write raced another one, so it's for the caller to re-read
+        // the stream and decide whether to write again.
+        Error::WrongStreamPosition { .. } => Code::Aborted,
+        Error::InvalidRead(_)
+        | Error::InvalidConsumerGroup { .. }
+        | Error::UnsupportedRelaxed { .. }
+        | Error::DeserError(_) => Code::InvalidArgument,
+        Error::IdInUse { .. } => Code::AlreadyExists,
+        Error::Cancelled | Error::Unavailable(_) => Code::Unavailable,
+        Error::Timeout { .. } => Code::DeadlineExceeded,
+        _ => Code::Internal,
+    }
+}
+
+/// Returns the status to answer a failed call with.
+#[must_use]
+pub fn status(err: &Error) -> Status {
+    let mut status = Status::new(code(err), err.to_string());
+    if let Error::WrongStreamPosition { got: Some(got), .. } = err {
+        status.metadata_mut().insert(CURRENT_POSITION, got.position().into());
+    }
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+    use mess_db::{write::ExpectedVersion, StreamPos};
+
+    #[test]
+    fn a_conflict_reports_the_current_position() {
+        let err = Error::WrongStreamPosition {
+            stream: "account-1".into(),
+            expected: ExpectedVersion::NoStream,
+            got: Some(StreamPos::Sequential(4)),
+        };
+        let status = status(&err);
+        assert!(status.code() == Code::Aborted);
+        assert!(status.metadata().get(CURRENT_POSITION).unwrap() == "4");
+    }
+
+    #[test]
+    fn a_cancelled_store_is_unavailable() {
+        assert!(code(&Error::Cancelled) == Code::Unavailable);
+        assert!(code(&Error::InvalidRead("x")) == Code::InvalidArgument);
+        assert!(code(&Error::SendFailed) == Code::Internal);
+    }
+}
@@ -0,0 +1,577 @@
+//! The HTTP routes. Every route needs a bearer token.
+//!
+//! | Route                         | Does                                  |
+//! |-------------------------------|---------------------------------------|
+//! | `POST /streams/{stream}`      | appends an [`AppendRequest`]          |
+//! | `GET /streams/{stream}`       | reads a stream from a stream position |
+//! | `GET /streams/{stream}/info`  | summarizes a stream                   |
+//! | `GET /categories/{category}`  | reads a category from a global one    |
+//! | `GET /messages`               | reads the global log                  |
+//! | `GET /subscribe`              | streams messages as server-sent events|
+//! | `GET /metrics`                | renders the metrics, when configured  |
+//!
+//! Reads take `from`, `limit`, `backwards` and comma-separated `types`, and
+//! answer with a [`PageJson`] whose `cursor` continues the read when passed
+//! back on its own.
+
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive},
+        Sse,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::{Stream, StreamExt};
+use ident::Id;
+use mess_db::{
+    error::Error as DbError,
+    metrics::prometheus::PrometheusHandle,
+    read::{Cursor, GetMessages, IntoCursor},
+    svc::{ActorHandle, SubscribeFilter},
+    Position, StreamPos,
+};
+use quick_cache::sync::Cache;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    auth::require_token,
+    config::Config,
+    error::{Error, Result},
+    json::{
+        AppendRequest, MessageJson, PageJson, PositionJson, StreamInfoJson,
+    },
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    db: ActorHandle,
+    pub(crate) tokens: Arc<[String]>,
+    // The streams and positions of recently appended messages which came
+    // with an id.
+    appended: Arc<Cache<Id, (String, Position)>>,
+    // Renders the installed recorder's metrics, if there is one.
+    metrics: Option<PrometheusHandle>,
+}
+
+impl AppState {
+    #[must_use]
+    pub fn new(db: ActorHandle, config: &Config) -> Self {
+        Self {
+            db,
+            tokens: config.auth.tokens.clone().into(),
+            appended: Arc::new(Cache::new(config.idempotency_cache.max(1))),
+            metrics: None,
+        }
+    }
+
+    /// Serves what `handle`'s recorder recorded on `GET /metrics`.
+    #[must_use]
+    pub fn with_metrics(mut self, handle: PrometheusHandle) -> Self {
+        self.metrics = Some(handle);
+        self
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/streams/{stream}", post(append).get(read_stream))
+        .route("/streams/{stream}/info", get(stream_info))
+        .route("/categories/{category}", get(read_category))
+        .route("/messages", get(read_all))
+        .route("/subscribe", get(subscribe))
+        .route("/metrics", get(metrics))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// Renders the metrics in the Prometheus text format, or answers `404 Not
+/// Found` when the server isn't recording them.
+async fn metrics(
+    State(state): State<AppState>,
+) -> Result<([(header::HeaderName, &'static str); 1], String)> {
+    let handle = state.metrics.as_ref().ok_or(Error::NotFound)?;
+    let text = handle.render();
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text))
+}
+
+/// Answers `201 Created` with the new message's position, or `200 OK` with
+/// the original one when an append with the same id was already made. The
+/// store keeps the id too, so a retry the cache missed isn't written twice,
+/// and an id already used in another stream is answered `409 Conflict`.
+async fn append(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+    Json(req): Json<AppendRequest>,
+) -> Result<(StatusCode, Json<PositionJson>)> {
+    let retryable = req.id.is_some();
+    let msg = req.into_message(&stream)?;
+    if !retryable {
+        let pos = state.db.put_message(msg).await?;
+        return Ok((StatusCode::CREATED, Json(pos.into())));
+    }
+    let id = msg.id;
+    // Concurrent appends with the same id wait on the guard of the first.
+    let guard = match state.appended.get_value_or_guard_async(&id).await {
+        Ok((original, pos)) if original == stream => {
+            return Ok((StatusCode::OK, Json(pos.into())));
+        }
+        Ok((original, _)) => {
+            return Err(DbError::IdInUse {
+                id: id.to_string(),
+                stream: original,
+            }
+            .into());
+        }
+        Err(guard) => guard,
+    };
+    let pos = state.db.put_message(msg).await?;
+    let _ = guard.insert((stream, pos));
+    Ok((StatusCode::CREATED, Json(pos.into())))
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReadQuery {
+    /// Continues the read a page ended, and can't be combined with the
+    /// other parameters.
+    cursor: Option<String>,
+    /// The first position to read: a stream position for stream reads and
+    /// a global one otherwise. With `backwards`, the last one.
+    from: Option<u64>,
+    limit: Option<usize>,
+    #[serde(default)]
+    backwards: bool,
+    types: Option<String>,
+}
+
+impl ReadQuery {
+    /// Returns the cursor to resume, if the query is for a next page.
+    fn cursor(&self) -> Result<Option<Cursor>> {
+        let Some(token) = &self.cursor else {
+            return Ok(None);
+        };
+        if self.from.is_some()
+            || self.limit.is_some()
+            || self.backwards
+            || self.types.is_some()
+        {
+            return Err(Error::BadRequest(
+                "cursor can't be combined with other parameters".into(),
+            ));
+        }
+        Ok(Some(token.parse()?))
+    }
+
+    /// Applies the parameters every read shares.
+    fn apply<S, G, P>(
+        &self,
+        read: GetMessages<S, G, P>,
+    ) -> GetMessages<S, G, P> {
+        let read = match self.limit {
+            Some(limit) => read.with_limit(limit),
+            None => read,
+        };
+        let read = match &self.types {
+            Some(types) => read.of_types(&split(types)),
+            None => read,
+        };
+        if self.backwards {
+            read.backwards()
+        } else {
+            read
+        }
+    }
+}
+
+fn split(list: &str) -> Vec<&str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty()).collect()
+}
+
+async fn page(
+    state: &AppState,
+    read: impl IntoCursor,
+) -> Result<Json<PageJson>> {
+    let page = state.db.fetch_page(read).await?;
+    let messages = page
+        .messages
+        .into_iter()
+        .map(|msg| msg.map(MessageJson::from))
+        .collect::<mess_db::error::Result<_>>()?;
+    Ok(Json(PageJson { messages, cursor: page.cursor.to_string() }))
+}
+
+async fn read_stream(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+    Query(query): Query<ReadQuery>,
+) -> Result<Json<PageJson>> {
+    if let Some(cursor) = query.cursor()? {
+        return page(&state, GetMessages::resume(cursor)).await;
+    }
+    let read = query.apply(GetMessages::default().in_stream(&stream));
+    match query.from {
+        Some(pos) => {
+            let read = read.from_stream_pos(StreamPos::Sequential(pos));
+            page(&state, read).await
+        }
+        None => page(&state, read).await,
+    }
+}
+
+async fn read_category(
+    State(state): State<AppState>,
+    Path(category): Path<String>,
+    Query(query): Query<ReadQuery>,
+) -> Result<Json<PageJson>> {
+    if let Some(cursor) = query.cursor()? {
+        return page(&state, GetMessages::resume(cursor)).await;
+    }
+    let read = query.apply(GetMessages::default().in_category(&category));
+    match query.from {
+        Some(pos) => page(&state, read.from_global(pos)).await,
+        None => page(&state, read).await,
+    }
+}
+
+async fn read_all(
+    State(state): State<AppState>,
+    Query(query): Query<ReadQuery>,
+) -> Result<Json<PageJson>> {
+    if let Some(cursor) = query.cursor()? {
+        return page(&state, GetMessages::resume(cursor)).await;
+    }
+    let read = query.apply(GetMessages::default());
+    match query.from {
+        Some(pos) => page(&state, read.from_global(pos)).await,
+        None => page(&state, read).await,
+    }
+}
+
+async fn stream_info(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+) -> Result<Json<StreamInfoJson>> {
+    let info = state.db.stream_info(&stream).await?.ok_or(Error::NotFound)?;
+    Ok(Json(info.into()))
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubscribeQuery {
+    /// The first global position to send. Without it, a reconnecting
+    /// client continues after its `Last-Event-ID`, and a new one starts at
+    /// the beginning.
+    from: Option<u64>,
+    stream: Option<String>,
+    /// One or more comma-separated categories.
+    category: Option<String>,
+    types: Option<String>,
+}
+
+impl SubscribeQuery {
+    fn filter(&self) -> Result<SubscribeFilter> {
+        let filter = match (&self.stream, &self.category) {
+            (Some(_), Some(_)) => {
+                return Err(Error::BadRequest(
+                    "subscribe to a stream or to categories, not both".into(),
+                ))
+            }
+            (Some(stream), None) => SubscribeFilter::in_stream(stream),
+            (None, Some(categories)) => {
+                SubscribeFilter::in_categories(&split(categories))
+            }
+            (None, None) => SubscribeFilter::all(),
+        };
+        Ok(match &self.types {
+            Some(types) => filter.of_types(&split(types)),
+            None => filter,
+        })
+    }
+}
+
+/// Sends each message as a `message` event whose id is its global position.
+/// A failure is sent as an `error` event, after which the stream ends.
+async fn subscribe(
+    State(state): State<AppState>,
+    Query(query): Query<SubscribeQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = core::result::Result<Event, Infallible>>>> {
+    let filter = query.filter()?;
+    let last_event = headers
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok()?.parse::<u64>().ok());
+    let from = query.from.or(last_event.map(|id| id + 1)).unwrap_or(0);
+    let events = state.db.subscribe(from, filter).map(|msg| {
+        Ok(match msg {
+            Ok(msg) => Event::default()
+                .id(msg.global_position.to_string())
+                .event("message")
+                .json_data(MessageJson::from(msg))
+                .expect("messages serialize to JSON"),
+            Err(err) => Event::default()
+                .event("error")
+                .data(json!({ "error": err.to_string() }).to_string()),
+        })
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+    use axum::{
+        body::Body,
+        http::{header, Request},
+    };
+    use http_body_util::BodyExt;
+    use mess_db::rocks::{db::DB, storage::RocksStorage};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "secret";
+
+    fn state() -> AppState {
+        let path = std::env::temp_dir().join(Id::new().to_string());
+        let db = ActorHandle::new(RocksStorage::new(DB::new(path).unwrap()))
+            .unwrap();
+        let config: Config = format!(
+            r#"
+            listen = "127.0.0.1:0"
+            [storage]
+            backend = "rocksdb"
+            path = "unused"
+            [auth]
+            tokens = ["{TOKEN}"]
+            "#
+        )
+        .parse()
+        .unwrap();
+        AppState::new(db, &config)
+    }
+
+    fn app() -> Router {
+        router(state())
+    }
+
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = body.map_or_else(Body::empty, |b| Body::from(b.to_string()));
+        let resp = app.clone().oneshot(req.body(body).unwrap()).await.unwrap();
+        let status = resp.status();
+        let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+        let json = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, json)
+    }
+
+    async fn append(
+        app: &Router,
+        stream: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        call(app, "POST", &format!("/streams/{stream}"), Some(body)).await
+    }
+
+    #[tokio::test]
+    async fn it_appends_and_reads_a_stream() {
+        let app = app();
+        for n in 0..3 {
+            let body = json!({"type": "Deposited", "data": {"n": n}});
+            let (status, pos) = append(&app, "account-1", body).await;
+            assert!(status == StatusCode::CREATED);
+            assert!(pos["stream_position"] == n);
+        }
+        let (status, page) =
+            call(&app, "GET", "/streams/account-1?from=1", None).await;
+        assert!(status == StatusCode::OK);
+        let messages = page["messages"].as_array().unwrap();
+        assert!(messages.len() == 2);
+        assert!(messages[0]["data"] == json!({"n": 1}));
+        assert!(messages[0]["type"] == "Deposited");
+
+        let (status, info) =
+            call(&app, "GET", "/streams/account-1/info", None).await;
+        assert!(status == StatusCode::OK);
+        assert!(info["position"] == 2);
+        assert!(info["count"] == 3);
+    }
+
+    #[tokio::test]
+    async fn a_conflicting_append_reports_the_current_position() {
+        let app = app();
+        append(&app, "account-1", json!({"type": "Opened", "data": {}})).await;
+        let body = json!({"type": "Opened", "data": {}, "expected_version": "no_stream"});
+        let (status, err) = append(&app, "account-1", body).await;
+        assert!(status == StatusCode::CONFLICT);
+        assert!(err["current"] == 0);
+    }
+
+    #[tokio::test]
+    async fn a_retried_append_returns_the_original_position() {
+        let app = app();
+        let body =
+            json!({"id": Id::new().to_string(), "type": "Opened", "data": {}});
+        let (first, pos) = append(&app, "account-1", body.clone()).await;
+        let (retry, again) = append(&app, "account-1", body).await;
+        assert!(first == StatusCode::CREATED);
+        assert!(retry == StatusCode::OK);
+        assert!(pos == again);
+        let (_, info) =
+            call(&app, "GET", "/streams/account-1/info", None).await;
+        assert!(info["count"] == 1);
+    }
+
+    #[tokio::test]
+    async fn an_id_used_in_another_stream_conflicts() {
+        let app = app();
+        let body =
+            json!({"id": Id::new().to_string(), "type": "Opened", "data": {}});
+        let (first, _) = append(&app, "account-1", body.clone()).await;
+        let (other, _) = append(&app, "account-2", body).await;
+        assert!(first == StatusCode::CREATED);
+        assert!(other == StatusCode::CONFLICT);
+        let (missing, _) =
+            call(&app, "GET", "/streams/account-2/info", None).await;
+        assert!(missing == StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn it_needs_a_token() {
+        let app = app();
+        let req = Request::get("/messages").body(Body::empty()).unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert!(resp.status() == StatusCode::UNAUTHORIZED);
+        assert!(resp.headers()[header::WWW_AUTHENTICATE] == "Bearer");
+
+        let req = Request::get("/messages")
+            .header(header::AUTHORIZATION, "Bearer wrong")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert!(resp.status() == StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn binary_data_round_trips_as_base64() {
+        let app = app();
+        let body = json!({"type": "Blob", "data_base64": "/wABAg=="});
+        append(&app, "blob-1", body).await;
+        let (_, page) = call(&app, "GET", "/streams/blob-1", None).await;
+        assert!(page["messages"][0]["data_base64"] == "/wABAg==");
+        assert!(page["messages"][0].get("data").is_none());
+    }
+
+    #[tokio::test]
+    async fn category_reads_page_with_the_cursor() {
+        let app = app();
+        for stream in ["account-1", "other-1", "account-2", "account-1"] {
+            append(&app, stream, json!({"type": "X", "data": {}})).await;
+        }
+        let (_, page) =
+            call(&app, "GET", "/categories/account?limit=2", None).await;
+        let globals: Vec<_> = page["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|msg| msg["global_position"].as_u64().unwrap())
+            .collect();
+        assert!(globals == [1, 3]);
+
+        let cursor = page["cursor"].as_str().unwrap();
+        let uri = format!("/categories/account?cursor={cursor}");
+        let (status, page) = call(&app, "GET", &uri, None).await;
+        assert!(status == StatusCode::OK);
+        assert!(page["messages"].as_array().unwrap().len() == 1);
+        assert!(page["messages"][0]["global_position"] == 4);
+
+        let uri = format!("/messages?cursor={cursor}&limit=1");
+        let (status, _) = call(&app, "GET", &uri, None).await;
+        assert!(status == StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn the_global_log_reads_backwards() {
+        let app = app();
+        for n in 0..3 {
+            append(&app, "account-1", json!({"type": "X", "data": n})).await;
+        }
+        let (_, page) =
+            call(&app, "GET", "/messages?backwards=true&limit=2", None).await;
+        let data: Vec<_> = page["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|msg| msg["data"].clone())
+            .collect();
+        assert!(data == [json!(2), json!(1)]);
+    }
+
+    #[tokio::test]
+    async fn info_on_a_missing_stream_is_not_found() {
+        let app = app();
+        let (status, err) =
+            call(&app, "GET", "/streams/none-1/info", None).await;
+        assert!(status == StatusCode::NOT_FOUND);
+        assert!(err["error"] == "not found");
+    }
+
+    #[tokio::test]
+    async fn subscribers_resume_after_the_last_event() {
+        let app = app();
+        for n in 0..3 {
+            append(&app, "account-1", json!({"type": "X", "data": n})).await;
+        }
+        let req = Request::get("/subscribe?category=account")
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .header("last-event-id", "1")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert!(resp.status() == StatusCode::OK);
+        let mut body = resp.into_body();
+        let frame = body.frame().await.unwrap().unwrap();
+        let text =
+            String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap();
+        assert!(text.contains("event: message"));
+        assert!(text.contains("id: 2\n"));
+    }
+
+    #[tokio::test]
+    async fn metrics_are_served_when_recorded() {
+        let (status, _) = call(&app(), "GET", "/metrics", None).await;
+        assert!(status == StatusCode::NOT_FOUND);
+
+        let recorder =
+            mess_db::metrics::prometheus::builder().unwrap().build_recorder();
+        let app = router(state().with_metrics(recorder.handle()));
+        let req = Request::builder()
+            .uri("/metrics")
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert!(resp.status() == StatusCode::OK);
+        let content_type = &resp.headers()[header::CONTENT_TYPE];
+        assert!(content_type.to_str().unwrap().starts_with("text/plain"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::Response,
+};
+use tonic::{service::Interceptor, Status};
+
+use crate::{
+    api::AppState,
+    error::{Error, Result},
+};
+
+/// Compares in time which depends only on the lengths, so a token can't be
+/// guessed byte by byte.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Returns whether an `Authorization` header value has one of the tokens.
+fn allowed(tokens: &[String], authorization: Option<&str>) -> bool {
+    let token = authorization.and_then(|value| value.strip_prefix("Bearer "));
+    token.is_some_and(|token| {
+        tokens.iter().any(|t| same(t.as_bytes(), token.as_bytes()))
+    })
+}
+
+/// Lets the request through if it has an `Authorization: Bearer` header
+/// with one of the configured tokens.
+pub async fn require_token(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response> {
+    let authorization =
+        req.headers().get(AUTHORIZATION).and_then(|value| value.to_str().ok());
+    if !allowed(&state.tokens, authorization) {
+        return Err(Error::Unauthorized);
+    }
+    Ok(next.run(req).await)
+}
+
+/// Lets gRPC calls through on the same terms as [`require_token`].
+#[derive(Clone)]
+pub struct GrpcAuth {
+    tokens: Arc<[String]>,
+}
+
+impl GrpcAuth {
+    #[must_use]
+    pub fn new(state: &AppState) -> Self {
+        Self { tokens: state.tokens.clone() }
+    }
+}
+
+impl Interceptor for GrpcAuth {
+    fn call(
+        &mut self,
+        req: tonic::Request<()>,
+    ) -> core::result::Result<tonic::Request<()>, Status> {
+        let authorization = req
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        match allowed(&self.tokens, authorization) {
+            true => Ok(req),
+            false => {
+                Err(Status::unauthenticated(Error::Unauthorized.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn only_equal_tokens_are_the_same() {
+        assert!(same(b"secret", b"secret"));
+        assert!(!same(b"secret", b"secreT"));
+        assert!(!same(b"secret", b"secret2"));
+        assert!(!same(b"", b"x"));
+    }
+
+    #[test]
+    fn grpc_calls_need_a_bearer_token() {
+        let mut auth = GrpcAuth { tokens: vec!["secret".to_owned()].into() };
+        assert!(auth.call(tonic::Request::new(())).is_err());
+        let mut req = tonic::Request::new(());
+        req.metadata_mut()
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(auth.call(req).is_ok());
+        let mut req = tonic::Request::new(());
+        req.metadata_mut().insert("authorization", "secret".parse().unwrap());
+        assert!(auth.call(req).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! The server's TOML configuration.
+//!
+//! ```toml
+//! listen = "127.0.0.1:8080"
+//! grpc_listen = "127.0.0.1:50051" # optional
+//! metrics = true # optional, serves GET /metrics
+//!
+//! [storage]
+//! backend = "rocksdb" # or "sqlite", with the `rusqlite` feature
+//! path = "data/mess"
+//!
+//! [auth]
+//! tokens = ["change-me"]
+//!
+//! [ipc] # optional, on Unix
+//! path = "/run/mess/mess.sock"
+//! mode = 0o660
+//! ```
+
+use std::{net::SocketAddr, path::PathBuf, str::FromStr};
+
+use mess_db::svc::ActorHandle;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// How many appended message ids are remembered for retries.
+pub const DEFAULT_IDEMPOTENCY_CACHE: usize = 100_000;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub listen: SocketAddr,
+    /// Where to serve gRPC, if anywhere. It takes the same tokens.
+    pub grpc_listen: Option<SocketAddr>,
+    pub storage: StorageConfig,
+    pub auth: AuthConfig,
+    /// Where to serve processes on this host over a Unix domain socket, if
+    /// anywhere.
+    pub ipc: Option<IpcConfig>,
+    /// How many appended message ids are remembered, so that a retried
+    /// append returns the original position instead of writing again.
+    #[serde(default = "default_idempotency_cache")]
+    pub idempotency_cache: usize,
+    /// Whether to record metrics and serve them in the Prometheus text
+    /// format on `GET /metrics`.
+    #[serde(default)]
+    pub metrics: bool,
+}
+
+const fn default_idempotency_cache() -> usize {
+    DEFAULT_IDEMPOTENCY_CACHE
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Rocksdb,
+    Sqlite,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: Backend,
+    pub path: PathBuf,
+    /// How many threads answer reads.
+    #[serde(default = "default_readers")]
+    pub readers: usize,
+}
+
+const fn default_readers() -> usize {
+    mess_db::svc::DEFAULT_READERS
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// The bearer tokens which are allowed in. Every request needs one.
+    pub tokens: Vec<String>,
+}
+
+/// The socket which local processes reach the store on. It takes no
+/// tokens: who may connect is up to the socket file's mode.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IpcConfig {
+    pub path: PathBuf,
+    #[serde(default = "default_ipc_mode")]
+    pub mode: u32,
+}
+
+/// Lets the owner and their group connect.
+const fn default_ipc_mode() -> u32 {
+    0o660
+}
+
+impl Config {
+    /// Reads the configuration from a TOML file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|err| {
+            Error::Config(format!("reading {}: {err}", path.display()))
+        })?;
+        text.parse()
+    }
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let config: Self =
+            toml::from_str(s).map_err(|err| Error::Config(err.to_string()))?;
+        if config.auth.tokens.is_empty() {
+            return Err(Error::Config("auth.tokens has no tokens".into()));
+        }
+        // An empty token would let in requests with an empty bearer token.
+        if config.auth.tokens.iter().any(String::is_empty) {
+            return Err(Error::Config("auth.tokens has an empty token".into()));
+        }
+        Ok(config)
+    }
+}
+
+impl StorageConfig {
+    /// Opens the store and starts the service actor on it.
+    pub fn open(&self) -> Result<ActorHandle> {
+        match self.backend {
+            Backend::Rocksdb => {
+                use mess_db::rocks::{db::DB, storage::RocksStorage};
+
+                let storage = RocksStorage::new(DB::new(&self.path)?);
+                Ok(ActorHandle::with_readers(storage, self.readers)?)
+            }
+            #[cfg(feature = "rusqlite")]
+            Backend::Sqlite => {
+                use mess_db::rusqlite::storage::SqliteStorage;
+
+                let storage = SqliteStorage::open(&self.path)?;
+                Ok(ActorHandle::with_readers(storage, self.readers)?)
+            }
+            #[cfg(not(feature = "rusqlite"))]
+            Backend::Sqlite => Err(Error::Config(
+                "the sqlite backend needs the rusqlite feature".into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+
+    const CONFIG: &str = r#"
+        listen = "127.0.0.1:8080"
+
+        [storage]
+        backend = "sqlite"
+        path = "mess.db"
+
+        [auth]
+        tokens = ["secret"]
+    "#;
+
+    #[test]
+    fn it_fills_in_the_defaults() {
+        let config: Config = CONFIG.parse().unwrap();
+        assert!(config.listen.port() == 8080);
+        assert!(config.grpc_listen.is_none());
+        assert!(config.storage.backend == Backend::Sqlite);
+        assert!(config.storage.readers == mess_db::svc::DEFAULT_READERS);
+        assert!(config.idempotency_cache == DEFAULT_IDEMPOTENCY_CACHE);
+        assert!(config.auth.tokens == ["secret"]);
+        assert!(config.ipc.is_none());
+        assert!(!config.metrics);
+    }
+
+    #[test]
+    fn the_socket_mode_is_octal() {
+        let config =
+            format!("{CONFIG}\n[ipc]\npath = \"mess.sock\"\nmode = 0o600");
+        let ipc = config.parse::<Config>().unwrap().ipc.unwrap();
+        assert!(ipc.mode == 0o600);
+        let config = format!("{CONFIG}\n[ipc]\npath = \"mess.sock\"");
+        let ipc = config.parse::<Config>().unwrap().ipc.unwrap();
+        assert!(ipc.mode == 0o660);
+    }
+
+    #[test]
+    fn it_needs_a_token() {
+        let config = CONFIG.replace(r#"["secret"]"#, "[]");
+        assert!(let Err(Error::Config(_)) = config.parse::<Config>());
+        let config = CONFIG.replace(r#"["secret"]"#, r#"["", "secret"]"#);
+        assert!(let Err(Error::Config(_)) = config.parse::<Config>());
+    }
+
+    #[test]
+    fn it_rejects_unknown_keys() {
+        let config = CONFIG.replace("[auth]", "[auth]\nuser = \"x\"");
+        assert!(let Err(Error::Config(_)) = config.parse::<Config>());
+    }
+}
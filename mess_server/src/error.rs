@@ -0,0 +1,83 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use mess_db::error::Error as DbError;
+use serde_json::json;
+use thiserror::Error;
+use tracing::error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("invalid config: {0}")]
+    Config(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("bad request: {0}")]
+    BadRequest(String),
+
+    #[error("missing or unknown bearer token")]
+    Unauthorized,
+
+    #[error("not found")]
+    NotFound,
+}
+
+pub type Result<T> = ::core::result::Result<T, Error>;
+
+impl Error {
+    #[must_use]
+    pub const fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_)
+            | Self::Db(
+                DbError::InvalidRead(_)
+                | DbError::DeserError(_)
+                | DbError::UnsupportedRelaxed { .. },
+            ) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Db(
+                DbError::WrongStreamPosition { .. } | DbError::IdInUse { .. },
+            ) => StatusCode::CONFLICT,
+            Self::Db(DbError::Cancelled | DbError::Unavailable(_)) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::Config(_) | Self::Io(_) | Self::Db(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+/// Errors are answered as `{"error": "..."}`. A conflicting append also
+/// reports the stream's current position, or `null` if it has none.
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            error!(err = ?self, "request failed");
+        }
+        let body = match &self {
+            Self::Db(DbError::WrongStreamPosition { got, .. }) => json!({
+                "error": self.to_string(),
+                "current": got.map(|pos| pos.position()),
+            }),
+            _ => json!({ "error": self.to_string() }),
+        };
+        let mut resp = (status, Json(body)).into_response();
+        if let Self::Unauthorized = self {
+            resp.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Bearer"),
+            );
+        }
+        resp
+    }
+}
@@ -0,0 +1,274 @@
+//! The JSON bodies of the API.
+//!
+//! Message data and metadata are bytes to the store. A request gives them
+//! either as any JSON value under `data` and `metadata`, which is stored as
+//! its text, or as base64 under `data_base64` and `metadata_base64`.
+//! Messages come back the same way: as JSON when the stored bytes are JSON,
+//! and as base64 otherwise.
+
+use std::borrow::Cow;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ident::Id;
+use mess_db::{
+    write::{ExpectedVersion, WriteMessage},
+    OwnedMessage, Position, StreamInfo, StreamPos,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppendRequest {
+    /// Identifies the message. Retrying an append with the same id returns
+    /// the position it was first written at.
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub data: Option<Value>,
+    pub data_base64: Option<String>,
+    pub metadata: Option<Value>,
+    pub metadata_base64: Option<String>,
+    #[serde(default)]
+    pub expected_version: ExpectedVersionJson,
+}
+
+/// `"any"`, `"no_stream"`, `"stream_exists"` or the exact position of the
+/// stream's last message.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ExpectedVersionJson {
+    Position(u64),
+    Keyword(Keyword),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Keyword {
+    Any,
+    NoStream,
+    StreamExists,
+}
+
+impl Default for ExpectedVersionJson {
+    fn default() -> Self {
+        Self::Keyword(Keyword::Any)
+    }
+}
+
+impl From<ExpectedVersionJson> for ExpectedVersion {
+    fn from(value: ExpectedVersionJson) -> Self {
+        match value {
+            ExpectedVersionJson::Position(pos) => {
+                Self::Exact(StreamPos::Sequential(pos))
+            }
+            ExpectedVersionJson::Keyword(Keyword::Any) => Self::Any,
+            ExpectedVersionJson::Keyword(Keyword::NoStream) => Self::NoStream,
+            ExpectedVersionJson::Keyword(Keyword::StreamExists) => {
+                Self::StreamExists
+            }
+        }
+    }
+}
+
+/// Returns the bytes to store for a payload given as JSON or as base64.
+fn payload(
+    name: &str,
+    json: Option<Value>,
+    base64: Option<String>,
+) -> Result<Option<Vec<u8>>> {
+    match (json, base64) {
+        (Some(_), Some(_)) => Err(Error::BadRequest(format!(
+            "give either {name} or {name}_base64, not both"
+        ))),
+        (Some(json), None) => Ok(Some(json.to_string().into_bytes())),
+        (None, Some(base64)) => STANDARD
+            .decode(base64)
+            .map(Some)
+            .map_err(|err| Error::BadRequest(format!("{name}_base64: {err}"))),
+        (None, None) => Ok(None),
+    }
+}
+
+impl AppendRequest {
+    /// Returns the message to write to `stream_name`, with its id.
+    pub fn into_message(self, stream_name: &str) -> Result<WriteMessage<'_>> {
+        let id = match &self.id {
+            Some(id) => id
+                .parse::<Id>()
+                .map_err(|_| Error::BadRequest(format!("invalid id {id}")))?,
+            None => Id::new(),
+        };
+        let data = payload("data", self.data, self.data_base64)?
+            .ok_or_else(|| Error::BadRequest("data is required".into()))?;
+        let metadata =
+            payload("metadata", self.metadata, self.metadata_base64)?;
+        Ok(WriteMessage {
+            id,
+            stream_name: Cow::Borrowed(stream_name),
+            message_type: self.message_type.into(),
+            data: data.into(),
+            metadata: metadata.unwrap_or_default().into(),
+            expected_version: self.expected_version.into(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PositionJson {
+    pub global_position: u64,
+    pub stream_position: u64,
+}
+
+impl From<Position> for PositionJson {
+    fn from(pos: Position) -> Self {
+        Self {
+            global_position: pos.global,
+            stream_position: pos.stream.position(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct MessageJson {
+    pub global_position: u64,
+    pub stream_position: u64,
+    pub stream_name: String,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_base64: Option<String>,
+}
+
+/// Splits stored bytes into their JSON or, failing that, base64 form.
+fn encoded(bytes: &[u8]) -> (Option<Value>, Option<String>) {
+    match serde_json::from_slice(bytes) {
+        Ok(json) => (Some(json), None),
+        Err(_) => (None, Some(STANDARD.encode(bytes))),
+    }
+}
+
+impl From<OwnedMessage> for MessageJson {
+    fn from(msg: OwnedMessage) -> Self {
+        let (data, data_base64) = encoded(&msg.data);
+        let (metadata, metadata_base64) = match msg.metadata.as_deref() {
+            None | Some([]) => (None, None),
+            Some(metadata) => encoded(metadata),
+        };
+        Self {
+            global_position: msg.global_position,
+            stream_position: msg.stream_position.position(),
+            stream_name: msg.stream_name,
+            message_type: msg.message_type,
+            data,
+            data_base64,
+            metadata,
+            metadata_base64,
+        }
+    }
+}
+
+/// A page of messages. Pass `cursor` back to read the next page.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PageJson {
+    pub messages: Vec<MessageJson>,
+    pub cursor: String,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StreamInfoJson {
+    /// The position of the last message, which the next append expects.
+    pub position: u64,
+    pub count: u64,
+    pub first_global: u64,
+    pub last_global: u64,
+    /// When the first message was recorded, in seconds since the Unix
+    /// epoch.
+    pub first_time: f64,
+    pub last_time: f64,
+}
+
+impl From<StreamInfo> for StreamInfoJson {
+    fn from(info: StreamInfo) -> Self {
+        Self {
+            position: info.position.position(),
+            count: info.count,
+            first_global: info.first_global,
+            last_global: info.last_global,
+            first_time: info.first_time.to_secs_f64(),
+            last_time: info.last_time.to_secs_f64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert2::assert;
+    use serde_json::json;
+
+    fn request(body: Value) -> Result<WriteMessage<'static>> {
+        let req: AppendRequest = serde_json::from_value(body).unwrap();
+        req.into_message("account-1")
+    }
+
+    #[test]
+    fn json_data_is_stored_as_its_text() {
+        let msg = request(json!({"type": "Opened", "data": {"a": 1}})).unwrap();
+        assert!(msg.data.as_ref() == br#"{"a":1}"#);
+        assert!(msg.metadata.is_empty());
+        assert!(msg.expected_version == ExpectedVersion::Any);
+    }
+
+    #[test]
+    fn base64_data_is_decoded() {
+        let body = json!({
+            "type": "Opened",
+            "data_base64": "AAEC",
+            "expected_version": 4,
+        });
+        let msg = request(body).unwrap();
+        assert!(msg.data.as_ref() == [0, 1, 2]);
+        let expected = ExpectedVersion::Exact(StreamPos::Sequential(4));
+        assert!(msg.expected_version == expected);
+    }
+
+    #[test]
+    fn data_is_given_exactly_once() {
+        let both = json!({"type": "X", "data": 1, "data_base64": "AA=="});
+        assert!(let Err(Error::BadRequest(_)) = request(both));
+        let neither = json!({"type": "X"});
+        assert!(let Err(Error::BadRequest(_)) = request(neither));
+    }
+
+    #[test]
+    fn messages_come_back_as_json_or_base64() {
+        let msg = OwnedMessage {
+            global_position: 3,
+            stream_position: StreamPos::Sequential(1),
+            stream_name: "account-1".into(),
+            message_type: "Opened".into(),
+            data: vec![0xff, 0],
+            metadata: Some(br#"{"k":"v"}"#.to_vec()),
+        };
+        let json = serde_json::to_value(MessageJson::from(msg)).unwrap();
+        assert!(
+            json == json!({
+                "global_position": 3,
+                "stream_position": 1,
+                "stream_name": "account-1",
+                "type": "Opened",
+                "data_base64": "/wA=",
+                "metadata": {"k": "v"},
+            })
+        );
+    }
+}
@@ -0,0 +1,14 @@
+//! An HTTP front end to a mess store, for services which can't embed it.
+//!
+//! One process owns the store and serves appends, reads and subscriptions
+//! as JSON over HTTP, and over gRPC with [`mess_grpc`] when configured to.
+//! Processes on the same host can also reach it through a Unix domain
+//! socket, with [`mess_db::ipc`]. See [`api`] for the routes and [`config`] for the configuration file.
+
+pub mod api;
+pub mod auth;
+pub mod config;
+pub mod error;
+pub mod json;
+
+pub use api::{router, AppState};
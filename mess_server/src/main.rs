@@ -0,0 +1,129 @@
+use std::io::IsTerminal;
+
+use mess_grpc::{pb::message_store_server::MessageStoreServer, MessService};
+use mess_server::{auth::GrpcAuth, config::Config, router, AppState};
+use tokio::{net::TcpListener, sync::watch};
+use tokio_stream::wrappers::TcpListenerStream;
+use tracing::info;
+
+fn configure_logging() {
+    use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+    let reg =
+        tracing_subscriber::registry().with(EnvFilter::from_default_env());
+    let use_json = !std::io::stdout().is_terminal()
+        || matches!(
+            std::env::var("LOG_FMT").unwrap_or_default().as_str(),
+            "json" | "JSON"
+        );
+    match use_json {
+        true => reg.with(fmt::layer().json()).init(),
+        _ => reg.with(fmt::layer().pretty()).init(),
+    };
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("ctrl-c handler installs");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(
+            tokio::signal::unix::SignalKind::terminate(),
+        )
+        .expect("SIGTERM handler installs")
+        .recv()
+        .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+}
+
+/// Resolves once shutdown has begun.
+async fn stopped(mut stop: watch::Receiver<()>) {
+    // An error means the sender is gone, which is just as final.
+    let _ = stop.changed().await;
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    configure_logging();
+
+    let path =
+        std::env::args().nth(1).unwrap_or_else(|| "mess-server.toml".into());
+    let config = Config::load(&path)?;
+    let db = config.storage.open()?;
+    let mut state = AppState::new(db.clone(), &config);
+    if config.metrics {
+        state = state.with_metrics(mess_db::metrics::prometheus::install()?);
+        info!("serving metrics");
+    }
+    let listener = TcpListener::bind(config.listen).await?;
+    info!(addr = %config.listen, backend = ?config.storage.backend, "listening");
+
+    let (stop, stop_recv) = watch::channel(());
+    let grpc = match config.grpc_listen {
+        Some(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+            info!(%addr, "serving gRPC");
+            let service = MessageStoreServer::with_interceptor(
+                MessService::new(db.clone()),
+                GrpcAuth::new(&state),
+            );
+            let serve = tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming_shutdown(
+                    TcpListenerStream::new(listener),
+                    stopped(stop_recv.clone()),
+                );
+            Some(tokio::spawn(serve))
+        }
+        None => None,
+    };
+    let ipc = match &config.ipc {
+        #[cfg(unix)]
+        Some(ipc) => {
+            let listener = mess_db::ipc::bind(&ipc.path, ipc.mode)?;
+            info!(path = %ipc.path.display(), mode = format!("{:o}", ipc.mode), "serving ipc");
+            let path = ipc.path.clone();
+            let serve = mess_db::ipc::serve(
+                db.clone(),
+                listener,
+                stopped(stop_recv.clone()),
+            );
+            Some(tokio::spawn(async move {
+                serve.await;
+                let _ = std::fs::remove_file(path);
+            }))
+        }
+        #[cfg(not(unix))]
+        Some(_) => return Err("ipc needs Unix domain sockets".into()),
+        None => None,
+    };
+    let closing = db.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("shutting down");
+        // Ends open subscriptions, which would keep the servers waiting.
+        closing.kill();
+        let _ = stop.send(());
+    });
+
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(stopped(stop_recv))
+        .await?;
+    if let Some(grpc) = grpc {
+        grpc.await??;
+    }
+    if let Some(ipc) = ipc {
+        ipc.await?;
+    }
+    db.shutdown().await?;
+    Ok(())
+}